@@ -0,0 +1,414 @@
+//! `#[derive(ApiSchema)]` for the API's request and response types.
+//!
+//! The generated impl describes a type the way serde sees it: field and
+//! variant renames, `rename_all`, defaults, `skip_serializing_if`, `flatten`
+//! and the enum tagging modes are all honoured, so the published OpenAPI
+//! document follows the types instead of a hand-maintained copy of them.
+//! Doc comments become descriptions, and `#[schema(format = "...", example =
+//! "...")]` on a field adds what the Rust type alone can't say, such as a
+//! string being a base58 pubkey.
+//!
+//! Serde attributes that would change the wire format in ways the derive
+//! can't follow (`with`, `transparent`, `skip_serializing`, ...) are compile
+//! errors rather than silently ignored; such types implement `ApiSchema` by
+//! hand.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as Tokens;
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Expr, ExprLit, Field, Fields, Lit, LitStr, Meta, Token, Type,
+};
+
+#[proc_macro_derive(ApiSchema, attributes(schema))]
+pub fn derive_api_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+#[derive(Default)]
+struct ContainerAttrs {
+    rename_all: Option<String>,
+    tag: Option<String>,
+    content: Option<String>,
+    untagged: bool,
+    default: bool,
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<String>,
+    default: bool,
+    skip_serializing_if: bool,
+    flatten: bool,
+    skip: bool,
+    format: Option<String>,
+    example: Option<String>,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<Tokens> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "ApiSchema can't be derived for generic types",
+        ));
+    }
+    let ident = &input.ident;
+    let name = ident.to_string();
+    let container = container_attrs(&input.attrs)?;
+    let description = optional_str(doc(&input.attrs));
+
+    let body = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(_) => object(&data.fields, &container, description, None)?,
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = &fields.unnamed[0].ty;
+                quote! {
+                    crate::openapi::describe(
+                        <#ty as crate::openapi::ApiSchema>::schema(components),
+                        #description,
+                        None,
+                        None,
+                    )
+                }
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "ApiSchema supports structs with named fields and newtype structs",
+                ))
+            }
+        },
+        Data::Enum(data) => enumeration(data, &container, description)?,
+        Data::Union(_) => return Err(syn::Error::new_spanned(ident, "ApiSchema can't be derived for unions")),
+    };
+
+    Ok(quote! {
+        impl crate::openapi::ApiSchema for #ident {
+            fn schema(components: &mut crate::openapi::Components) -> serde_json::Value {
+                components.reference(#name, |components| #body)
+            }
+        }
+    })
+}
+
+/// An object schema for named `fields`, plus the internal tag if any.
+fn object(
+    fields: &Fields,
+    container: &ContainerAttrs,
+    description: Tokens,
+    tag: Option<(&str, &str)>,
+) -> syn::Result<Tokens> {
+    let tag = tag.map(|(tag, name)| {
+        quote! { object.property(#tag, true, crate::openapi::string_enum(None, &[#name])); }
+    });
+    let properties = fields.iter().map(|field| property(field, container)).collect::<syn::Result<Vec<_>>>()?;
+    Ok(quote! {{
+        let mut object = crate::openapi::Object::new(#description);
+        #tag
+        #(#properties)*
+        object.finish()
+    }})
+}
+
+fn property(field: &Field, container: &ContainerAttrs) -> syn::Result<Tokens> {
+    let attrs = field_attrs(&field.attrs)?;
+    let ty = &field.ty;
+    if attrs.skip {
+        return Ok(Tokens::new());
+    }
+    if attrs.flatten {
+        return Ok(quote! { object.flatten(<#ty as crate::openapi::ApiSchema>::schema(components)); });
+    }
+
+    let ident = field.ident.as_ref().expect("named field").to_string();
+    let ident = ident.trim_start_matches("r#");
+    let name = attrs
+        .rename
+        .unwrap_or_else(|| rename_field(ident, container.rename_all.as_deref()));
+    let required = !(is_option(ty) || attrs.default || container.default || attrs.skip_serializing_if);
+    let description = optional_str(doc(&field.attrs));
+    let format = optional_str(attrs.format);
+    let example = optional_str(attrs.example);
+    Ok(quote! {
+        object.property(
+            #name,
+            #required,
+            crate::openapi::describe(
+                <#ty as crate::openapi::ApiSchema>::schema(components),
+                #description,
+                #format,
+                #example,
+            ),
+        );
+    })
+}
+
+fn enumeration(data: &syn::DataEnum, container: &ContainerAttrs, description: Tokens) -> syn::Result<Tokens> {
+    let mut names = Vec::new();
+    let mut variants = Vec::new();
+    for variant in &data.variants {
+        let attrs = field_attrs(&variant.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+        let name = attrs
+            .rename
+            .unwrap_or_else(|| rename_variant(&variant.ident.to_string(), container.rename_all.as_deref()));
+        names.push(name);
+        variants.push(variant);
+    }
+
+    let all_unit = variants.iter().all(|variant| matches!(variant.fields, Fields::Unit));
+    if all_unit && !container.untagged && container.tag.is_none() {
+        return Ok(quote! { crate::openapi::string_enum(#description, &[#(#names),*]) });
+    }
+
+    let mut alternatives = Vec::new();
+    for (variant, name) in variants.iter().zip(&names) {
+        let content = match &variant.fields {
+            Fields::Unit => None,
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = &fields.unnamed[0].ty;
+                Some(quote! { <#ty as crate::openapi::ApiSchema>::schema(components) })
+            }
+            Fields::Named(_) => None,
+            Fields::Unnamed(_) => {
+                return Err(syn::Error::new_spanned(variant, "ApiSchema doesn't support tuple variants"));
+            }
+        };
+        let variant_description = optional_str(doc(&variant.attrs));
+        let named = matches!(variant.fields, Fields::Named(_));
+
+        let alternative = match (&container.tag, &container.content) {
+            _ if container.untagged => match (content, named) {
+                (Some(content), _) => content,
+                (None, true) => object(&variant.fields, &ContainerAttrs::default(), variant_description, None)?,
+                (None, false) => {
+                    return Err(syn::Error::new_spanned(variant, "ApiSchema doesn't support unit variants in untagged enums"));
+                }
+            },
+            (Some(tag), Some(content_name)) => {
+                let content = match (content, named) {
+                    (Some(content), _) => quote! { Some((#content_name, #content)) },
+                    (None, true) => {
+                        let inner = object(&variant.fields, &ContainerAttrs::default(), variant_description, None)?;
+                        quote! { Some((#content_name, #inner)) }
+                    }
+                    (None, false) => quote! { None },
+                };
+                quote! { crate::openapi::tagged(#tag, #name, #content) }
+            }
+            (Some(tag), None) => match (content, named) {
+                (Some(content), _) => quote! {
+                    serde_json::json!({ "allOf": [crate::openapi::tagged(#tag, #name, None), #content] })
+                },
+                (None, true) => object(
+                    &variant.fields,
+                    &ContainerAttrs::default(),
+                    variant_description,
+                    Some((tag.as_str(), name.as_str())),
+                )?,
+                (None, false) => quote! { crate::openapi::tagged(#tag, #name, None) },
+            },
+            (None, _) => match (content, named) {
+                (None, false) => quote! { crate::openapi::string_enum(None, &[#name]) },
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        variant,
+                        "ApiSchema needs `#[serde(tag)]` or `#[serde(untagged)]` on enums with data",
+                    ));
+                }
+            },
+        };
+        alternatives.push(alternative);
+    }
+    Ok(quote! { crate::openapi::one_of(#description, vec![#(#alternatives),*]) })
+}
+
+fn container_attrs(attrs: &[Attribute]) -> syn::Result<ContainerAttrs> {
+    let mut container = ContainerAttrs::default();
+    for_each_serde_meta(attrs, |meta| {
+        if meta.path.is_ident("rename_all") {
+            container.rename_all = Some(string_value(meta)?);
+        } else if meta.path.is_ident("tag") {
+            container.tag = Some(string_value(meta)?);
+        } else if meta.path.is_ident("content") {
+            container.content = Some(string_value(meta)?);
+        } else if meta.path.is_ident("untagged") {
+            container.untagged = true;
+        } else if meta.path.is_ident("default") {
+            container.default = true;
+            skip_value(meta)?;
+        } else if SHAPE_NEUTRAL.iter().any(|name| meta.path.is_ident(name)) {
+            skip_value(meta)?;
+        } else {
+            return Err(unsupported(meta));
+        }
+        Ok(())
+    })?;
+    Ok(container)
+}
+
+fn field_attrs(attrs: &[Attribute]) -> syn::Result<FieldAttrs> {
+    let mut field = FieldAttrs::default();
+    for_each_serde_meta(attrs, |meta| {
+        if meta.path.is_ident("rename") {
+            field.rename = Some(string_value(meta)?);
+        } else if meta.path.is_ident("default") {
+            field.default = true;
+            skip_value(meta)?;
+        } else if meta.path.is_ident("skip_serializing_if") {
+            field.skip_serializing_if = true;
+            skip_value(meta)?;
+        } else if meta.path.is_ident("flatten") {
+            field.flatten = true;
+        } else if meta.path.is_ident("skip") {
+            field.skip = true;
+        } else if SHAPE_NEUTRAL.iter().any(|name| meta.path.is_ident(name)) {
+            skip_value(meta)?;
+        } else {
+            return Err(unsupported(meta));
+        }
+        Ok(())
+    })?;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("schema")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("format") {
+                field.format = Some(string_value(&meta)?);
+            } else if meta.path.is_ident("example") {
+                field.example = Some(string_value(&meta)?);
+            } else {
+                return Err(meta.error("expected `format` or `example`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(field)
+}
+
+/// Serde attributes that leave the serialized shape alone. `rename` on a
+/// container only changes the name in error messages, and `alias` only adds
+/// accepted spellings on input.
+const SHAPE_NEUTRAL: &[&str] = &["deny_unknown_fields", "rename", "alias", "bound", "crate", "expecting"];
+
+fn unsupported(meta: &ParseNestedMeta) -> syn::Error {
+    let name = meta.path.get_ident().map_or_else(String::new, ToString::to_string);
+    meta.error(format!(
+        "ApiSchema doesn't understand #[serde({})]; implement ApiSchema by hand for this type",
+        name
+    ))
+}
+
+fn for_each_serde_meta(
+    attrs: &[Attribute],
+    mut visit: impl FnMut(&ParseNestedMeta) -> syn::Result<()>,
+) -> syn::Result<()> {
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| visit(&meta))?;
+    }
+    Ok(())
+}
+
+fn string_value(meta: &ParseNestedMeta) -> syn::Result<String> {
+    Ok(meta.value()?.parse::<LitStr>()?.value())
+}
+
+/// Consumes the value of a serde attribute this derive doesn't interpret.
+fn skip_value(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<Lit>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|inner| skip_value(&inner))?;
+    }
+    Ok(())
+}
+
+/// Doc comment lines joined into paragraphs.
+fn doc(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(pair) => match &pair.value {
+                Expr::Lit(ExprLit { lit: Lit::Str(text), .. }) => Some(text.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    let text = lines
+        .split(|line| line.is_empty())
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| paragraph.join(" "))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    (!text.is_empty()).then_some(text)
+}
+
+fn optional_str(value: Option<String>) -> Tokens {
+    match value {
+        Some(value) => quote! { Some(#value) },
+        None => quote! { None },
+    }
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path.qself.is_none() && path.path.segments.last().is_some_and(|last| last.ident == "Option"),
+        _ => false,
+    }
+}
+
+/// Applies a serde `rename_all` rule to a snake_case field name.
+fn rename_field(name: &str, rule: Option<&str>) -> String {
+    match rule {
+        Some("UPPERCASE") | Some("SCREAMING_SNAKE_CASE") => name.to_ascii_uppercase(),
+        Some("PascalCase") => pascal(name),
+        Some("camelCase") => {
+            let pascal = pascal(name);
+            let mut chars = pascal.chars();
+            chars.next().map_or_else(String::new, |first| first.to_ascii_lowercase().to_string() + chars.as_str())
+        }
+        Some("kebab-case") => name.replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => name.replace('_', "-").to_ascii_uppercase(),
+        _ => name.to_string(),
+    }
+}
+
+/// Applies a serde `rename_all` rule to a PascalCase variant name.
+fn rename_variant(name: &str, rule: Option<&str>) -> String {
+    let snake = || {
+        let mut snake = String::new();
+        for (i, c) in name.char_indices() {
+            if i > 0 && c.is_ascii_uppercase() {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        }
+        snake
+    };
+    match rule {
+        Some("lowercase") => name.to_ascii_lowercase(),
+        Some("UPPERCASE") => name.to_ascii_uppercase(),
+        Some("camelCase") => {
+            let mut chars = name.chars();
+            chars.next().map_or_else(String::new, |first| first.to_ascii_lowercase().to_string() + chars.as_str())
+        }
+        Some("snake_case") => snake(),
+        Some("SCREAMING_SNAKE_CASE") => snake().to_ascii_uppercase(),
+        Some("kebab-case") => snake().replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => snake().replace('_', "-").to_ascii_uppercase(),
+        _ => name.to_string(),
+    }
+}
+
+fn pascal(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or_else(String::new, |first| first.to_ascii_uppercase().to_string() + chars.as_str())
+        })
+        .collect()
+}
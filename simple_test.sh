@@ -7,8 +7,19 @@ YELLOW='\033[1;33m'
 BLUE='\033[0;34m'
 NC='\033[0m' # No Color
 
-# Base URL
-BASE_URL="http://127.0.0.1:3000"
+# Base URL, overridable from the environment
+BASE_URL="${BASE_URL:-http://127.0.0.1:3000}"
+
+# The server requires an API key with the build, sign and keygen scopes. Keys
+# are loaded at startup, so create one before starting the server:
+#   export API_KEY=$(cargo run -q -- --create-api-key smoke-test --scopes build,sign,keygen)
+# or start the server with --auth-enabled false and leave API_KEY unset.
+AUTH_HEADER=()
+if [ -n "$API_KEY" ]; then
+    AUTH_HEADER=(-H "X-API-Key: $API_KEY")
+else
+    echo -e "${YELLOW}⚠️  API_KEY is not set; requests will get 401 unless the server runs with --auth-enabled false${NC}"
+fi
 
 echo -e "${BLUE}🚀 Solana Fellowship API - Complete Test${NC}"
 echo -e "${BLUE}=====================================${NC}"
@@ -17,8 +28,8 @@ echo ""
 # Test 1: Generate Keypair
 echo -e "${YELLOW}1️⃣  Testing Generate Keypair${NC}"
 echo "----------------------------------------"
-keypair_response=$(curl -s -X POST "$BASE_URL/keypair")
-if [ $? -eq 0 ]; then
+keypair_response=$(curl -s "${AUTH_HEADER[@]}" -X POST "$BASE_URL/keypair")
+if [[ "$keypair_response" == *'"success":true'* ]]; then
     echo -e "${GREEN}✅ Success${NC}"
     echo "$keypair_response" | python3 -m json.tool 2>/dev/null || echo "$keypair_response"
     
@@ -38,8 +49,8 @@ echo ""
 echo -e "${YELLOW}2️⃣  Testing Create Token${NC}"
 echo "----------------------------------------"
 create_token_data="{\"mintAuthority\":\"$pubkey\",\"mint\":\"$pubkey\",\"decimals\":6}"
-response=$(curl -s -X POST "$BASE_URL/token/create" -H "Content-Type: application/json" -d "$create_token_data")
-if [ $? -eq 0 ]; then
+response=$(curl -s "${AUTH_HEADER[@]}" -X POST "$BASE_URL/token/create" -H "Content-Type: application/json" -d "$create_token_data")
+if [[ "$response" == *'"success":true'* ]]; then
     echo -e "${GREEN}✅ Success${NC}"
     echo "$response" | python3 -m json.tool 2>/dev/null || echo "$response"
 else
@@ -52,8 +63,8 @@ echo ""
 echo -e "${YELLOW}3️⃣  Testing Mint Token${NC}"
 echo "----------------------------------------"
 mint_token_data="{\"mint\":\"$pubkey\",\"destination\":\"$pubkey\",\"authority\":\"$pubkey\",\"amount\":1000000}"
-response=$(curl -s -X POST "$BASE_URL/token/mint" -H "Content-Type: application/json" -d "$mint_token_data")
-if [ $? -eq 0 ]; then
+response=$(curl -s "${AUTH_HEADER[@]}" -X POST "$BASE_URL/token/mint" -H "Content-Type: application/json" -d "$mint_token_data")
+if [[ "$response" == *'"success":true'* ]]; then
     echo -e "${GREEN}✅ Success${NC}"
     echo "$response" | python3 -m json.tool 2>/dev/null || echo "$response"
 else
@@ -66,8 +77,8 @@ echo ""
 echo -e "${YELLOW}4️⃣  Testing Sign Message${NC}"
 echo "----------------------------------------"
 sign_message_data="{\"message\":\"Hello, Solana!\",\"secret\":\"$secret\"}"
-sign_response=$(curl -s -X POST "$BASE_URL/message/sign" -H "Content-Type: application/json" -d "$sign_message_data")
-if [ $? -eq 0 ]; then
+sign_response=$(curl -s "${AUTH_HEADER[@]}" -X POST "$BASE_URL/message/sign" -H "Content-Type: application/json" -d "$sign_message_data")
+if [[ "$sign_response" == *'"success":true'* ]]; then
     echo -e "${GREEN}✅ Success${NC}"
     echo "$sign_response" | python3 -m json.tool 2>/dev/null || echo "$sign_response"
     
@@ -87,8 +98,8 @@ echo ""
 echo -e "${YELLOW}5️⃣  Testing Verify Message${NC}"
 echo "----------------------------------------"
 verify_message_data="{\"message\":\"Hello, Solana!\",\"signature\":\"$signature\",\"pubkey\":\"$signed_pubkey\"}"
-response=$(curl -s -X POST "$BASE_URL/message/verify" -H "Content-Type: application/json" -d "$verify_message_data")
-if [ $? -eq 0 ]; then
+response=$(curl -s "${AUTH_HEADER[@]}" -X POST "$BASE_URL/message/verify" -H "Content-Type: application/json" -d "$verify_message_data")
+if [[ "$response" == *'"success":true'* ]]; then
     echo -e "${GREEN}✅ Success${NC}"
     echo "$response" | python3 -m json.tool 2>/dev/null || echo "$response"
 else
@@ -101,12 +112,12 @@ echo ""
 echo -e "${YELLOW}6️⃣  Testing Send SOL${NC}"
 echo "----------------------------------------"
 # Generate a second keypair for the "to" address
-second_keypair=$(curl -s -X POST "$BASE_URL/keypair")
+second_keypair=$(curl -s "${AUTH_HEADER[@]}" -X POST "$BASE_URL/keypair")
 second_pubkey=$(echo "$second_keypair" | grep -o '"pubkey":"[^"]*"' | cut -d'"' -f4)
 
 send_sol_data="{\"from\":\"$pubkey\",\"to\":\"$second_pubkey\",\"lamports\":100000}"
-response=$(curl -s -X POST "$BASE_URL/send/sol" -H "Content-Type: application/json" -d "$send_sol_data")
-if [ $? -eq 0 ]; then
+response=$(curl -s "${AUTH_HEADER[@]}" -X POST "$BASE_URL/send/sol" -H "Content-Type: application/json" -d "$send_sol_data")
+if [[ "$response" == *'"success":true'* ]]; then
     echo -e "${GREEN}✅ Success${NC}"
     echo "$response" | python3 -m json.tool 2>/dev/null || echo "$response"
 else
@@ -119,8 +130,8 @@ echo ""
 echo -e "${YELLOW}7️⃣  Testing Send Token${NC}"
 echo "----------------------------------------"
 send_token_data="{\"destination\":\"$second_pubkey\",\"mint\":\"$pubkey\",\"owner\":\"$pubkey\",\"amount\":100000}"
-response=$(curl -s -X POST "$BASE_URL/send/token" -H "Content-Type: application/json" -d "$send_token_data")
-if [ $? -eq 0 ]; then
+response=$(curl -s "${AUTH_HEADER[@]}" -X POST "$BASE_URL/send/token" -H "Content-Type: application/json" -d "$send_token_data")
+if [[ "$response" == *'"success":true'* ]]; then
     echo -e "${GREEN}✅ Success${NC}"
     echo "$response" | python3 -m json.tool 2>/dev/null || echo "$response"
 else
@@ -132,8 +143,8 @@ echo ""
 # Test 8: Health Check
 echo -e "${YELLOW}🏥 Testing Health Check${NC}"
 echo "----------------------------------------"
-response=$(curl -s -X GET "$BASE_URL/health")
-if [ $? -eq 0 ]; then
+response=$(curl -s "${AUTH_HEADER[@]}" -X GET "$BASE_URL/health")
+if [[ "$response" == *'"success":true'* ]]; then
     echo -e "${GREEN}✅ Success${NC}"
     echo "$response" | python3 -m json.tool 2>/dev/null || echo "$response"
 else
@@ -144,4 +155,4 @@ echo ""
 
 echo -e "${GREEN}🎉 All tests completed!${NC}"
 echo -e "${BLUE}========================${NC}"
-echo -e "${YELLOW}💡 Make sure your server is running with: cargo run${NC}" 
\ No newline at end of file
+echo -e "${YELLOW}💡 Make sure your server is running with: cargo run (and API_KEY is set, see the top of this script)${NC}" 
\ No newline at end of file
@@ -0,0 +1,652 @@
+use axum::{
+    body::{to_bytes, Body, HttpBody},
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use crate::auth::Caller;
+use crate::error::{ApiError, ErrorCode};
+use crate::telemetry::current_request_id;
+use crate::tls::ClientIdentity;
+
+/// `prev_hash` of the first record in a log.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+const REDACTED: &str = "[REDACTED]";
+/// Fields whose values are recorded, at any depth of a request or response
+/// body; every other field is redacted, so a field added to a handler stays
+/// out of the log until it is listed here. Nested objects are only recorded
+/// through listed fields.
+const RECORDED_FIELDS: &[&str] = &[
+    // Messages and transactions. Signatures and signed transactions are not
+    // recorded.
+    "message",
+    "message_encoding",
+    "signature_encoding",
+    "application_domain",
+    "format",
+    "signers",
+    "envelope",
+    "public_key",
+    "version",
+    "signed",
+    "missing_signers",
+    "complete",
+    // Keys, by reference only.
+    "key_id",
+    "key_ids",
+    "pubkey",
+    "secret_format",
+    "imported_format",
+    "derivation_path",
+    "accounts",
+    "word_count",
+    "account",
+    "change",
+    "count",
+    // Vanity jobs.
+    "job_id",
+    "prefix",
+    "suffix",
+    "case_insensitive",
+    "threads",
+    "max_attempts",
+    "expected_attempts",
+    "status",
+    "attempts",
+    "attempts_per_second",
+    "elapsed_seconds",
+    "estimated_seconds_remaining",
+    // Vault keys and API keys.
+    "id",
+    "label",
+    "name",
+    "scopes",
+    "disabled",
+    "created_at",
+    "rotated_at",
+    "previous_pubkeys",
+];
+/// Responses on audited routes are small JSON documents and are buffered to
+/// be recorded; a larger body, or one of unknown length, is streamed to the
+/// client and recorded without its body.
+const MAX_RESPONSE_BYTES: usize = 4 * 1024 * 1024;
+
+/// Redacts every field of `value` not in `RECORDED_FIELDS`.
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (name, field) in map.iter_mut() {
+                if RECORDED_FIELDS.contains(&name.as_str()) {
+                    redact(field);
+                } else {
+                    *field = Value::String(REDACTED.to_string());
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// A request body as recorded: redacted JSON, or nothing for an empty or
+/// non-JSON body. Only objects are recorded field by field; anything else,
+/// such as the solana-keygen `id.json` array `/keypair/import` accepts, is
+/// redacted whole.
+fn recorded_body(bytes: &[u8]) -> Option<Value> {
+    let mut value: Value = serde_json::from_slice(bytes).ok()?;
+    if !value.is_object() {
+        return Some(Value::String(REDACTED.to_string()));
+    }
+    redact(&mut value);
+    Some(value)
+}
+
+/// The redacted `data` of a success response, or the whole body when it has
+/// no envelope.
+fn recorded_response(bytes: &[u8]) -> Option<Value> {
+    let mut body: Value = serde_json::from_slice(bytes).ok()?;
+    let mut data = body.get_mut("data").map(Value::take).unwrap_or(body);
+    redact(&mut data);
+    Some(data)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    Error,
+}
+
+/// One line of the audit log. `hash` is the SHA-256 of the record without
+/// `hash`, serialized with sorted keys, so each record commits to every
+/// record before it through `prev_hash`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuditRecord {
+    pub seq: u64,
+    pub time: String,
+    pub request_id: Option<String>,
+    pub api_key_id: Option<String>,
+    pub api_key_name: Option<String>,
+    pub client_ip: Option<String>,
+    /// Mutual TLS identity, or the certificate subject when it has none.
+    pub client_certificate: Option<String>,
+    pub method: String,
+    pub route: String,
+    pub status: u16,
+    pub outcome: Outcome,
+    pub error_code: Option<String>,
+    /// Redacted request body.
+    pub request: Option<Value>,
+    /// Redacted `data` of a success response.
+    pub response: Option<Value>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditRecord {
+    fn compute_hash(&self) -> String {
+        let mut value = serde_json::to_value(self).expect("audit records serialize");
+        if let Value::Object(map) = &mut value {
+            map.remove("hash");
+        }
+        hex::encode(Sha256::digest(value.to_string().as_bytes()))
+    }
+}
+
+struct Head {
+    file: File,
+    next_seq: u64,
+    last_hash: String,
+}
+
+struct AuditLogInner {
+    path: PathBuf,
+    head: Mutex<Head>,
+    /// Request bodies are buffered to be recorded; this caps them the way
+    /// `limits.max_body_bytes` caps the handlers' extractors.
+    max_body_bytes: usize,
+}
+
+/// Append-only, hash-chained log of calls to the signing, key generation and
+/// admin routes. Disabled (recording nothing) by default.
+#[derive(Default)]
+pub struct AuditLog {
+    inner: Option<AuditLogInner>,
+}
+
+/// Result of walking a log's hash chain.
+pub struct ChainSummary {
+    pub records: u64,
+    pub head_hash: String,
+}
+
+/// Reads every record of the log at `path`, checking sequence numbers, links
+/// and hashes. Returns the first inconsistency found.
+pub fn verify(path: &Path) -> Result<ChainSummary, String> {
+    let file = File::open(path).map_err(|e| format!("cannot open {}: {}", path.display(), e))?;
+    let mut summary = ChainSummary { records: 0, head_hash: GENESIS_HASH.to_string() };
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("reading {}: {}", path.display(), e))?;
+        let context = |problem: String| format!("line {}: {}", index + 1, problem);
+        let record: AuditRecord = serde_json::from_str(&line).map_err(|e| context(format!("not an audit record: {}", e)))?;
+        if record.seq != summary.records {
+            return Err(context(format!("expected seq {}, found {}", summary.records, record.seq)));
+        }
+        if record.prev_hash != summary.head_hash {
+            return Err(context(format!("seq {} does not link to the previous record", record.seq)));
+        }
+        if record.compute_hash() != record.hash {
+            return Err(context(format!("seq {} has been modified; its hash does not match", record.seq)));
+        }
+        summary.records += 1;
+        summary.head_hash = record.hash;
+    }
+    Ok(summary)
+}
+
+impl AuditLog {
+    /// Opens the log at `path` for appending, creating it if needed. The
+    /// whole chain is checked first, so a tampered log is caught at startup.
+    pub fn open(path: impl AsRef<Path>, max_body_bytes: usize) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        let summary = if path.exists() {
+            verify(&path).map_err(|e| format!("{} failed verification at {}", path.display(), e))?
+        } else {
+            ChainSummary { records: 0, head_hash: GENESIS_HASH.to_string() }
+        };
+        let file = open_append(&path).map_err(|e| format!("cannot open {}: {}", path.display(), e))?;
+        tracing::info!(
+            "Audit log {} has {} records; head hash {}",
+            path.display(),
+            summary.records,
+            summary.head_hash
+        );
+        let head = Head { file, next_seq: summary.records, last_hash: summary.head_hash };
+        Ok(Self {
+            inner: Some(AuditLogInner { path, head: Mutex::new(head), max_body_bytes }),
+        })
+    }
+
+    /// Links `record` to the chain and appends it, synced to disk. This blocks
+    /// on the file, so `record` runs it on the blocking thread pool; the lock
+    /// keeps concurrent appends in chain order.
+    fn append(&self, mut record: AuditRecord) -> Result<(), String> {
+        let Some(inner) = &self.inner else {
+            return Ok(());
+        };
+        let mut head = inner.head.lock().unwrap();
+        record.seq = head.next_seq;
+        record.prev_hash = head.last_hash.clone();
+        record.hash = record.compute_hash();
+        let mut line = serde_json::to_vec(&record).map_err(|e| e.to_string())?;
+        line.push(b'\n');
+        head.file
+            .write_all(&line)
+            .and_then(|_| head.file.sync_data())
+            .map_err(|e| format!("writing {}: {}", inner.path.display(), e))?;
+        head.next_seq += 1;
+        head.last_hash = record.hash;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn open_append(path: &Path) -> std::io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    OpenOptions::new().append(true).create(true).mode(0o600).open(path)
+}
+
+#[cfg(not(unix))]
+fn open_append(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().append(true).create(true).open(path)
+}
+
+/// Records the call in the audit log once the response is ready. If the
+/// record can't be written the response is withheld and a `500` returned
+/// instead, so no signature or key leaves the server unaudited.
+pub async fn record(State(audit): State<Arc<AuditLog>>, request: Request, next: Next) -> Response {
+    let Some(max_body_bytes) = audit.inner.as_ref().map(|inner| inner.max_body_bytes) else {
+        return next.run(request).await;
+    };
+    let (parts, body) = request.into_parts();
+    let caller = parts.extensions.get::<Caller>().map(|Caller(caller)| caller.clone());
+    let mut record = AuditRecord {
+        seq: 0,
+        time: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        request_id: current_request_id(),
+        api_key_id: caller.as_ref().map(|caller| caller.id.clone()),
+        api_key_name: caller.map(|caller| caller.name),
+        client_ip: parts.extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(peer)| peer.ip().to_string()),
+        client_certificate: parts
+            .extensions
+            .get::<ClientIdentity>()
+            .map(|client| client.identity.clone().unwrap_or_else(|| client.subject.clone())),
+        method: parts.method.to_string(),
+        route: parts
+            .extensions
+            .get::<MatchedPath>()
+            .map_or_else(|| parts.uri.path().to_string(), |path| path.as_str().to_string()),
+        status: 0,
+        outcome: Outcome::Error,
+        error_code: None,
+        request: None,
+        response: None,
+        prev_hash: String::new(),
+        hash: String::new(),
+    };
+
+    let response = match to_bytes(body, max_body_bytes).await {
+        Ok(bytes) => {
+            record.request = recorded_body(&bytes);
+            next.run(Request::from_parts(parts, Body::from(bytes))).await
+        }
+        Err(_) => ApiError::new(ErrorCode::PayloadTooLarge, "Request body is too large").into_response(),
+    };
+
+    let (mut parts, body) = response.into_parts();
+    let fits = body.size_hint().upper().is_some_and(|size| size <= MAX_RESPONSE_BYTES as u64);
+    let body = if fits {
+        match to_bytes(body, MAX_RESPONSE_BYTES).await {
+            Ok(bytes) => {
+                if parts.status.is_success() {
+                    record.response = recorded_response(&bytes);
+                }
+                Body::from(bytes)
+            }
+            Err(e) => {
+                tracing::error!("Failed to read the response to {} {}: {}", record.method, record.route, e);
+                let (failed, body) = ApiError::internal("Response unavailable").into_response().into_parts();
+                parts = failed;
+                body
+            }
+        }
+    } else {
+        body
+    };
+    record.status = parts.status.as_u16();
+    record.error_code = parts.extensions.get::<ErrorCode>().map(|code| code.name());
+    record.outcome = if parts.status.is_success() { Outcome::Success } else { Outcome::Error };
+
+    let written = {
+        let audit = audit.clone();
+        tokio::task::spawn_blocking(move || audit.append(record)).await
+    };
+    if let Err(e) = written.unwrap_or_else(|e| Err(format!("audit writer failed: {}", e))) {
+        tracing::error!("Failed to write audit record, withholding the response: {}", e);
+        return ApiError::internal("Audit log unavailable").into_response();
+    }
+    Response::from_parts(parts, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, routing::post, Json, Router};
+    use serde_json::json;
+    use std::fs;
+    use tower::ServiceExt;
+    use crate::config::FeatureToggles;
+    use crate::keystore::random_bytes;
+    use crate::openapi;
+
+    /// Fields on audited routes that carry secrets, signatures or credentials.
+    const SENSITIVE: &[&str] = &[
+        "secret",
+        "secrets",
+        "mnemonic",
+        "passphrase",
+        "password",
+        "keypair",
+        "keypair_json",
+        "keystore",
+        "signature",
+        "signatures",
+        "transaction",
+        "key",
+    ];
+
+    /// `METHOD path` and the operation object of each route in the sign,
+    /// keygen and admin groups, which `routes::app_router` audits.
+    fn audited_operations(document: &Value) -> Vec<(String, &Value)> {
+        let audited = ["sign", "keygen", "admin"].map(|scope| format!("with the `{}` scope", scope));
+        let paths = document["paths"].as_object().expect("paths");
+        paths
+            .iter()
+            .flat_map(|(path, item)| {
+                let operations = item.as_object().expect("path item");
+                operations.iter().map(move |(method, operation)| (format!("{} {}", method, path), operation))
+            })
+            .filter(|(_, operation)| {
+                let description = operation["description"].as_str().unwrap_or_default();
+                audited.iter().any(|scope| description.contains(scope.as_str()))
+            })
+            .collect()
+    }
+
+    /// A value of `schema` with every property present and the first
+    /// alternative of every `oneOf`.
+    fn sample(schema: &Value, schemas: &Value) -> Value {
+        if let Some(reference) = schema["$ref"].as_str() {
+            return sample(&schemas[reference.rsplit('/').next().unwrap()], schemas);
+        }
+        if let Some(parts) = schema["allOf"].as_array() {
+            let mut merged = Value::Null;
+            for part in parts {
+                match (sample(part, schemas), &mut merged) {
+                    (Value::Object(fields), Value::Object(into)) => into.extend(fields),
+                    (value, _) => merged = value,
+                }
+            }
+            return merged;
+        }
+        if let Some(alternatives) = schema["oneOf"].as_array() {
+            return sample(&alternatives[0], schemas);
+        }
+        if let Some(values) = schema["enum"].as_array() {
+            return values[0].clone();
+        }
+        match schema["type"].as_str() {
+            Some("object") => {
+                let properties = schema["properties"].as_object().into_iter().flatten();
+                Value::Object(properties.map(|(name, property)| (name.clone(), sample(property, schemas))).collect())
+            }
+            Some("array") => json!([sample(&schema["items"], schemas)]),
+            Some("integer") => json!(7),
+            Some("number") => json!(1.5),
+            Some("boolean") => json!(true),
+            _ => json!("sample"),
+        }
+    }
+
+    /// Paths in `value` that hold a sensitive or unlisted field unredacted.
+    fn leaks(value: &Value, path: &str, found: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                for (name, field) in map {
+                    let path = format!("{}.{}", path, name);
+                    let recorded = RECORDED_FIELDS.contains(&name.as_str()) && !SENSITIVE.contains(&name.as_str());
+                    if *field == json!(REDACTED) {
+                        continue;
+                    }
+                    if recorded {
+                        leaks(field, &path, found);
+                    } else {
+                        found.push(path);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|item| leaks(item, path, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn audited_routes_record_no_secrets() {
+        let features = FeatureToggles { vault: true, vanity: true, siws: true, metrics: true };
+        let document = openapi::document(&features);
+        let schemas = &document["components"]["schemas"];
+        let operations = audited_operations(&document);
+        assert!(operations.iter().any(|(operation, _)| operation == "post /keypair/import"));
+        assert!(operations.iter().any(|(operation, _)| operation == "delete /vault/keys/{key_id}"));
+
+        let mut redacted = 0;
+        for (operation, spec) in operations {
+            let mut bodies = Vec::new();
+            if let Some(schema) = spec.pointer("/requestBody/content/application~1json/schema") {
+                let request = sample(schema, schemas);
+                bodies.push(("request", recorded_body(request.to_string().as_bytes())));
+            }
+            let response = sample(&spec["responses"]["200"]["content"]["application/json"]["schema"], schemas);
+            bodies.push(("response", recorded_response(response.to_string().as_bytes())));
+
+            for (kind, recorded) in bodies {
+                let recorded = recorded.unwrap_or_else(|| panic!("{} {} was not recorded", operation, kind));
+                let mut found = Vec::new();
+                leaks(&recorded, "", &mut found);
+                assert!(found.is_empty(), "{} records its {} fields {:?}", operation, kind, found);
+                redacted += recorded.to_string().matches(REDACTED).count();
+            }
+        }
+        assert!(redacted > 20, "only {} fields were redacted; are the samples complete?", redacted);
+    }
+
+    #[test]
+    fn listed_fields_are_recorded_at_any_depth() {
+        let request = json!({ "message": "hi", "key_id": "key_1", "secret": "5Kd3", "passphrase": "hunter2" });
+        let expected = json!({ "message": "hi", "key_id": "key_1", "secret": REDACTED, "passphrase": REDACTED });
+        assert_eq!(recorded_body(request.to_string().as_bytes()), Some(expected));
+
+        let response = json!({
+            "success": true,
+            "data": {
+                "pubkey": "Fu1",
+                "mnemonic": "abandon abandon",
+                "accounts": [{ "derivation_path": "m/44'/501'/0'/0'", "pubkey": "Fu2", "secret": "5Kd3" }],
+                "new_field": { "pubkey": "Fu3" },
+            },
+        });
+        let expected = json!({
+            "pubkey": "Fu1",
+            "mnemonic": REDACTED,
+            "accounts": [{ "derivation_path": "m/44'/501'/0'/0'", "pubkey": "Fu2", "secret": REDACTED }],
+            "new_field": REDACTED,
+        });
+        assert_eq!(recorded_response(response.to_string().as_bytes()), Some(expected));
+        assert_eq!(recorded_body(b""), None);
+        assert_eq!(recorded_body(b"not json"), None);
+    }
+
+    #[test]
+    fn imported_keypairs_are_redacted_in_every_form() {
+        let bytes: Vec<u8> = (0..64).collect();
+        let text = serde_json::to_string(&bytes).unwrap();
+        let whole = Some(json!(REDACTED));
+        let field = Some(json!({ "keypair": REDACTED }));
+        for (body, expected) in [
+            (json!(bytes), &whole),
+            (json!(text), &whole),
+            (json!({ "keypair": bytes }), &field),
+            (json!({ "keypair": text }), &field),
+        ] {
+            assert_eq!(&recorded_body(body.to_string().as_bytes()), expected, "{}", body);
+        }
+    }
+
+    fn log_file() -> PathBuf {
+        std::env::temp_dir().join(format!("audit-{}.log", hex::encode(random_bytes::<8>())))
+    }
+
+    fn entry(route: &str) -> AuditRecord {
+        AuditRecord {
+            seq: 0,
+            time: "2026-01-01T00:00:00.000Z".to_string(),
+            request_id: None,
+            api_key_id: Some("3f9a61c2b07d45e8".to_string()),
+            api_key_name: None,
+            client_ip: None,
+            client_certificate: None,
+            method: "POST".to_string(),
+            route: route.to_string(),
+            status: 200,
+            outcome: Outcome::Success,
+            error_code: None,
+            request: None,
+            response: None,
+            prev_hash: String::new(),
+            hash: String::new(),
+        }
+    }
+
+    /// A log of three linked records.
+    fn written_log() -> (PathBuf, Vec<String>) {
+        let path = log_file();
+        let log = AuditLog::open(&path, 1024).unwrap();
+        for route in ["/message/sign", "/keypair", "/admin/api-keys"] {
+            log.append(entry(route)).unwrap();
+        }
+        let lines = fs::read_to_string(&path).unwrap().lines().map(String::from).collect();
+        (path, lines)
+    }
+
+    #[test]
+    fn records_are_chained_and_survive_a_restart() {
+        let (path, lines) = written_log();
+        let records: Vec<AuditRecord> = lines.iter().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(records.iter().map(|record| record.seq).collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(records[0].prev_hash, GENESIS_HASH);
+        assert_eq!(records[1].prev_hash, records[0].hash);
+        assert_eq!(records[2].prev_hash, records[1].hash);
+
+        let summary = verify(&path).unwrap();
+        assert_eq!((summary.records, summary.head_hash.as_str()), (3, records[2].hash.as_str()));
+
+        AuditLog::open(&path, 1024).unwrap().append(entry("/transaction/sign")).unwrap();
+        let summary = verify(&path).unwrap();
+        assert_eq!(summary.records, 4);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn tampering_breaks_the_chain() {
+        let (path, lines) = written_log();
+        let edited = lines[1].replace("/keypair", "/keypair/export");
+        let forged = {
+            let mut record: AuditRecord = serde_json::from_str(&edited).unwrap();
+            record.hash = record.compute_hash();
+            serde_json::to_string(&record).unwrap()
+        };
+        let cases = [
+            (vec![&lines[0], &edited, &lines[2]], "line 2: seq 1 has been modified"),
+            (vec![&lines[0], &forged, &lines[2]], "line 3: seq 2 does not link"),
+            (vec![&lines[0], &lines[2]], "line 2: expected seq 1, found 2"),
+            (vec![&lines[1], &lines[0], &lines[2]], "line 1: expected seq 0, found 1"),
+        ];
+        for (tampered, problem) in cases {
+            let text: Vec<&str> = tampered.iter().map(|line| line.as_str()).collect();
+            fs::write(&path, text.join("\n") + "\n").unwrap();
+            let error = verify(&path).err().unwrap_or_default();
+            assert!(error.starts_with(problem), "{} instead of {}", error, problem);
+            assert!(AuditLog::open(&path, 1024).is_err());
+        }
+
+        fs::write(&path, format!("{}\nnot json\n", lines[0])).unwrap();
+        assert!(verify(&path).err().unwrap_or_default().starts_with("line 2: not an audit record"));
+        fs::remove_file(path).unwrap();
+    }
+
+    fn app(audit: Arc<AuditLog>) -> Router {
+        let signed = || async { Json(json!({ "success": true, "data": { "pubkey": "Fu1", "signature": "5sig" } })) };
+        Router::new()
+            .route("/message/sign", post(signed))
+            .route("/keypair/export", post(|| async { "x".repeat(MAX_RESPONSE_BYTES + 1) }))
+            .route_layer(axum::middleware::from_fn_with_state(audit, record))
+    }
+
+    async fn call(app: &Router, path: &str, body: &str) -> (StatusCode, usize) {
+        let request = Request::post(path).body(Body::from(body.to_string())).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        (status, to_bytes(response.into_body(), usize::MAX).await.unwrap().len())
+    }
+
+    #[tokio::test]
+    async fn middleware_records_redacted_calls() {
+        let path = log_file();
+        let app = app(Arc::new(AuditLog::open(&path, 1024).unwrap()));
+
+        assert_eq!(call(&app, "/message/sign", r#"{"message":"hi","secret":"5Kd3"}"#).await.0, StatusCode::OK);
+        let (status, length) = call(&app, "/keypair/export", "").await;
+        assert_eq!((status, length), (StatusCode::OK, MAX_RESPONSE_BYTES + 1));
+        let oversized = "x".repeat(2048);
+        assert_eq!(call(&app, "/message/sign", &oversized).await.0, StatusCode::PAYLOAD_TOO_LARGE);
+
+        assert_eq!(verify(&path).unwrap().records, 3);
+        let text = fs::read_to_string(&path).unwrap();
+        let records: Vec<AuditRecord> = text.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(records[0].route, "/message/sign");
+        assert_eq!(records[0].request, Some(json!({ "message": "hi", "secret": REDACTED })));
+        assert_eq!(records[0].response, Some(json!({ "pubkey": "Fu1", "signature": REDACTED })));
+        assert_eq!((records[1].status, &records[1].response), (200, &None));
+        assert_eq!((records[2].outcome, records[2].error_code.as_deref()), (Outcome::Error, Some("PAYLOAD_TOO_LARGE")));
+        assert!(!text.contains("5Kd3") && !text.contains("5sig"));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn disabled_log_passes_calls_through() {
+        let app = app(Arc::new(AuditLog::default()));
+        // Bodies are not buffered, so the audit body limit doesn't apply.
+        assert_eq!(call(&app, "/message/sign", &"x".repeat(2048)).await.0, StatusCode::OK);
+        let (status, length) = call(&app, "/keypair/export", "").await;
+        assert_eq!((status, length), (StatusCode::OK, MAX_RESPONSE_BYTES + 1));
+    }
+}
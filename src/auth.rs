@@ -0,0 +1,439 @@
+use axum::{
+    extract::{Request, State},
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use crate::error::{ApiError, ErrorCode};
+use crate::keystore::random_bytes;
+use crate::openapi::ApiSchema;
+use crate::tls::ClientIdentity;
+use crate::vault::{now, write_private};
+
+pub const API_KEY_FILE_VERSION: u32 = 1;
+pub const API_KEY_HEADER: &str = "x-api-key";
+const KEY_PREFIX: &str = "sfa_";
+
+/// Paths served without an API key: the health check and the API description.
+pub const PUBLIC_PATHS: &[&str] = &[
+    "/health",
+    "/openapi.json",
+    "/docs",
+    "/docs/swagger-ui.css",
+    "/docs/swagger-ui-bundle.js",
+    "/docs/swagger-initializer.js",
+];
+
+/// What an API key may do. `admin` implies every other scope.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, ApiSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Build, decode and verify: endpoints that never see a secret key.
+    Build,
+    /// Sign messages and transactions with a given secret or vault key.
+    Sign,
+    /// Generate, convert or reveal secret keys, including vault keys.
+    Keygen,
+    /// Read the Prometheus metrics at `/metrics`.
+    Metrics,
+    /// Manage API keys and disable or delete vault keys.
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 5] = [Scope::Build, Scope::Sign, Scope::Keygen, Scope::Metrics, Scope::Admin];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Scope::Build => "build",
+            Scope::Sign => "sign",
+            Scope::Keygen => "keygen",
+            Scope::Metrics => "metrics",
+            Scope::Admin => "admin",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|scope| scope.name() == name.trim())
+            .ok_or_else(|| format!("unknown scope '{}'; expected build, sign, keygen, metrics or admin", name.trim()))
+    }
+
+    /// Whether holding `scopes` grants this scope.
+    pub fn granted_by(self, scopes: &[Scope]) -> bool {
+        scopes.contains(&self) || scopes.contains(&Scope::Admin)
+    }
+}
+
+/// On-disk key list. Only a SHA-256 hash of each key is kept; the key itself
+/// is shown once, when it is created.
+#[derive(Serialize, Deserialize)]
+struct ApiKeyFile {
+    version: u32,
+    keys: Vec<StoredApiKey>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct StoredApiKey {
+    id: String,
+    name: String,
+    scopes: Vec<Scope>,
+    created_at: u64,
+    /// Hex SHA-256 of the full key.
+    hash: String,
+}
+
+#[derive(Serialize, Clone, Debug, ApiSchema)]
+pub struct ApiKeyInfo {
+    #[schema(example = "3f9a61c2b07d45e8")]
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: u64,
+}
+
+impl ApiKeyInfo {
+    fn from_stored(key: &StoredApiKey) -> Self {
+        Self {
+            id: key.id.clone(),
+            name: key.name.clone(),
+            scopes: key.scopes.clone(),
+            created_at: key.created_at,
+        }
+    }
+
+    pub fn allows(&self, scope: Scope) -> bool {
+        scope.granted_by(&self.scopes)
+    }
+}
+
+struct ApiKeyStoreInner {
+    path: PathBuf,
+    file: Mutex<ApiKeyFile>,
+}
+
+/// API keys accepted by the server. Empty (and every admin call failing) when
+/// authentication is disabled.
+#[derive(Default)]
+pub struct ApiKeyStore {
+    inner: Option<ApiKeyStoreInner>,
+}
+
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+impl ApiKeyStore {
+    /// Opens the key file at `path`, creating it on first write.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        let file = if path.exists() {
+            let contents = fs::read(&path).map_err(|e| format!("reading {}: {}", path.display(), e))?;
+            let file: ApiKeyFile = serde_json::from_slice(&contents)
+                .map_err(|e| format!("parsing {}: {}", path.display(), e))?;
+            if file.version != API_KEY_FILE_VERSION {
+                return Err(format!("unsupported API key file version {}", file.version));
+            }
+            file
+        } else {
+            ApiKeyFile { version: API_KEY_FILE_VERSION, keys: Vec::new() }
+        };
+        Ok(Self {
+            inner: Some(ApiKeyStoreInner { path, file: Mutex::new(file) }),
+        })
+    }
+
+    fn inner(&self) -> Result<&ApiKeyStoreInner, ApiError> {
+        self.inner
+            .as_ref()
+            .ok_or_else(|| ApiError::new(ErrorCode::FeatureUnavailable, "API key authentication is disabled"))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner
+            .as_ref()
+            .is_none_or(|inner| inner.file.lock().unwrap().keys.is_empty())
+    }
+
+    /// Adds a key and returns it with its info. The key can't be recovered later.
+    pub fn create(&self, name: String, scopes: Vec<Scope>) -> Result<(String, ApiKeyInfo), ApiError> {
+        let inner = self.inner()?;
+        let id = hex::encode(random_bytes::<8>());
+        let key = format!("{}{}_{}", KEY_PREFIX, id, bs58::encode(random_bytes::<32>()).into_string());
+        let stored = StoredApiKey {
+            id,
+            name,
+            scopes,
+            created_at: now(),
+            hash: hash_key(&key),
+        };
+        let info = ApiKeyInfo::from_stored(&stored);
+
+        let mut file = inner.file.lock().unwrap();
+        file.keys.push(stored);
+        if let Err(e) = inner.persist(&file) {
+            file.keys.pop();
+            return Err(e);
+        }
+        Ok((key, info))
+    }
+
+    pub fn list(&self) -> Result<Vec<ApiKeyInfo>, ApiError> {
+        let file = self.inner()?.file.lock().unwrap();
+        Ok(file.keys.iter().map(ApiKeyInfo::from_stored).collect())
+    }
+
+    pub fn delete(&self, id: &str) -> Result<ApiKeyInfo, ApiError> {
+        let inner = self.inner()?;
+        let mut file = inner.file.lock().unwrap();
+        let position = file
+            .keys
+            .iter()
+            .position(|key| key.id == id)
+            .ok_or_else(|| ApiError::new(ErrorCode::NotFound, format!("API key '{}' not found", id)))?;
+        let removed = file.keys.remove(position);
+        if let Err(e) = inner.persist(&file) {
+            file.keys.insert(position, removed);
+            return Err(e);
+        }
+        Ok(ApiKeyInfo::from_stored(&removed))
+    }
+
+    /// Looks a presented key up by the ID embedded in it and checks its hash.
+    pub fn authenticate(&self, key: &str) -> Option<ApiKeyInfo> {
+        let inner = self.inner.as_ref()?;
+        let (id, _) = key.strip_prefix(KEY_PREFIX)?.split_once('_')?;
+        let file = inner.file.lock().unwrap();
+        let stored = file.keys.iter().find(|stored| stored.id == id)?;
+        constant_time_eq(hash_key(key).as_bytes(), stored.hash.as_bytes()).then(|| ApiKeyInfo::from_stored(stored))
+    }
+}
+
+impl ApiKeyStoreInner {
+    /// Writes the key file to a temporary file and renames it into place.
+    fn persist(&self, file: &ApiKeyFile) -> Result<(), ApiError> {
+        let storage = |e: String| ApiError::internal(format!("API key storage error: {}", e));
+        let contents = serde_json::to_vec_pretty(file).map_err(|e| storage(e.to_string()))?;
+        let tmp = self.path.with_extension("json.tmp");
+        write_private(&tmp, &contents).map_err(|e| storage(e.to_string()))?;
+        fs::rename(&tmp, &self.path).map_err(|e| storage(e.to_string()))
+    }
+}
+
+/// The key a request authenticated with, added as a request extension and,
+/// for the access log, to the response.
+#[derive(Clone, Debug)]
+pub struct Caller(pub ApiKeyInfo);
+
+fn presented_key(request: &Request) -> Option<&str> {
+    let headers = request.headers();
+    headers
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| {
+            headers
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+        })
+        .map(str::trim)
+}
+
+/// Rejects requests without a valid API key, except on `PUBLIC_PATHS`, and
+/// records the caller for `require_scope`.
+pub async fn authenticate(State(store): State<Arc<ApiKeyStore>>, mut request: Request, next: Next) -> Response {
+    if PUBLIC_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+    let caller = match presented_key(&request) {
+        None => match request.extensions().get::<ClientIdentity>() {
+            // `require_scope` checks the certificate's scopes instead.
+            Some(client) if !client.scopes.is_empty() => {
+                tracing::debug!("Request authenticated by client certificate {:?}", client.identity);
+                return next.run(request).await;
+            }
+            _ => return ApiError::new(ErrorCode::Unauthorized, "Missing API key").into_response(),
+        },
+        Some(key) => match store.authenticate(key) {
+            Some(caller) => caller,
+            None => return ApiError::new(ErrorCode::Unauthorized, "Invalid API key").into_response(),
+        },
+    };
+    tracing::debug!("Request authenticated as API key {} ({})", caller.id, caller.name);
+    request.extensions_mut().insert(Caller(caller.clone()));
+    let mut response = next.run(request).await;
+    response.extensions_mut().insert(Caller(caller));
+    response
+}
+
+/// Route layer for a group of routes needing `scope`. Requests carry a
+/// `Caller` whenever `authenticate` is installed and an API key was given;
+/// otherwise the scopes of a mapped client certificate apply, and without
+/// either, authentication is disabled and every request passes.
+pub async fn require_scope(State(scope): State<Scope>, request: Request, next: Next) -> Response {
+    let extensions = request.extensions();
+    let refusal = match (extensions.get::<Caller>(), extensions.get::<ClientIdentity>()) {
+        (Some(Caller(caller)), _) if !caller.allows(scope) => {
+            Some(format!("API key '{}' lacks the '{}' scope", caller.name, scope.name()))
+        }
+        (None, Some(client)) if !client.scopes.is_empty() && !client.allows(scope) => Some(format!(
+            "Client certificate identity '{}' lacks the '{}' scope",
+            client.identity.as_deref().unwrap_or(&client.subject),
+            scope.name()
+        )),
+        _ => None,
+    };
+    match refusal {
+        Some(message) => ApiError::new(ErrorCode::InsufficientScope, message).into_response(),
+        None => next.run(request).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::StatusCode, middleware, routing::post, Router};
+    use tower::ServiceExt;
+
+    fn key_file() -> PathBuf {
+        std::env::temp_dir().join(format!("api-keys-{}.json", hex::encode(random_bytes::<8>())))
+    }
+
+    #[test]
+    fn hashes_keys_with_sha256() {
+        assert_eq!(hash_key("abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert!(constant_time_eq(b"same", b"same"));
+        assert!(!constant_time_eq(b"same", b"sane"));
+        assert!(!constant_time_eq(b"same", b"sam"));
+        assert!(constant_time_eq(b"", b""));
+    }
+
+    #[test]
+    fn admin_grants_every_scope() {
+        for scope in Scope::ALL {
+            assert!(scope.granted_by(&[Scope::Admin]));
+            assert!(scope.granted_by(&[scope]));
+            assert_eq!(Scope::parse(&format!(" {} ", scope.name())), Ok(scope));
+        }
+        assert!(!Scope::Keygen.granted_by(&[Scope::Build, Scope::Sign]));
+        assert!(!Scope::Build.granted_by(&[]));
+        assert!(Scope::parse("root").is_err());
+    }
+
+    #[test]
+    fn keys_authenticate_only_as_issued() {
+        let path = key_file();
+        let store = ApiKeyStore::open(&path).unwrap();
+        let (key, info) = store.create("ci".to_string(), vec![Scope::Build]).unwrap();
+        let (other_key, _) = store.create("ops".to_string(), vec![Scope::Admin]).unwrap();
+        assert!(key.starts_with(&format!("{}{}_", KEY_PREFIX, info.id)));
+        assert_eq!(store.authenticate(&key).map(|caller| caller.name), Some("ci".to_string()));
+
+        let mut tampered = key.clone();
+        let last = tampered.pop().unwrap();
+        tampered.push(if last == 'a' { 'b' } else { 'a' });
+        let (_, other_secret) = other_key.rsplit_once('_').unwrap();
+        let spliced = format!("{}{}_{}", KEY_PREFIX, info.id, other_secret);
+        for rejected in [&tampered, &spliced, &key[KEY_PREFIX.len()..], "", "sfa_", "sfa_nothing"] {
+            assert!(store.authenticate(rejected).is_none(), "{} was accepted", rejected);
+        }
+
+        // Only hashes reach the disk, and they survive a restart.
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains(&key) && contents.contains(&hash_key(&key)));
+        let reopened = ApiKeyStore::open(&path).unwrap();
+        assert_eq!(reopened.authenticate(&key).map(|caller| caller.scopes), Some(vec![Scope::Build]));
+
+        assert_eq!(reopened.delete(&info.id).unwrap().id, info.id);
+        assert!(reopened.authenticate(&key).is_none());
+        assert_eq!(reopened.delete(&info.id).err().map(|e| e.code), Some(ErrorCode::NotFound));
+        assert_eq!(reopened.list().unwrap().len(), 1);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn disabled_store_accepts_nothing() {
+        let store = ApiKeyStore::default();
+        assert!(store.is_empty());
+        assert!(store.authenticate("sfa_0_0").is_none());
+        let refused = store.create("x".to_string(), vec![Scope::Admin]).err().map(|e| e.code);
+        assert_eq!(refused, Some(ErrorCode::FeatureUnavailable));
+    }
+
+    fn app(store: Arc<ApiKeyStore>) -> Router {
+        let scoped = |path: &str, scope: Scope| {
+            Router::new()
+                .route(path, post(|| async { "ok" }))
+                .route_layer(middleware::from_fn_with_state(scope, require_scope))
+        };
+        Router::new()
+            .merge(scoped("/build", Scope::Build))
+            .merge(scoped("/sign", Scope::Sign))
+            .merge(scoped("/health", Scope::Build))
+            .layer(middleware::from_fn_with_state(store, authenticate))
+    }
+
+    async fn status(app: &Router, path: &str, headers: &[(&str, &str)], client: Option<ClientIdentity>) -> StatusCode {
+        let mut request = axum::http::Request::post(path);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let mut request = request.body(Body::empty()).unwrap();
+        if let Some(client) = client {
+            request.extensions_mut().insert(client);
+        }
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn middleware_checks_keys_and_scopes() {
+        let path = key_file();
+        let store = Arc::new(ApiKeyStore::open(&path).unwrap());
+        let (key, _) = store.create("ci".to_string(), vec![Scope::Build]).unwrap();
+        let app = app(store);
+        let bearer = format!("Bearer {}", key);
+
+        assert_eq!(status(&app, "/build", &[], None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&app, "/build", &[("x-api-key", "sfa_bad_key")], None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&app, "/build", &[("x-api-key", &key)], None).await, StatusCode::OK);
+        assert_eq!(status(&app, "/build", &[("authorization", &bearer)], None).await, StatusCode::OK);
+        assert_eq!(status(&app, "/sign", &[("x-api-key", &key)], None).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(&app, "/health", &[], None).await, StatusCode::OK);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn mapped_client_certificates_carry_their_scopes() {
+        let path = key_file();
+        let store = Arc::new(ApiKeyStore::open(&path).unwrap());
+        let (key, _) = store.create("ci".to_string(), vec![Scope::Sign]).unwrap();
+        let app = app(store);
+        let client = |identity: Option<&str>, scopes: Vec<Scope>| {
+            Some(ClientIdentity {
+                subject: "O=Acme,CN=ops-client".to_string(),
+                identity: identity.map(String::from),
+                scopes,
+            })
+        };
+
+        assert_eq!(status(&app, "/sign", &[], client(Some("ops"), vec![Scope::Sign])).await, StatusCode::OK);
+        assert_eq!(status(&app, "/build", &[], client(Some("ops"), vec![Scope::Sign])).await, StatusCode::FORBIDDEN);
+        // Without scopes the certificate is no credential, and a key takes precedence.
+        assert_eq!(status(&app, "/sign", &[], client(Some("ops"), vec![])).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(&app, "/sign", &[], client(None, vec![])).await, StatusCode::UNAUTHORIZED);
+        let with_key = [("x-api-key", key.as_str())];
+        let refused = status(&app, "/build", &with_key, client(Some("ops"), vec![Scope::Build])).await;
+        assert_eq!(refused, StatusCode::FORBIDDEN);
+        fs::remove_file(path).unwrap();
+    }
+}
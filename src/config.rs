@@ -0,0 +1,1057 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use crate::auth::Scope;
+
+/// Prefix for environment variable overrides, e.g. `SOLANA_API_PORT`.
+const ENV_PREFIX: &str = "SOLANA_API_";
+
+pub const USAGE: &str = "\
+Usage: solana_fellowship_api [OPTIONS]
+
+Settings are read from the config file, then SOLANA_API_* environment
+variables, then these flags; later sources win.
+
+Options:
+  --config <PATH>                 TOML config file (env: SOLANA_API_CONFIG)
+  --host <IP>                     Listen address (env: SOLANA_API_HOST)
+  --port <PORT>                   Listen port (env: SOLANA_API_PORT)
+  --log-level <FILTER>            Log filter, e.g. info or debug,hyper=warn
+                                  (env: SOLANA_API_LOG_LEVEL, RUST_LOG)
+  --log-format <FORMAT>           text or json (env: SOLANA_API_LOG_FORMAT)
+  --audit-log <PATH>              Hash-chained audit log (env: SOLANA_API_AUDIT_PATH)
+  --verify-audit-log <PATH>       Check an audit log's hash chain and exit
+  --vault <PATH>                  Key vault file (env: SOLANA_API_VAULT_PATH, VAULT_PATH)
+  --otlp-endpoint <URL>           Export traces over OTLP/gRPC, e.g. http://localhost:4317
+                                  (env: SOLANA_API_OTLP_ENDPOINT, OTEL_EXPORTER_OTLP_ENDPOINT)
+  --cluster <NAME>                mainnet-beta, devnet, testnet or localnet
+                                  (env: SOLANA_API_CLUSTER)
+  --cors-origin <ORIGIN>          Allowed CORS origin, repeatable
+                                  (env: SOLANA_API_CORS_ORIGINS, comma separated)
+  --max-body-bytes <BYTES>        Request body limit (env: SOLANA_API_MAX_BODY_BYTES)
+  --request-timeout-secs <SECS>   Request timeout (env: SOLANA_API_REQUEST_TIMEOUT_SECS)
+  --tls-cert <PATH>               PEM certificate chain (env: SOLANA_API_TLS_CERT)
+  --tls-key <PATH>                PEM private key (env: SOLANA_API_TLS_KEY)
+  --tls-client-ca <PATH>          PEM CA bundle for client certificates (mutual TLS)
+                                  (env: SOLANA_API_TLS_CLIENT_CA)
+  --allow-plaintext <BOOL>        Allow plain HTTP on a non-loopback address
+                                  (env: SOLANA_API_ALLOW_PLAINTEXT)
+  --auth-enabled <BOOL>           Require API keys (env: SOLANA_API_AUTH_ENABLED)
+  --api-keys <PATH>               Hashed API key file (env: SOLANA_API_AUTH_KEYS_PATH)
+  --create-api-key <NAME>         Add an API key to the key file, print it and exit
+  --scopes <LIST>                 Scopes for --create-api-key, comma separated:
+                                  build, sign, keygen, metrics, admin (default: admin)
+  --rate-limit-enabled <BOOL>     Enforce rate limits and quotas
+                                  (env: SOLANA_API_RATE_LIMIT_ENABLED)
+  --rate-limit-per-minute <N>     Default requests per minute per client
+                                  (env: SOLANA_API_RATE_LIMIT_PER_MINUTE)
+  --daily-quota <N>               Default requests per client per UTC day, 0 for none
+                                  (env: SOLANA_API_RATE_LIMIT_DAILY_QUOTA)
+  --quotas <PATH>                 Daily quota usage file (env: SOLANA_API_RATE_LIMIT_QUOTA_PATH)
+  --enable <FEATURE>              Enable vault, vanity, siws or metrics
+  --disable <FEATURE>             Disable vault, vanity, siws or metrics
+                                  (env: SOLANA_API_FEATURES_<FEATURE>=true|false)
+  -h, --help                      Print this help
+";
+
+const CLUSTERS: &[&str] = &["mainnet-beta", "devnet", "testnet", "localnet"];
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub log: LogConfig,
+    pub telemetry: TelemetryConfig,
+    /// Solana cluster this deployment serves; only reported at startup.
+    pub cluster: String,
+    pub cors: CorsConfig,
+    pub limits: LimitsConfig,
+    pub tls: TlsConfig,
+    pub auth: AuthConfig,
+    pub audit: AuditConfig,
+    pub vault: VaultConfig,
+    pub rate_limit: RateLimitConfig,
+    pub features: FeatureToggles,
+    /// Set by `--create-api-key`; a one-off action rather than a setting.
+    #[serde(skip)]
+    pub create_api_key: Option<NewApiKey>,
+    /// Set by `--verify-audit-log`.
+    #[serde(skip)]
+    pub verify_audit_log: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Serving plain HTTP is only allowed on loopback addresses unless this is set.
+    pub allow_plaintext: bool,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// A `tracing_subscriber::EnvFilter` directive.
+    pub level: String,
+    pub format: LogFormat,
+    /// One `access` event per request, with its route, status, latency and caller.
+    pub access_log: bool,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, with the request span's fields.
+    Json,
+}
+
+impl LogFormat {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(format!("'{}' is not text or json", other)),
+        }
+    }
+}
+
+/// Spans are exported over OTLP/gRPC when `otlp_endpoint` is set; request IDs
+/// and trace context are handled either way.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryConfig {
+    pub otlp_endpoint: Option<String>,
+    pub service_name: String,
+    /// Fraction of new traces exported. Traces started by a caller follow
+    /// the caller's sampling decision.
+    pub sample_ratio: f64,
+}
+
+/// Compiled into a `cors::CorsPolicy`; see there for matching rules.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Exact origins such as `https://wallet.example.com`, wildcard subdomains
+    /// such as `https://*.example.com`, or `*` for any origin.
+    pub allowed_origins: Vec<String>,
+    /// Sent only to listed origins, never to those allowed through `*`.
+    pub allow_credentials: bool,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub expose_headers: Vec<String>,
+    /// How long browsers may cache a preflight response.
+    pub max_age_secs: u64,
+    /// Per-route overrides; the first matching `path` wins.
+    pub routes: Vec<CorsRoute>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct CorsRoute {
+    /// An exact path, or a prefix ending in `/*`.
+    pub path: String,
+    pub allowed_methods: Option<Vec<String>>,
+    pub allowed_headers: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub max_body_bytes: usize,
+    pub request_timeout_secs: u64,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientAuth {
+    /// Every connection must present a certificate signed by `client_ca_path`.
+    #[default]
+    Required,
+    /// Connections without a certificate are accepted but get no identity.
+    Optional,
+}
+
+/// HTTPS is enabled when `cert_path` and `key_path` are set. The files are
+/// polled and reloaded when they change, so certificates can be rotated
+/// without a restart.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert_path: Option<PathBuf>,
+    pub key_path: Option<PathBuf>,
+    /// Enables mutual TLS.
+    pub client_ca_path: Option<PathBuf>,
+    pub client_auth: ClientAuth,
+    /// Client certificate subject (e.g. `O=Acme,CN=ops-client`) to identity.
+    /// When non-empty, certificates with unlisted subjects are refused.
+    pub client_identities: HashMap<String, ClientIdentityConfig>,
+    pub reload_interval_secs: u64,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ClientIdentityConfig {
+    /// Names the client in logs, audit records and rate limits.
+    pub identity: String,
+    /// Lets requests without an API key through with these scopes; when
+    /// empty, such requests still need a key.
+    #[serde(default)]
+    pub scopes: Vec<Scope>,
+}
+
+impl TlsConfig {
+    pub fn enabled(&self) -> bool {
+        self.cert_path.is_some() && self.key_path.is_some()
+    }
+}
+
+/// Every route except `/health` and the API description needs an API key
+/// from `keys_path` whose scopes cover the route.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub enabled: bool,
+    /// Hashed keys, managed with `--create-api-key` and `/admin/api-keys`.
+    pub keys_path: PathBuf,
+}
+
+/// Token buckets per client: the API key ID, else the identity mapped from a
+/// client certificate, else the IP address. A route gets the limits of the first entry in
+/// `routes` matching its path, and buckets are not shared between entries.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Sustained rate for routes without an override.
+    pub requests_per_minute: u32,
+    /// Requests a client may make at once before the rate applies.
+    pub burst: u32,
+    /// Requests per client per UTC day; 0 for no quota.
+    pub daily_quota: u64,
+    /// Quota usage, saved periodically so quotas survive restarts.
+    pub quota_path: PathBuf,
+    pub routes: Vec<RateLimitRoute>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitRoute {
+    /// Identifies the limit in logs and in the quota file.
+    pub name: String,
+    /// Exact paths, or prefixes ending in `/*`; they share one bucket.
+    pub paths: Vec<String>,
+    pub requests_per_minute: u32,
+    pub burst: Option<u32>,
+    pub daily_quota: Option<u64>,
+}
+
+/// Calls to the signing, key generation and admin routes are appended to
+/// `path`, each record hashed together with the one before it.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    pub enabled: bool,
+    pub path: PathBuf,
+}
+
+/// Where the key vault is kept. Its master key is only ever read from
+/// `VAULT_MASTER_KEY`, so it never ends up in a config file.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct VaultConfig {
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct NewApiKey {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+/// Route groups that can be switched off per deployment.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureToggles {
+    pub vault: bool,
+    pub vanity: bool,
+    pub siws: bool,
+    /// `/metrics` and the request metrics behind it.
+    pub metrics: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server: ServerConfig::default(),
+            log: LogConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            cluster: "mainnet-beta".to_string(),
+            cors: CorsConfig::default(),
+            limits: LimitsConfig::default(),
+            tls: TlsConfig::default(),
+            auth: AuthConfig::default(),
+            audit: AuditConfig::default(),
+            vault: VaultConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            features: FeatureToggles::default(),
+            create_api_key: None,
+            verify_audit_log: None,
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: Ipv4Addr::LOCALHOST.to_string(),
+            port: 3000,
+            allow_plaintext: false,
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            format: LogFormat::default(),
+            access_log: true,
+        }
+    }
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: "solana-fellowship-api".to_string(),
+            sample_ratio: 1.0,
+        }
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec!["*".to_string()],
+            allow_credentials: false,
+            allowed_methods: ["GET", "POST", "DELETE"].map(String::from).to_vec(),
+            allowed_headers: ["content-type", "authorization", "x-api-key", "x-request-id", "traceparent", "tracestate"]
+                .map(String::from)
+                .to_vec(),
+            expose_headers: ["retry-after", "x-ratelimit-limit", "x-ratelimit-remaining", "x-ratelimit-reset", "x-request-id"]
+                .map(String::from)
+                .to_vec(),
+            max_age_secs: 600,
+            routes: Vec::new(),
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 2 * 1024 * 1024,
+            request_timeout_secs: 30,
+        }
+    }
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            cert_path: None,
+            key_path: None,
+            client_ca_path: None,
+            client_auth: ClientAuth::default(),
+            client_identities: HashMap::new(),
+            reload_interval_secs: 10,
+        }
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            keys_path: PathBuf::from("api_keys.json"),
+        }
+    }
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: PathBuf::from("audit.log"),
+        }
+    }
+}
+
+impl Default for VaultConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("vault.json"),
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            requests_per_minute: 600,
+            burst: 100,
+            daily_quota: 0,
+            quota_path: PathBuf::from("quotas.json"),
+            routes: vec![
+                RateLimitRoute {
+                    name: "keygen".to_string(),
+                    paths: vec!["/keypair/*".to_string()],
+                    requests_per_minute: 60,
+                    burst: Some(10),
+                    daily_quota: None,
+                },
+                RateLimitRoute {
+                    name: "sign".to_string(),
+                    paths: ["/message/sign", "/message/offchain/sign", "/transaction/sign"]
+                        .map(String::from)
+                        .to_vec(),
+                    requests_per_minute: 300,
+                    burst: Some(50),
+                    daily_quota: None,
+                },
+            ],
+        }
+    }
+}
+
+impl Default for FeatureToggles {
+    fn default() -> Self {
+        Self {
+            vault: true,
+            vanity: true,
+            siws: true,
+            metrics: true,
+        }
+    }
+}
+
+pub enum ConfigError {
+    /// `--help` was given; print `USAGE` and exit successfully.
+    Help,
+    /// Every problem found, so they can all be fixed in one go.
+    Invalid(Vec<String>),
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("'{}' is not a boolean", value)),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("'{}' is not a valid number", value))
+}
+
+impl FeatureToggles {
+    fn set(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        match name {
+            "vault" => self.vault = enabled,
+            "vanity" => self.vanity = enabled,
+            "siws" => self.siws = enabled,
+            "metrics" => self.metrics = enabled,
+            other => return Err(format!("unknown feature '{}'", other)),
+        }
+        Ok(())
+    }
+}
+
+impl RateLimitConfig {
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.requests_per_minute == 0 {
+            errors.push("rate_limit.requests_per_minute must be greater than zero".to_string());
+        }
+        if self.burst == 0 {
+            errors.push("rate_limit.burst must be greater than zero".to_string());
+        }
+        let mut names = vec!["default"];
+        for route in &self.routes {
+            let field = format!("rate_limit.routes '{}'", route.name);
+            if route.name.trim().is_empty() {
+                errors.push("rate_limit.routes: every entry needs a name".to_string());
+            } else if names.contains(&route.name.as_str()) {
+                errors.push(format!("{}: the name is already used", field));
+            }
+            names.push(&route.name);
+            if route.paths.is_empty() {
+                errors.push(format!("{}: paths must not be empty", field));
+            }
+            for path in route.paths.iter().filter(|path| !path.starts_with('/')) {
+                errors.push(format!("{}: path '{}' must start with '/'", field, path));
+            }
+            if route.requests_per_minute == 0 {
+                errors.push(format!("{}: requests_per_minute must be greater than zero", field));
+            }
+            if route.burst == Some(0) {
+                errors.push(format!("{}: burst must be greater than zero", field));
+            }
+        }
+        errors
+    }
+}
+
+impl Config {
+    /// Loads the configuration from the process arguments and environment.
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::load_from(std::env::args().skip(1).collect(), |name| std::env::var(name).ok())
+    }
+
+    fn load_from(args: Vec<String>, env: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let flags = parse_flags(args)?;
+        let mut errors = Vec::new();
+
+        let path = flags
+            .iter()
+            .rev()
+            .find(|(name, _)| name == "config")
+            .map(|(_, value)| value.clone())
+            .or_else(|| env(&format!("{}CONFIG", ENV_PREFIX)));
+        let mut config = match path {
+            Some(path) => Self::from_file(PathBuf::from(path)).map_err(|e| ConfigError::Invalid(vec![e]))?,
+            None => Self::default(),
+        };
+
+        config.apply_env(&env, &mut errors);
+        // `--cors-origin` flags replace the configured list rather than extend it.
+        if flags.iter().any(|(name, _)| name == "cors-origin") {
+            config.cors.allowed_origins.clear();
+        }
+        for (name, value) in &flags {
+            if let Err(e) = config.apply_flag(name, value) {
+                errors.push(format!("--{}: {}", name, e));
+            }
+        }
+        if let Some(new_key) = &mut config.create_api_key {
+            if new_key.scopes.is_empty() {
+                new_key.scopes = vec![Scope::Admin];
+            }
+        }
+        errors.extend(config.validate());
+
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+
+    fn from_file(path: PathBuf) -> Result<Self, String> {
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("cannot read config file {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("invalid config file {}: {}", path.display(), e))
+    }
+
+    fn apply_env(&mut self, env: &impl Fn(&str) -> Option<String>, errors: &mut Vec<String>) {
+        let var = |name: &str| env(&format!("{}{}", ENV_PREFIX, name)).map(|value| (format!("{}{}", ENV_PREFIX, name), value));
+        let mut check = |name: String, result: Result<(), String>| {
+            if let Err(e) = result {
+                errors.push(format!("{}: {}", name, e));
+            }
+        };
+
+        if let Some((_, value)) = var("HOST") {
+            self.server.host = value;
+        }
+        if let Some((name, value)) = var("PORT") {
+            check(name, parse_number(&value).map(|port| self.server.port = port));
+        }
+        if let Some((name, value)) = var("ALLOW_PLAINTEXT") {
+            check(name, parse_bool(&value).map(|allow| self.server.allow_plaintext = allow));
+        }
+        // RUST_LOG is still honoured, below the service's own variable.
+        if let Some(value) = var("LOG_LEVEL").map(|(_, value)| value).or_else(|| env("RUST_LOG")) {
+            self.log.level = value;
+        }
+        if let Some((name, value)) = var("LOG_FORMAT") {
+            check(name, LogFormat::parse(&value).map(|format| self.log.format = format));
+        }
+        if let Some((name, value)) = var("ACCESS_LOG") {
+            check(name, parse_bool(&value).map(|enabled| self.log.access_log = enabled));
+        }
+        if let Some((name, value)) = var("AUDIT_ENABLED") {
+            check(name, parse_bool(&value).map(|enabled| self.audit.enabled = enabled));
+        }
+        if let Some((_, value)) = var("AUDIT_PATH") {
+            self.audit.path = PathBuf::from(value);
+        }
+        // VAULT_PATH, read before there was a config file, still works below the service's own variable.
+        if let Some(value) = var("VAULT_PATH").map(|(_, value)| value).or_else(|| env("VAULT_PATH")) {
+            self.vault.path = PathBuf::from(value);
+        }
+        // The standard OpenTelemetry variable is honoured below the service's own.
+        if let Some(value) = var("OTLP_ENDPOINT").map(|(_, value)| value).or_else(|| env("OTEL_EXPORTER_OTLP_ENDPOINT")) {
+            self.telemetry.otlp_endpoint = Some(value);
+        }
+        if let Some((_, value)) = var("TELEMETRY_SERVICE_NAME") {
+            self.telemetry.service_name = value;
+        }
+        if let Some((name, value)) = var("TELEMETRY_SAMPLE_RATIO") {
+            check(name, parse_number(&value).map(|ratio| self.telemetry.sample_ratio = ratio));
+        }
+        if let Some((_, value)) = var("CLUSTER") {
+            self.cluster = value;
+        }
+        if let Some((_, value)) = var("CORS_ORIGINS") {
+            self.cors.allowed_origins = value
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        if let Some((name, value)) = var("CORS_ALLOW_CREDENTIALS") {
+            check(name, parse_bool(&value).map(|enabled| self.cors.allow_credentials = enabled));
+        }
+        if let Some((name, value)) = var("CORS_MAX_AGE_SECS") {
+            check(name, parse_number(&value).map(|secs| self.cors.max_age_secs = secs));
+        }
+        if let Some((name, value)) = var("MAX_BODY_BYTES") {
+            check(name, parse_number(&value).map(|bytes| self.limits.max_body_bytes = bytes));
+        }
+        if let Some((name, value)) = var("REQUEST_TIMEOUT_SECS") {
+            check(name, parse_number(&value).map(|secs| self.limits.request_timeout_secs = secs));
+        }
+        if let Some((_, value)) = var("TLS_CERT") {
+            self.tls.cert_path = Some(PathBuf::from(value));
+        }
+        if let Some((_, value)) = var("TLS_KEY") {
+            self.tls.key_path = Some(PathBuf::from(value));
+        }
+        if let Some((_, value)) = var("TLS_CLIENT_CA") {
+            self.tls.client_ca_path = Some(PathBuf::from(value));
+        }
+        if let Some((name, value)) = var("AUTH_ENABLED") {
+            check(name, parse_bool(&value).map(|enabled| self.auth.enabled = enabled));
+        }
+        if let Some((_, value)) = var("AUTH_KEYS_PATH") {
+            self.auth.keys_path = PathBuf::from(value);
+        }
+        if let Some((name, value)) = var("RATE_LIMIT_ENABLED") {
+            check(name, parse_bool(&value).map(|enabled| self.rate_limit.enabled = enabled));
+        }
+        if let Some((name, value)) = var("RATE_LIMIT_PER_MINUTE") {
+            check(name, parse_number(&value).map(|rate| self.rate_limit.requests_per_minute = rate));
+        }
+        if let Some((name, value)) = var("RATE_LIMIT_BURST") {
+            check(name, parse_number(&value).map(|burst| self.rate_limit.burst = burst));
+        }
+        if let Some((name, value)) = var("RATE_LIMIT_DAILY_QUOTA") {
+            check(name, parse_number(&value).map(|quota| self.rate_limit.daily_quota = quota));
+        }
+        if let Some((_, value)) = var("RATE_LIMIT_QUOTA_PATH") {
+            self.rate_limit.quota_path = PathBuf::from(value);
+        }
+        for feature in ["vault", "vanity", "siws", "metrics"] {
+            if let Some((name, value)) = var(&format!("FEATURES_{}", feature.to_ascii_uppercase())) {
+                let features = &mut self.features;
+                check(name, parse_bool(&value).and_then(|enabled| features.set(feature, enabled)));
+            }
+        }
+    }
+
+    fn apply_flag(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "config" => {}
+            "host" => self.server.host = value.to_string(),
+            "port" => self.server.port = parse_number(value)?,
+            "allow-plaintext" => self.server.allow_plaintext = parse_bool(value)?,
+            "log-level" => self.log.level = value.to_string(),
+            "log-format" => self.log.format = LogFormat::parse(value)?,
+            "audit-log" => self.audit.path = PathBuf::from(value),
+            "verify-audit-log" => self.verify_audit_log = Some(PathBuf::from(value)),
+            "vault" => self.vault.path = PathBuf::from(value),
+            "otlp-endpoint" => self.telemetry.otlp_endpoint = Some(value.to_string()),
+            "cluster" => self.cluster = value.to_string(),
+            "cors-origin" => self.cors.allowed_origins.push(value.to_string()),
+            "max-body-bytes" => self.limits.max_body_bytes = parse_number(value)?,
+            "request-timeout-secs" => self.limits.request_timeout_secs = parse_number(value)?,
+            "tls-cert" => self.tls.cert_path = Some(PathBuf::from(value)),
+            "tls-key" => self.tls.key_path = Some(PathBuf::from(value)),
+            "tls-client-ca" => self.tls.client_ca_path = Some(PathBuf::from(value)),
+            "auth-enabled" => self.auth.enabled = parse_bool(value)?,
+            "api-keys" => self.auth.keys_path = PathBuf::from(value),
+            "create-api-key" => self.create_api_key.get_or_insert_with(NewApiKey::default).name = value.to_string(),
+            "scopes" => {
+                self.create_api_key.get_or_insert_with(NewApiKey::default).scopes =
+                    value.split(',').map(Scope::parse).collect::<Result<_, _>>()?
+            }
+            "rate-limit-enabled" => self.rate_limit.enabled = parse_bool(value)?,
+            "rate-limit-per-minute" => self.rate_limit.requests_per_minute = parse_number(value)?,
+            "daily-quota" => self.rate_limit.daily_quota = parse_number(value)?,
+            "quotas" => self.rate_limit.quota_path = PathBuf::from(value),
+            "enable" => self.features.set(value, true)?,
+            "disable" => self.features.set(value, false)?,
+            other => return Err(format!("unknown option '--{}'", other)),
+        }
+        Ok(())
+    }
+
+    /// Returns every validation problem; empty when the config is usable.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        match self.server.host.parse::<IpAddr>() {
+            Ok(host) if !host.is_loopback() && !self.tls.enabled() && !self.server.allow_plaintext => {
+                errors.push(format!(
+                    "server.host: refusing plain HTTP on {}; configure tls.cert_path and tls.key_path or set server.allow_plaintext",
+                    host
+                ));
+            }
+            Ok(_) => {}
+            Err(_) => errors.push(format!("server.host: '{}' is not an IP address", self.server.host)),
+        }
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&self.log.level) {
+            errors.push(format!("log.level: '{}' is not a valid filter: {}", self.log.level, e));
+        }
+        if let Some(endpoint) = &self.telemetry.otlp_endpoint {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                errors.push(format!("telemetry.otlp_endpoint: '{}' must be an http:// or https:// URL", endpoint));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.telemetry.sample_ratio) {
+            errors.push(format!("telemetry.sample_ratio: {} must be between 0 and 1", self.telemetry.sample_ratio));
+        }
+        if !CLUSTERS.contains(&self.cluster.as_str()) {
+            errors.push(format!("cluster: '{}' is not one of {}", self.cluster, CLUSTERS.join(", ")));
+        }
+        if let Err(cors_errors) = crate::cors::CorsPolicy::from_config(&self.cors) {
+            errors.extend(cors_errors);
+        }
+        if self.tls.cert_path.is_some() != self.tls.key_path.is_some() {
+            errors.push("tls: cert_path and key_path must be set together".to_string());
+        }
+        if !self.tls.enabled() && self.tls.client_ca_path.is_some() {
+            errors.push("tls.client_ca_path requires cert_path and key_path".to_string());
+        }
+        if self.tls.client_ca_path.is_none() && !self.tls.client_identities.is_empty() {
+            errors.push("tls.client_identities requires client_ca_path".to_string());
+        }
+        for (subject, mapped) in &self.tls.client_identities {
+            if let Err(e) = crate::tls::parse_subject(subject) {
+                errors.push(format!("tls.client_identities: '{}' is not a valid subject: {}", subject, e));
+            }
+            if mapped.identity.trim().is_empty() {
+                errors.push(format!("tls.client_identities '{}': identity must not be empty", subject));
+            }
+        }
+        if self.tls.reload_interval_secs == 0 {
+            errors.push("tls.reload_interval_secs must be greater than zero".to_string());
+        }
+        if self.vault.path.as_os_str().is_empty() {
+            errors.push("vault.path must not be empty".to_string());
+        } else if self.vault.path.is_dir() {
+            errors.push(format!("vault.path: {} is a directory", self.vault.path.display()));
+        }
+        if self.create_api_key.as_ref().is_some_and(|new_key| new_key.name.trim().is_empty()) {
+            errors.push("--create-api-key: a key name is required (--scopes only applies together with it)".to_string());
+        }
+        errors.extend(self.rate_limit.validate());
+        if self.limits.max_body_bytes == 0 {
+            errors.push("limits.max_body_bytes must be greater than zero".to_string());
+        }
+        if self.limits.request_timeout_secs == 0 {
+            errors.push("limits.request_timeout_secs must be greater than zero".to_string());
+        }
+        errors
+    }
+
+    /// Only valid after `validate` has passed.
+    pub fn listen_addr(&self) -> SocketAddr {
+        let host = self.server.host.parse().unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        SocketAddr::new(host, self.server.port)
+    }
+}
+
+/// Splits `--name value` and `--name=value` arguments into pairs.
+fn parse_flags(args: Vec<String>) -> Result<Vec<(String, String)>, ConfigError> {
+    let mut flags = Vec::new();
+    let mut errors = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Err(ConfigError::Help);
+        }
+        let Some(flag) = arg.strip_prefix("--") else {
+            errors.push(format!("unexpected argument '{}'", arg));
+            continue;
+        };
+        match flag.split_once('=') {
+            Some((name, value)) => flags.push((name.to_string(), value.to_string())),
+            None => match args.next() {
+                Some(value) => flags.push((flag.to_string(), value)),
+                None => errors.push(format!("--{} needs a value", flag)),
+            },
+        }
+    }
+    if errors.is_empty() {
+        Ok(flags)
+    } else {
+        Err(ConfigError::Invalid(errors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn load(argv: &[&str], env: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let env: HashMap<String, String> = env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        Config::load_from(args(argv), |name| env.get(name).cloned())
+    }
+
+    fn loaded(argv: &[&str], env: &[(&str, &str)]) -> Config {
+        match load(argv, env) {
+            Ok(config) => config,
+            Err(ConfigError::Help) => panic!("unexpected --help"),
+            Err(ConfigError::Invalid(errors)) => panic!("invalid config: {:?}", errors),
+        }
+    }
+
+    fn errors(argv: &[&str], env: &[(&str, &str)]) -> Vec<String> {
+        match load(argv, env) {
+            Err(ConfigError::Invalid(errors)) => errors,
+            Err(ConfigError::Help) => panic!("unexpected --help"),
+            Ok(_) => panic!("config was accepted"),
+        }
+    }
+
+    fn errors_for(argv: &[&str]) -> Vec<String> {
+        errors(argv, &[])
+    }
+
+    fn config_file(name: &str, text: &str) -> String {
+        let path = std::env::temp_dir().join(format!("config-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        path.display().to_string()
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        let config = loaded(&[], &[]);
+        assert_eq!(config.listen_addr(), "127.0.0.1:3000".parse().unwrap());
+        assert_eq!(config.cluster, "mainnet-beta");
+        assert!(Config::default().validate().is_empty());
+    }
+
+    #[test]
+    fn flags_override_env_which_overrides_the_file() {
+        let path = config_file(
+            "precedence",
+            "cluster = \"devnet\"\n[server]\nport = 4000\n[log]\nlevel = \"warn\"\n",
+        );
+        let config = loaded(&["--config", &path], &[]);
+        assert_eq!((config.server.port, config.cluster.as_str(), config.log.level.as_str()), (4000, "devnet", "warn"));
+
+        let env = [("SOLANA_API_PORT", "5000"), ("SOLANA_API_CLUSTER", "testnet")];
+        let config = loaded(&["--config", &path], &env);
+        assert_eq!((config.server.port, config.cluster.as_str(), config.log.level.as_str()), (5000, "testnet", "warn"));
+
+        let config = loaded(&["--config", &path, "--port=6000"], &env);
+        assert_eq!((config.server.port, config.cluster.as_str()), (6000, "testnet"));
+
+        // The file itself can come from the environment, and the flag wins over it.
+        let config = loaded(&[], &[("SOLANA_API_CONFIG", &path)]);
+        assert_eq!(config.server.port, 4000);
+        let other = config_file("precedence-flag", "[server]\nport = 4100\n");
+        let config = loaded(&["--config", &other], &[("SOLANA_API_CONFIG", &path)]);
+        assert_eq!(config.server.port, 4100);
+    }
+
+    #[test]
+    fn vault_path_comes_from_file_env_or_flag() {
+        assert_eq!(loaded(&[], &[]).vault.path, PathBuf::from("vault.json"));
+        let path = config_file("vault", "[vault]\npath = \"/srv/file-vault.json\"\n");
+        assert_eq!(loaded(&["--config", &path], &[]).vault.path, PathBuf::from("/srv/file-vault.json"));
+
+        let env = [("VAULT_PATH", "/srv/legacy.json")];
+        assert_eq!(loaded(&["--config", &path], &env).vault.path, PathBuf::from("/srv/legacy.json"));
+        let env = [("VAULT_PATH", "/srv/legacy.json"), ("SOLANA_API_VAULT_PATH", "/srv/env.json")];
+        assert_eq!(loaded(&["--config", &path], &env).vault.path, PathBuf::from("/srv/env.json"));
+        assert_eq!(loaded(&["--vault", "/srv/flag.json"], &env).vault.path, PathBuf::from("/srv/flag.json"));
+
+        assert_eq!(errors(&["--vault", ""], &[]), ["vault.path must not be empty"]);
+        let dir = std::env::temp_dir();
+        let errors = errors(&["--vault", dir.to_str().unwrap()], &[]);
+        assert_eq!(errors, [format!("vault.path: {} is a directory", dir.display())]);
+    }
+
+    #[test]
+    fn rust_log_ranks_below_the_service_variable() {
+        assert_eq!(loaded(&[], &[("RUST_LOG", "debug")]).log.level, "debug");
+        let env = [("RUST_LOG", "debug"), ("SOLANA_API_LOG_LEVEL", "warn")];
+        assert_eq!(loaded(&[], &env).log.level, "warn");
+    }
+
+    #[test]
+    fn cors_origin_flags_replace_the_configured_list() {
+        let env = [("SOLANA_API_CORS_ORIGINS", "https://a.example.com,https://b.example.com")];
+        assert_eq!(loaded(&[], &env).cors.allowed_origins, ["https://a.example.com", "https://b.example.com"]);
+        let config = loaded(
+            &["--cors-origin", "https://c.example.com", "--cors-origin=https://d.example.com"],
+            &env,
+        );
+        assert_eq!(config.cors.allowed_origins, ["https://c.example.com", "https://d.example.com"]);
+    }
+
+    #[test]
+    fn features_can_be_toggled_from_env_and_flags() {
+        let config = loaded(&["--disable", "vanity"], &[("SOLANA_API_FEATURES_VAULT", "false")]);
+        assert!(!config.features.vault);
+        assert!(!config.features.vanity);
+        assert!(config.features.siws);
+        let config = loaded(&["--enable", "vault"], &[("SOLANA_API_FEATURES_VAULT", "off")]);
+        assert!(config.features.vault);
+    }
+
+    #[test]
+    fn create_api_key_defaults_to_admin() {
+        let new_key = loaded(&["--create-api-key", "ops"], &[]).create_api_key.unwrap();
+        assert_eq!((new_key.name.as_str(), new_key.scopes.as_slice()), ("ops", [Scope::Admin].as_slice()));
+        let new_key = loaded(&["--create-api-key", "ci", "--scopes", "build,sign"], &[]).create_api_key.unwrap();
+        assert_eq!(new_key.scopes, [Scope::Build, Scope::Sign]);
+        assert!(errors(&["--scopes", "build"], &[]).iter().any(|e| e.starts_with("--create-api-key")));
+    }
+
+    #[test]
+    fn help_and_malformed_arguments() {
+        assert!(matches!(load(&["--port", "1", "-h"], &[]), Err(ConfigError::Help)));
+        assert!(matches!(load(&["--help"], &[]), Err(ConfigError::Help)));
+        let errors = errors(&["stray", "--port"], &[]);
+        assert_eq!(errors, ["unexpected argument 'stray'", "--port needs a value"]);
+    }
+
+    #[test]
+    fn reports_every_bad_value_together() {
+        let errors = errors(
+            &["--frobnicate", "1", "--auth-enabled", "maybe", "--port", "http", "--enable", "teleport"],
+            &[("SOLANA_API_RATE_LIMIT_PER_MINUTE", "lots")],
+        );
+        assert_eq!(errors.len(), 5, "{:?}", errors);
+        assert!(errors.iter().any(|e| e == "SOLANA_API_RATE_LIMIT_PER_MINUTE: 'lots' is not a valid number"));
+        assert!(errors.iter().any(|e| e == "--frobnicate: unknown option '--frobnicate'"));
+        assert!(errors.iter().any(|e| e == "--auth-enabled: 'maybe' is not a boolean"));
+        assert!(errors.iter().any(|e| e == "--port: 'http' is not a valid number"));
+        assert!(errors.iter().any(|e| e == "--enable: unknown feature 'teleport'"));
+    }
+
+    #[test]
+    fn rejects_unknown_file_keys() {
+        let path = config_file("unknown", "[server]\nhots = \"0.0.0.0\"\n");
+        let errors = errors(&["--config", &path], &[]);
+        assert!(errors[0].contains("unknown field `hots`"), "{:?}", errors);
+    }
+
+    #[test]
+    fn plain_http_needs_loopback_tls_or_an_explicit_opt_in() {
+        let refused = errors_for(&["--host", "0.0.0.0"]);
+        assert!(refused[0].starts_with("server.host: refusing plain HTTP"), "{:?}", refused);
+        assert!(loaded(&["--host", "0.0.0.0", "--allow-plaintext", "yes"], &[]).validate().is_empty());
+        assert!(loaded(&["--host", "::1"], &[]).validate().is_empty());
+        assert_eq!(errors_for(&["--host", "localhost"]), ["server.host: 'localhost' is not an IP address"]);
+    }
+
+    #[test]
+    fn validates_settings() {
+        let mut config = Config {
+            cluster: "moonnet".to_string(),
+            ..Config::default()
+        };
+        config.log.level = "info,[".to_string();
+        config.telemetry.otlp_endpoint = Some("localhost:4317".to_string());
+        config.telemetry.sample_ratio = 1.5;
+        config.limits.max_body_bytes = 0;
+        config.limits.request_timeout_secs = 0;
+        config.tls.key_path = Some(PathBuf::from("key.pem"));
+        let errors = config.validate();
+        for field in [
+            "cluster:",
+            "log.level:",
+            "telemetry.otlp_endpoint:",
+            "telemetry.sample_ratio:",
+            "limits.max_body_bytes",
+            "limits.request_timeout_secs",
+            "tls: cert_path and key_path",
+        ] {
+            assert!(errors.iter().any(|e| e.starts_with(field)), "no {} error in {:?}", field, errors);
+        }
+        assert_eq!(errors.len(), 7, "{:?}", errors);
+    }
+
+    #[test]
+    fn validates_client_identity_subjects() {
+        let path = config_file(
+            "identities",
+            "[tls]\ncert_path = \"server.pem\"\nkey_path = \"server.key\"\nclient_ca_path = \"ca.pem\"\n\
+             [tls.client_identities]\n\"O=Acme,CN=ops\" = { identity = \"ops\", scopes = [\"sign\"] }\n\
+             \"CN\" = { identity = \"broken\" }\n\"CN=ci\" = { identity = \" \" }\n",
+        );
+        let mut errors = errors_for(&["--config", &path]);
+        errors.sort();
+        assert_eq!(
+            errors,
+            [
+                "tls.client_identities 'CN=ci': identity must not be empty",
+                "tls.client_identities: 'CN' is not a valid subject: 'CN' has no '='",
+            ]
+        );
+    }
+
+    #[test]
+    fn validates_rate_limit_routes() {
+        let route = |name: &str, paths: &[&str], requests_per_minute| RateLimitRoute {
+            name: name.to_string(),
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+            requests_per_minute,
+            burst: None,
+            daily_quota: None,
+        };
+        let mut rate_limit = RateLimitConfig {
+            routes: vec![route("signing", &["/message/sign", "/transaction/*"], 10)],
+            ..RateLimitConfig::default()
+        };
+        assert!(rate_limit.validate().is_empty());
+
+        rate_limit.requests_per_minute = 0;
+        rate_limit.routes = vec![
+            route("default", &["/keypair"], 10),
+            route("signing", &["message/sign"], 0),
+            route("signing", &[], 10),
+            route(" ", &["/pda/find"], 10),
+        ];
+        rate_limit.routes[0].burst = Some(0);
+        assert_eq!(
+            rate_limit.validate(),
+            [
+                "rate_limit.requests_per_minute must be greater than zero",
+                "rate_limit.routes 'default': the name is already used",
+                "rate_limit.routes 'default': burst must be greater than zero",
+                "rate_limit.routes 'signing': path 'message/sign' must start with '/'",
+                "rate_limit.routes 'signing': requests_per_minute must be greater than zero",
+                "rate_limit.routes 'signing': the name is already used",
+                "rate_limit.routes 'signing': paths must not be empty",
+                "rate_limit.routes: every entry needs a name",
+            ]
+        );
+    }
+}
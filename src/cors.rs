@@ -0,0 +1,470 @@
+use axum::{
+    extract::{Request, State},
+    http::{
+        header::{
+            ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+            ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+            ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+        },
+        HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use crate::config::CorsConfig;
+
+/// An allowed origin: either exact, or `scheme://*.domain[:port]`, which
+/// matches any subdomain (at any depth) but not the bare domain.
+enum OriginPattern {
+    Exact(String),
+    Subdomain { scheme: String, suffix: String },
+}
+
+impl OriginPattern {
+    fn parse(pattern: &str) -> Result<Self, String> {
+        let pattern = pattern.to_ascii_lowercase();
+        let (scheme, rest) = pattern
+            .split_once("://")
+            .filter(|(scheme, _)| *scheme == "http" || *scheme == "https")
+            .ok_or_else(|| format!("'{}' is not an origin like https://example.com", pattern))?;
+        if rest.is_empty() || rest.contains('/') || rest.contains('@') {
+            return Err(format!("'{}' is not an origin like https://example.com", pattern));
+        }
+        match rest.strip_prefix("*.") {
+            Some(domain) if !domain.is_empty() && !domain.contains('*') => Ok(Self::Subdomain {
+                scheme: format!("{}://", scheme),
+                suffix: format!(".{}", domain),
+            }),
+            Some(_) => Err(format!("'{}' has an invalid wildcard", pattern)),
+            None if rest.contains('*') => Err(format!("'{}' may only use a wildcard as its first label", pattern)),
+            None => Ok(Self::Exact(pattern)),
+        }
+    }
+
+    fn matches(&self, origin: &str) -> bool {
+        match self {
+            Self::Exact(exact) => origin == exact,
+            Self::Subdomain { scheme, suffix } => origin
+                .strip_prefix(scheme.as_str())
+                .and_then(|host| host.strip_suffix(suffix.as_str()))
+                .is_some_and(|label| {
+                    !label.is_empty()
+                        && !label.starts_with('.')
+                        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+                }),
+        }
+    }
+}
+
+/// Methods and headers allowed on a group of routes.
+struct RouteRule {
+    methods: Vec<Method>,
+    methods_value: HeaderValue,
+    headers: Vec<HeaderName>,
+    headers_value: HeaderValue,
+}
+
+impl RouteRule {
+    fn new(methods: &[String], headers: &[String], errors: &mut Vec<String>, field: &str) -> Self {
+        let methods: Vec<Method> = methods
+            .iter()
+            .filter_map(|method| {
+                Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                    .map_err(|_| errors.push(format!("{}: '{}' is not an HTTP method", field, method)))
+                    .ok()
+            })
+            .collect();
+        let headers: Vec<HeaderName> = headers
+            .iter()
+            .filter_map(|header| {
+                HeaderName::from_bytes(header.as_bytes())
+                    .map_err(|_| errors.push(format!("{}: '{}' is not a header name", field, header)))
+                    .ok()
+            })
+            .collect();
+        Self {
+            methods_value: join_header(methods.iter().map(Method::as_str)),
+            headers_value: join_header(headers.iter().map(HeaderName::as_str)),
+            methods,
+            headers,
+        }
+    }
+}
+
+fn join_header<'a>(values: impl Iterator<Item = &'a str>) -> HeaderValue {
+    HeaderValue::from_str(&values.collect::<Vec<_>>().join(", ")).expect("validated tokens")
+}
+
+/// `/vault/*` matches `/vault` and everything under it; other paths match exactly.
+pub enum PathPattern {
+    Exact(String),
+    Prefix(String),
+}
+
+impl PathPattern {
+    pub fn parse(path: &str) -> Self {
+        match path.strip_suffix("/*") {
+            Some(prefix) => Self::Prefix(prefix.to_string()),
+            None => Self::Exact(path.to_string()),
+        }
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        match self {
+            Self::Exact(exact) => path == exact,
+            Self::Prefix(prefix) => path
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/')),
+        }
+    }
+}
+
+/// How an origin was allowed; only listed origins may receive credentials.
+#[derive(Clone, Copy)]
+enum Grant {
+    Listed,
+    Any,
+}
+
+pub struct CorsPolicy {
+    origins: Vec<OriginPattern>,
+    any_origin: bool,
+    allow_credentials: bool,
+    default_rule: RouteRule,
+    routes: Vec<(PathPattern, RouteRule)>,
+    expose_headers: Option<HeaderValue>,
+    max_age: HeaderValue,
+}
+
+impl CorsPolicy {
+    /// Compiles the `[cors]` config, returning every problem found.
+    pub fn from_config(config: &CorsConfig) -> Result<Self, Vec<String>> {
+        let mut errors = Vec::new();
+        let any_origin = config.allowed_origins.iter().any(|origin| origin == "*");
+        let origins: Vec<OriginPattern> = config
+            .allowed_origins
+            .iter()
+            .filter(|origin| *origin != "*")
+            .filter_map(|origin| {
+                OriginPattern::parse(origin)
+                    .map_err(|e| errors.push(format!("cors.allowed_origins: {}", e)))
+                    .ok()
+            })
+            .collect();
+        if config.allow_credentials && config.allowed_origins.iter().all(|origin| origin == "*") {
+            errors.push("cors.allow_credentials needs at least one listed origin; '*' never receives credentials".to_string());
+        }
+
+        let default_rule = RouteRule::new(&config.allowed_methods, &config.allowed_headers, &mut errors, "cors");
+        let routes = config
+            .routes
+            .iter()
+            .map(|route| {
+                let field = format!("cors.routes '{}'", route.path);
+                if !route.path.starts_with('/') {
+                    errors.push(format!("{}: path must start with '/'", field));
+                }
+                let pattern = PathPattern::parse(&route.path);
+                let rule = RouteRule::new(
+                    route.allowed_methods.as_ref().unwrap_or(&config.allowed_methods),
+                    route.allowed_headers.as_ref().unwrap_or(&config.allowed_headers),
+                    &mut errors,
+                    &field,
+                );
+                (pattern, rule)
+            })
+            .collect();
+
+        let mut expose = Vec::new();
+        for header in &config.expose_headers {
+            match HeaderName::from_bytes(header.as_bytes()) {
+                Ok(name) => expose.push(name),
+                Err(_) => errors.push(format!("cors.expose_headers: '{}' is not a header name", header)),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Self {
+            origins,
+            any_origin,
+            allow_credentials: config.allow_credentials,
+            default_rule,
+            routes,
+            expose_headers: (!expose.is_empty()).then(|| join_header(expose.iter().map(HeaderName::as_str))),
+            max_age: HeaderValue::from(config.max_age_secs),
+        })
+    }
+
+    fn rule_for(&self, path: &str) -> &RouteRule {
+        self.routes
+            .iter()
+            .find(|(pattern, _)| pattern.matches(path))
+            .map_or(&self.default_rule, |(_, rule)| rule)
+    }
+
+    fn grant(&self, origin: &str) -> Option<Grant> {
+        let origin = origin.to_ascii_lowercase();
+        if self.origins.iter().any(|pattern| pattern.matches(&origin)) {
+            Some(Grant::Listed)
+        } else if self.any_origin {
+            Some(Grant::Any)
+        } else {
+            None
+        }
+    }
+
+    fn allow_origin(&self, headers: &mut HeaderMap, origin: &HeaderValue, grant: Grant) {
+        match grant {
+            Grant::Listed => {
+                headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+                if self.allow_credentials {
+                    headers.insert(ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+                }
+            }
+            Grant::Any => {
+                headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+            }
+        }
+    }
+
+    fn preflight(&self, origin: &HeaderValue, grant: Option<Grant>, rule: &RouteRule, request: &HeaderMap) -> Response {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            VARY,
+            HeaderValue::from_static("origin, access-control-request-method, access-control-request-headers"),
+        );
+
+        let method_allowed = request
+            .get(ACCESS_CONTROL_REQUEST_METHOD)
+            .and_then(|method| Method::from_bytes(method.as_bytes()).ok())
+            .is_some_and(|method| rule.methods.contains(&method));
+        let headers_allowed = request
+            .get_all(ACCESS_CONTROL_REQUEST_HEADERS)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .all(|name| rule.headers.iter().any(|allowed| allowed.as_str().eq_ignore_ascii_case(name)));
+        let Some(grant) = grant.filter(|_| method_allowed && headers_allowed) else {
+            return (StatusCode::FORBIDDEN, headers).into_response();
+        };
+
+        self.allow_origin(&mut headers, origin, grant);
+        headers.insert(ACCESS_CONTROL_ALLOW_METHODS, rule.methods_value.clone());
+        if !rule.headers.is_empty() {
+            headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, rule.headers_value.clone());
+        }
+        headers.insert(ACCESS_CONTROL_MAX_AGE, self.max_age.clone());
+        (StatusCode::NO_CONTENT, headers).into_response()
+    }
+}
+
+/// Answers preflight requests and adds CORS headers to responses for allowed
+/// origins. Requests from other origins are still served; the browser simply
+/// withholds the response from the calling page.
+pub async fn cors(State(policy): State<Arc<CorsPolicy>>, request: Request, next: Next) -> Response {
+    let Some(origin) = request.headers().get(ORIGIN).cloned() else {
+        return next.run(request).await;
+    };
+    let grant = origin.to_str().ok().and_then(|origin| policy.grant(origin));
+    let rule = policy.rule_for(request.uri().path());
+
+    if request.method() == Method::OPTIONS && request.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD) {
+        return policy.preflight(&origin, grant, rule, request.headers());
+    }
+
+    let grant = grant.filter(|_| rule.methods.contains(request.method()));
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.append(VARY, HeaderValue::from_static("origin"));
+    if let Some(grant) = grant {
+        policy.allow_origin(headers, &origin, grant);
+        if let Some(expose) = &policy.expose_headers {
+            headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, expose.clone());
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::post, Router};
+    use tower::ServiceExt;
+    use crate::config::CorsRoute;
+
+    fn policy(origins: &[&str], allow_credentials: bool) -> CorsPolicy {
+        let config = CorsConfig {
+            allowed_origins: origins.iter().map(|origin| origin.to_string()).collect(),
+            allow_credentials,
+            ..CorsConfig::default()
+        };
+        CorsPolicy::from_config(&config).unwrap_or_else(|errors| panic!("{:?}", errors))
+    }
+
+    fn granted(policy: &CorsPolicy, origin: &str) -> Option<&'static str> {
+        policy.grant(origin).map(|grant| match grant {
+            Grant::Listed => "listed",
+            Grant::Any => "any",
+        })
+    }
+
+    #[test]
+    fn matches_exact_origins_case_insensitively() {
+        let policy = policy(&["https://Wallet.example.com"], false);
+        assert_eq!(granted(&policy, "https://wallet.example.com"), Some("listed"));
+        assert_eq!(granted(&policy, "HTTPS://WALLET.EXAMPLE.COM"), Some("listed"));
+        for origin in [
+            "http://wallet.example.com",
+            "https://wallet.example.com:8443",
+            "https://wallet.example.com.evil.io",
+            "null",
+        ] {
+            assert_eq!(granted(&policy, origin), None, "{}", origin);
+        }
+    }
+
+    #[test]
+    fn wildcards_match_subdomains_but_not_the_bare_domain() {
+        let policy = policy(&["https://*.example.org", "http://*.local.test:8080"], false);
+        for origin in ["https://app.example.org", "https://a.b.example.org", "http://dev.local.test:8080"] {
+            assert_eq!(granted(&policy, origin), Some("listed"), "{}", origin);
+        }
+        for origin in [
+            "https://example.org",
+            "https://.example.org",
+            "https://evilexample.org",
+            "https://app.example.org.evil.io",
+            "http://app.example.org",
+            "https://a/b.example.org",
+            "https://user@app.example.org",
+            "http://dev.local.test",
+        ] {
+            assert_eq!(granted(&policy, origin), None, "{}", origin);
+        }
+    }
+
+    #[test]
+    fn star_allows_any_origin_after_listed_ones() {
+        let policy = policy(&["*", "https://wallet.example.com"], true);
+        assert_eq!(granted(&policy, "https://wallet.example.com"), Some("listed"));
+        assert_eq!(granted(&policy, "https://elsewhere.io"), Some("any"));
+    }
+
+    #[test]
+    fn rejects_malformed_origins_and_credentials_for_star_only() {
+        let config = CorsConfig {
+            allowed_origins: [
+                "example.com",
+                "ftp://example.com",
+                "https://example.com/path",
+                "https://a.*.example.com",
+                "https://*.",
+            ]
+            .map(String::from)
+            .to_vec(),
+            ..CorsConfig::default()
+        };
+        assert_eq!(CorsPolicy::from_config(&config).err().map(|errors| errors.len()), Some(5));
+
+        let config = CorsConfig { allow_credentials: true, ..CorsConfig::default() };
+        let errors = CorsPolicy::from_config(&config).err().unwrap_or_default();
+        assert_eq!(errors, ["cors.allow_credentials needs at least one listed origin; '*' never receives credentials"]);
+    }
+
+    #[test]
+    fn path_patterns_match_exact_paths_and_whole_segments() {
+        let vault = PathPattern::parse("/vault/*");
+        assert!(vault.matches("/vault") && vault.matches("/vault/keys") && vault.matches("/vault/keys/abc"));
+        assert!(!vault.matches("/vaults") && !vault.matches("/vaultkeys/x"));
+        let exact = PathPattern::parse("/message/sign");
+        assert!(exact.matches("/message/sign"));
+        assert!(!exact.matches("/message/sign/") && !exact.matches("/message/signature"));
+    }
+
+    fn app(config: CorsConfig) -> Router {
+        let policy = Arc::new(CorsPolicy::from_config(&config).unwrap_or_else(|errors| panic!("{:?}", errors)));
+        Router::new()
+            .route("/message/sign", post(|| async { "signed" }))
+            .route("/vault/keys", post(|| async { "created" }))
+            .layer(middleware::from_fn_with_state(policy, cors))
+    }
+
+    async fn send(app: &Router, request: axum::http::Request<Body>) -> Response {
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    fn header(response: &Response, name: HeaderName) -> Option<&str> {
+        response.headers().get(name).and_then(|value| value.to_str().ok())
+    }
+
+    fn preflight(path: &str, origin: &str, method: &str, headers: &str) -> axum::http::Request<Body> {
+        axum::http::Request::options(path)
+            .header(ORIGIN, origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, method)
+            .header(ACCESS_CONTROL_REQUEST_HEADERS, headers)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn answers_preflights_per_route() {
+        let app = app(CorsConfig {
+            allowed_origins: vec!["https://wallet.example.com".to_string()],
+            allow_credentials: true,
+            routes: vec![CorsRoute {
+                path: "/vault/*".to_string(),
+                allowed_methods: Some(vec!["GET".to_string()]),
+                allowed_headers: None,
+            }],
+            ..CorsConfig::default()
+        });
+
+        let allowed = preflight("/message/sign", "https://wallet.example.com", "POST", "Content-Type, X-API-Key");
+        let response = send(&app, allowed).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(header(&response, ACCESS_CONTROL_ALLOW_ORIGIN), Some("https://wallet.example.com"));
+        assert_eq!(header(&response, ACCESS_CONTROL_ALLOW_CREDENTIALS), Some("true"));
+        assert_eq!(header(&response, ACCESS_CONTROL_ALLOW_METHODS), Some("GET, POST, DELETE"));
+        assert_eq!(header(&response, ACCESS_CONTROL_MAX_AGE), Some("600"));
+
+        for request in [
+            preflight("/vault/keys", "https://wallet.example.com", "POST", "content-type"),
+            preflight("/message/sign", "https://wallet.example.com", "POST", "x-secret"),
+            preflight("/message/sign", "https://other.example.com", "POST", "content-type"),
+        ] {
+            let response = send(&app, request).await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+            assert_eq!(header(&response, ACCESS_CONTROL_ALLOW_ORIGIN), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn decorates_simple_requests_from_allowed_origins_only() {
+        let app = app(CorsConfig::default());
+        let request = |origin: Option<&str>| {
+            let builder = axum::http::Request::post("/message/sign");
+            let builder = match origin {
+                Some(origin) => builder.header(ORIGIN, origin),
+                None => builder,
+            };
+            builder.body(Body::empty()).unwrap()
+        };
+
+        let response = send(&app, request(Some("https://anywhere.io"))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header(&response, ACCESS_CONTROL_ALLOW_ORIGIN), Some("*"));
+        assert_eq!(header(&response, ACCESS_CONTROL_ALLOW_CREDENTIALS), None);
+        assert!(header(&response, ACCESS_CONTROL_EXPOSE_HEADERS).is_some_and(|value| value.contains("retry-after")));
+        assert_eq!(header(&response, VARY), Some("origin"));
+
+        let response = send(&app, request(None)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header(&response, ACCESS_CONTROL_ALLOW_ORIGIN), None);
+        assert_eq!(header(&response, VARY), None);
+    }
+}
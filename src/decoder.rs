@@ -0,0 +1,758 @@
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use solana_sdk::address_lookup_table::instruction::ProgramInstruction as LookupTableInstruction;
+use solana_sdk::compute_budget::{self, ComputeBudgetInstruction};
+use solana_sdk::program_option::COption;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::stake::instruction::StakeInstruction;
+use solana_sdk::stake::state::{Authorized, Lockup};
+use solana_sdk::system_instruction::SystemInstruction;
+use solana_sdk::vote::instruction::VoteInstruction;
+use solana_sdk::vote::state::{Vote, VoteStateUpdate};
+use solana_sdk::{pubkey, system_program};
+use spl_token::instruction::TokenInstruction as Token;
+use spl_token_2022::instruction::TokenInstruction as Token2022;
+use crate::programs::well_known_label;
+use crate::utils::encode_base64;
+use crate::openapi::ApiSchema;
+
+const MEMO_V1_ID: Pubkey = pubkey!("Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo");
+const MEMO_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+/// Parses a known program's instruction data and accounts into named fields.
+type Parser = fn(&[String], &[u8]) -> Result<Value, String>;
+
+/// An instruction with its raw fields and, for known programs, the data and
+/// accounts parsed into named fields under `parsed`.
+#[derive(Serialize, ApiSchema)]
+pub struct DecodedInstruction {
+    #[schema(format = "base58", example = "11111111111111111111111111111111")]
+    pub program_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "System Program")]
+    pub program: Option<&'static str>,
+    #[schema(format = "base58", example = r#"["FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT", "9jqAyNviuf2CcdajQyTv5YgPhCt4zHJUtmQTAdwCWaxM"]"#)]
+    pub accounts: Vec<String>,
+    /// Raw instruction data, base64 encoded.
+    #[schema(format = "base64", example = "AgAAAKCGAQAAAAAA")]
+    pub data: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parsed: Option<Value>,
+    /// Set when the program is known but the data doesn't parse.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Decodes one instruction. `accounts` are the instruction's accounts in order;
+/// they are strings because v0 lookup table entries can't be resolved offline.
+pub fn decode_instruction(program_id: &Pubkey, accounts: Vec<String>, data: &[u8]) -> DecodedInstruction {
+    let parser: Option<Parser> = match *program_id {
+        id if id == system_program::id() => Some(parse_system),
+        id if id == spl_token::id() => Some(parse_token),
+        id if id == spl_token_2022::id() => Some(parse_token_2022),
+        id if id == spl_associated_token_account::id() => Some(parse_associated_token),
+        id if id == MEMO_ID || id == MEMO_V1_ID => Some(parse_memo),
+        id if id == compute_budget::id() => Some(parse_compute_budget),
+        id if id == solana_sdk::stake::program::id() => Some(parse_stake),
+        id if id == solana_sdk::vote::program::id() => Some(parse_vote),
+        id if id == solana_sdk::address_lookup_table::program::id() => Some(parse_lookup_table),
+        _ => None,
+    };
+
+    let (parsed, error) = match parser.map(|parse| parse(&accounts, data)) {
+        Some(Ok(parsed)) => (Some(parsed), None),
+        Some(Err(e)) => (None, Some(e)),
+        None => (None, None),
+    };
+
+    DecodedInstruction {
+        program_id: program_id.to_string(),
+        program: well_known_label(program_id),
+        accounts,
+        data: encode_base64(data),
+        parsed,
+        error,
+    }
+}
+
+/// Builds `{"type": kind, "accounts": {<account names>...}, "info": fields}`.
+/// Accounts past the named ones are listed under `remainingAccounts`.
+fn parsed(kind: &str, accounts: &[String], names: &[&str], fields: Value) -> Value {
+    parsed_with_extra(kind, accounts, names, "remainingAccounts", fields)
+}
+
+/// `parsed` for SPL Token instructions, whose accounts past the named ones
+/// are the signers of a multisig authority.
+fn token_parsed(kind: &str, accounts: &[String], names: &[&str], fields: Value) -> Value {
+    parsed_with_extra(kind, accounts, names, "signers", fields)
+}
+
+fn parsed_with_extra(kind: &str, accounts: &[String], names: &[&str], extra: &str, fields: Value) -> Value {
+    let mut named = Map::new();
+    for (name, account) in names.iter().zip(accounts) {
+        named.insert(name.to_string(), json!(account));
+    }
+    if accounts.len() > names.len() {
+        named.insert(extra.to_string(), json!(accounts[names.len()..]));
+    }
+    json!({"type": kind, "accounts": named, "info": fields})
+}
+
+/// Turns a Rust variant name from `Debug` output into the camelCase names used
+/// for `type` fields, e.g. `MintTokens` or `TransferFeeExtension(..)`.
+fn variant_name(value: &impl std::fmt::Debug) -> String {
+    let debug = format!("{:?}", value);
+    let name: String = debug.chars().take_while(|c| c.is_ascii_alphanumeric()).collect();
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_lowercase().to_string() + chars.as_str(),
+        None => name,
+    }
+}
+
+fn optional_pubkey(value: COption<Pubkey>) -> Value {
+    json!(Option::<Pubkey>::from(value).map(|pubkey| pubkey.to_string()))
+}
+
+fn parse_system(accounts: &[String], data: &[u8]) -> Result<Value, String> {
+    let instruction: SystemInstruction =
+        bincode::deserialize(data).map_err(|e| format!("Invalid System instruction: {}", e))?;
+    Ok(match instruction {
+        SystemInstruction::CreateAccount { lamports, space, owner } => parsed(
+            "createAccount",
+            accounts,
+            &["source", "newAccount"],
+            json!({"lamports": lamports, "space": space, "owner": owner.to_string()}),
+        ),
+        SystemInstruction::Assign { owner } => {
+            parsed("assign", accounts, &["account"], json!({"owner": owner.to_string()}))
+        }
+        SystemInstruction::Transfer { lamports } => {
+            parsed("transfer", accounts, &["source", "destination"], json!({"lamports": lamports}))
+        }
+        SystemInstruction::CreateAccountWithSeed { base, seed, lamports, space, owner } => parsed(
+            "createAccountWithSeed",
+            accounts,
+            &["source", "newAccount", "base"],
+            json!({
+                "base": base.to_string(),
+                "seed": seed,
+                "lamports": lamports,
+                "space": space,
+                "owner": owner.to_string(),
+            }),
+        ),
+        SystemInstruction::AdvanceNonceAccount => parsed(
+            "advanceNonce",
+            accounts,
+            &["nonceAccount", "recentBlockhashesSysvar", "nonceAuthority"],
+            json!({}),
+        ),
+        SystemInstruction::WithdrawNonceAccount(lamports) => parsed(
+            "withdrawFromNonce",
+            accounts,
+            &["nonceAccount", "destination", "recentBlockhashesSysvar", "rentSysvar", "nonceAuthority"],
+            json!({"lamports": lamports}),
+        ),
+        SystemInstruction::InitializeNonceAccount(authority) => parsed(
+            "initializeNonce",
+            accounts,
+            &["nonceAccount", "recentBlockhashesSysvar", "rentSysvar"],
+            json!({"nonceAuthority": authority.to_string()}),
+        ),
+        SystemInstruction::AuthorizeNonceAccount(authority) => parsed(
+            "authorizeNonce",
+            accounts,
+            &["nonceAccount", "nonceAuthority"],
+            json!({"newAuthorized": authority.to_string()}),
+        ),
+        SystemInstruction::UpgradeNonceAccount => parsed("upgradeNonce", accounts, &["nonceAccount"], json!({})),
+        SystemInstruction::Allocate { space } => parsed("allocate", accounts, &["account"], json!({"space": space})),
+        SystemInstruction::AllocateWithSeed { base, seed, space, owner } => parsed(
+            "allocateWithSeed",
+            accounts,
+            &["account", "base"],
+            json!({"seed": seed, "space": space, "owner": owner.to_string(), "base": base.to_string()}),
+        ),
+        SystemInstruction::AssignWithSeed { base, seed, owner } => parsed(
+            "assignWithSeed",
+            accounts,
+            &["account", "base"],
+            json!({"seed": seed, "owner": owner.to_string(), "base": base.to_string()}),
+        ),
+        SystemInstruction::TransferWithSeed { lamports, from_seed, from_owner } => parsed(
+            "transferWithSeed",
+            accounts,
+            &["source", "sourceBase", "destination"],
+            json!({"lamports": lamports, "sourceSeed": from_seed, "sourceOwner": from_owner.to_string()}),
+        ),
+    })
+}
+
+/// Instructions shared by SPL Token and Token-2022, which use the same variant
+/// names and layouts. Evaluates to `Err(instruction)` for anything else.
+macro_rules! parse_token_common {
+    ($kind:ident, $instruction:expr, $accounts:expr) => {
+        match $instruction {
+            $kind::InitializeMint { decimals, mint_authority, freeze_authority } => Ok(token_parsed(
+                "initializeMint",
+                $accounts,
+                &["mint", "rentSysvar"],
+                json!({
+                    "decimals": decimals,
+                    "mintAuthority": mint_authority.to_string(),
+                    "freezeAuthority": optional_pubkey(freeze_authority),
+                }),
+            )),
+            $kind::InitializeMint2 { decimals, mint_authority, freeze_authority } => Ok(token_parsed(
+                "initializeMint2",
+                $accounts,
+                &["mint"],
+                json!({
+                    "decimals": decimals,
+                    "mintAuthority": mint_authority.to_string(),
+                    "freezeAuthority": optional_pubkey(freeze_authority),
+                }),
+            )),
+            $kind::InitializeAccount => Ok(token_parsed(
+                "initializeAccount",
+                $accounts,
+                &["account", "mint", "owner", "rentSysvar"],
+                json!({}),
+            )),
+            $kind::InitializeAccount2 { owner } => Ok(token_parsed(
+                "initializeAccount2",
+                $accounts,
+                &["account", "mint", "rentSysvar"],
+                json!({"owner": owner.to_string()}),
+            )),
+            $kind::InitializeAccount3 { owner } => Ok(token_parsed(
+                "initializeAccount3",
+                $accounts,
+                &["account", "mint"],
+                json!({"owner": owner.to_string()}),
+            )),
+            $kind::InitializeMultisig { m } => {
+                Ok(token_parsed("initializeMultisig", $accounts, &["multisig", "rentSysvar"], json!({"m": m})))
+            }
+            $kind::InitializeMultisig2 { m } => {
+                Ok(token_parsed("initializeMultisig2", $accounts, &["multisig"], json!({"m": m})))
+            }
+            $kind::Transfer { amount } => Ok(token_parsed(
+                "transfer",
+                $accounts,
+                &["source", "destination", "authority"],
+                json!({"amount": amount}),
+            )),
+            $kind::Approve { amount } => Ok(token_parsed(
+                "approve",
+                $accounts,
+                &["source", "delegate", "owner"],
+                json!({"amount": amount}),
+            )),
+            $kind::Revoke => Ok(token_parsed("revoke", $accounts, &["source", "owner"], json!({}))),
+            $kind::SetAuthority { authority_type, new_authority } => Ok(token_parsed(
+                "setAuthority",
+                $accounts,
+                &["account", "authority"],
+                json!({
+                    "authorityType": variant_name(&authority_type),
+                    "newAuthority": optional_pubkey(new_authority),
+                }),
+            )),
+            $kind::MintTo { amount } => Ok(token_parsed(
+                "mintTo",
+                $accounts,
+                &["mint", "account", "mintAuthority"],
+                json!({"amount": amount}),
+            )),
+            $kind::Burn { amount } => Ok(token_parsed(
+                "burn",
+                $accounts,
+                &["account", "mint", "authority"],
+                json!({"amount": amount}),
+            )),
+            $kind::CloseAccount => Ok(token_parsed(
+                "closeAccount",
+                $accounts,
+                &["account", "destination", "owner"],
+                json!({}),
+            )),
+            $kind::FreezeAccount => Ok(token_parsed(
+                "freezeAccount",
+                $accounts,
+                &["account", "mint", "freezeAuthority"],
+                json!({}),
+            )),
+            $kind::ThawAccount => Ok(token_parsed(
+                "thawAccount",
+                $accounts,
+                &["account", "mint", "freezeAuthority"],
+                json!({}),
+            )),
+            $kind::TransferChecked { amount, decimals } => Ok(token_parsed(
+                "transferChecked",
+                $accounts,
+                &["source", "mint", "destination", "authority"],
+                json!({"amount": amount, "decimals": decimals}),
+            )),
+            $kind::ApproveChecked { amount, decimals } => Ok(token_parsed(
+                "approveChecked",
+                $accounts,
+                &["source", "mint", "delegate", "owner"],
+                json!({"amount": amount, "decimals": decimals}),
+            )),
+            $kind::MintToChecked { amount, decimals } => Ok(token_parsed(
+                "mintToChecked",
+                $accounts,
+                &["mint", "account", "mintAuthority"],
+                json!({"amount": amount, "decimals": decimals}),
+            )),
+            $kind::BurnChecked { amount, decimals } => Ok(token_parsed(
+                "burnChecked",
+                $accounts,
+                &["account", "mint", "authority"],
+                json!({"amount": amount, "decimals": decimals}),
+            )),
+            $kind::SyncNative => Ok(token_parsed("syncNative", $accounts, &["account"], json!({}))),
+            $kind::InitializeImmutableOwner => {
+                Ok(token_parsed("initializeImmutableOwner", $accounts, &["account"], json!({})))
+            }
+            $kind::AmountToUiAmount { amount } => {
+                Ok(token_parsed("amountToUiAmount", $accounts, &["mint"], json!({"amount": amount})))
+            }
+            $kind::UiAmountToAmount { ui_amount } => {
+                Ok(token_parsed("uiAmountToAmount", $accounts, &["mint"], json!({"uiAmount": ui_amount})))
+            }
+            other => Err(other),
+        }
+    };
+}
+
+fn parse_token(accounts: &[String], data: &[u8]) -> Result<Value, String> {
+    let instruction = Token::unpack(data).map_err(|e| format!("Invalid SPL Token instruction: {}", e))?;
+    Ok(match parse_token_common!(Token, instruction, accounts) {
+        Ok(parsed) => parsed,
+        Err(other) => parsed(&variant_name(&other), accounts, &["mint"], json!({})),
+    })
+}
+
+#[allow(deprecated)]
+fn parse_token_2022(accounts: &[String], data: &[u8]) -> Result<Value, String> {
+    let instruction = Token2022::unpack(data).map_err(|e| format!("Invalid Token-2022 instruction: {}", e))?;
+    let other = match parse_token_common!(Token2022, instruction, accounts) {
+        Ok(parsed) => return Ok(parsed),
+        Err(other) => other,
+    };
+    let extension_names =
+        |types: &[spl_token_2022::extension::ExtensionType]| types.iter().map(variant_name).collect::<Vec<_>>();
+    Ok(match other {
+        Token2022::GetAccountDataSize { extension_types } => parsed(
+            "getAccountDataSize",
+            accounts,
+            &["mint"],
+            json!({"extensionTypes": extension_names(&extension_types)}),
+        ),
+        Token2022::Reallocate { extension_types } => token_parsed(
+            "reallocate",
+            accounts,
+            &["account", "payer", "systemProgram", "owner"],
+            json!({"extensionTypes": extension_names(&extension_types)}),
+        ),
+        Token2022::InitializeMintCloseAuthority { close_authority } => parsed(
+            "initializeMintCloseAuthority",
+            accounts,
+            &["mint"],
+            json!({"closeAuthority": optional_pubkey(close_authority)}),
+        ),
+        Token2022::InitializePermanentDelegate { delegate } => parsed(
+            "initializePermanentDelegate",
+            accounts,
+            &["mint"],
+            json!({"delegate": delegate.to_string()}),
+        ),
+        Token2022::CreateNativeMint => {
+            parsed("createNativeMint", accounts, &["payer", "nativeMint", "systemProgram"], json!({}))
+        }
+        Token2022::InitializeNonTransferableMint => {
+            parsed("initializeNonTransferableMint", accounts, &["mint"], json!({}))
+        }
+        Token2022::WithdrawExcessLamports => token_parsed(
+            "withdrawExcessLamports",
+            accounts,
+            &["source", "destination", "authority"],
+            json!({}),
+        ),
+        // Extension instruction groups carry their own sub-instruction; only
+        // the group is named here.
+        other => parsed(&variant_name(&other), accounts, &[], json!({})),
+    })
+}
+
+fn parse_associated_token(accounts: &[String], data: &[u8]) -> Result<Value, String> {
+    const CREATE_ACCOUNTS: &[&str] = &["source", "account", "wallet", "mint", "systemProgram", "tokenProgram"];
+    // An empty data buffer is the original `Create` encoding.
+    match data.first() {
+        None | Some(0) => Ok(parsed("create", accounts, CREATE_ACCOUNTS, json!({}))),
+        Some(1) => Ok(parsed("createIdempotent", accounts, CREATE_ACCOUNTS, json!({}))),
+        Some(2) => Ok(parsed(
+            "recoverNested",
+            accounts,
+            &["nestedSource", "nestedMint", "destination", "nestedOwner", "ownerMint", "wallet", "tokenProgram"],
+            json!({}),
+        )),
+        Some(tag) => Err(format!("Unknown Associated Token Account instruction {}", tag)),
+    }
+}
+
+fn parse_memo(accounts: &[String], data: &[u8]) -> Result<Value, String> {
+    let memo = std::str::from_utf8(data).map_err(|e| format!("Memo is not valid UTF-8: {}", e))?;
+    Ok(parsed_with_extra("memo", accounts, &[], "signers", json!({"memo": memo})))
+}
+
+fn parse_compute_budget(accounts: &[String], data: &[u8]) -> Result<Value, String> {
+    let instruction: ComputeBudgetInstruction = solana_sdk::borsh1::try_from_slice_unchecked(data)
+        .map_err(|e| format!("Invalid Compute Budget instruction: {}", e))?;
+    Ok(match instruction {
+        ComputeBudgetInstruction::Unused => parsed("unused", accounts, &[], json!({})),
+        ComputeBudgetInstruction::RequestHeapFrame(bytes) => {
+            parsed("requestHeapFrame", accounts, &[], json!({"bytes": bytes}))
+        }
+        ComputeBudgetInstruction::SetComputeUnitLimit(units) => {
+            parsed("setComputeUnitLimit", accounts, &[], json!({"units": units}))
+        }
+        ComputeBudgetInstruction::SetComputeUnitPrice(micro_lamports) => {
+            parsed("setComputeUnitPrice", accounts, &[], json!({"microLamports": micro_lamports}))
+        }
+        ComputeBudgetInstruction::SetLoadedAccountsDataSizeLimit(bytes) => {
+            parsed("setLoadedAccountsDataSizeLimit", accounts, &[], json!({"bytes": bytes}))
+        }
+    })
+}
+
+fn authorized_json(authorized: &Authorized) -> Value {
+    json!({"staker": authorized.staker.to_string(), "withdrawer": authorized.withdrawer.to_string()})
+}
+
+fn lockup_json(lockup: &Lockup) -> Value {
+    json!({
+        "unixTimestamp": lockup.unix_timestamp,
+        "epoch": lockup.epoch,
+        "custodian": lockup.custodian.to_string(),
+    })
+}
+
+#[allow(deprecated)]
+fn parse_stake(accounts: &[String], data: &[u8]) -> Result<Value, String> {
+    let instruction: StakeInstruction =
+        bincode::deserialize(data).map_err(|e| format!("Invalid Stake instruction: {}", e))?;
+    Ok(match instruction {
+        StakeInstruction::Initialize(authorized, lockup) => parsed(
+            "initialize",
+            accounts,
+            &["stakeAccount", "rentSysvar"],
+            json!({"authorized": authorized_json(&authorized), "lockup": lockup_json(&lockup)}),
+        ),
+        StakeInstruction::Authorize(new_authority, authority_type) => parsed(
+            "authorize",
+            accounts,
+            &["stakeAccount", "clockSysvar", "authority", "custodian"],
+            json!({"newAuthority": new_authority.to_string(), "authorityType": variant_name(&authority_type)}),
+        ),
+        StakeInstruction::DelegateStake => parsed(
+            "delegate",
+            accounts,
+            &["stakeAccount", "voteAccount", "clockSysvar", "stakeHistorySysvar", "stakeConfigAccount", "stakeAuthority"],
+            json!({}),
+        ),
+        StakeInstruction::Split(lamports) => parsed(
+            "split",
+            accounts,
+            &["stakeAccount", "newSplitAccount", "stakeAuthority"],
+            json!({"lamports": lamports}),
+        ),
+        StakeInstruction::Withdraw(lamports) => parsed(
+            "withdraw",
+            accounts,
+            &["stakeAccount", "destination", "clockSysvar", "stakeHistorySysvar", "withdrawAuthority", "custodian"],
+            json!({"lamports": lamports}),
+        ),
+        StakeInstruction::Deactivate => parsed(
+            "deactivate",
+            accounts,
+            &["stakeAccount", "clockSysvar", "stakeAuthority"],
+            json!({}),
+        ),
+        StakeInstruction::SetLockup(args) => parsed(
+            "setLockup",
+            accounts,
+            &["stakeAccount", "custodian"],
+            json!({"lockup": {
+                "unixTimestamp": args.unix_timestamp,
+                "epoch": args.epoch,
+                "custodian": args.custodian.map(|c| c.to_string()),
+            }}),
+        ),
+        StakeInstruction::Merge => parsed(
+            "merge",
+            accounts,
+            &["destination", "source", "clockSysvar", "stakeHistorySysvar", "stakeAuthority"],
+            json!({}),
+        ),
+        StakeInstruction::AuthorizeWithSeed(args) => parsed(
+            "authorizeWithSeed",
+            accounts,
+            &["stakeAccount", "authorityBase", "clockSysvar", "custodian"],
+            json!({
+                "newAuthorized": args.new_authorized_pubkey.to_string(),
+                "authorityType": variant_name(&args.stake_authorize),
+                "authoritySeed": args.authority_seed,
+                "authorityOwner": args.authority_owner.to_string(),
+            }),
+        ),
+        StakeInstruction::InitializeChecked => parsed(
+            "initializeChecked",
+            accounts,
+            &["stakeAccount", "rentSysvar", "staker", "withdrawer"],
+            json!({}),
+        ),
+        StakeInstruction::AuthorizeChecked(authority_type) => parsed(
+            "authorizeChecked",
+            accounts,
+            &["stakeAccount", "clockSysvar", "authority", "newAuthority", "custodian"],
+            json!({"authorityType": variant_name(&authority_type)}),
+        ),
+        StakeInstruction::AuthorizeCheckedWithSeed(args) => parsed(
+            "authorizeCheckedWithSeed",
+            accounts,
+            &["stakeAccount", "authorityBase", "clockSysvar", "newAuthority", "custodian"],
+            json!({
+                "authorityType": variant_name(&args.stake_authorize),
+                "authoritySeed": args.authority_seed,
+                "authorityOwner": args.authority_owner.to_string(),
+            }),
+        ),
+        StakeInstruction::SetLockupChecked(args) => parsed(
+            "setLockupChecked",
+            accounts,
+            &["stakeAccount", "custodian", "newCustodian"],
+            json!({"lockup": {"unixTimestamp": args.unix_timestamp, "epoch": args.epoch}}),
+        ),
+        StakeInstruction::GetMinimumDelegation => parsed("getMinimumDelegation", accounts, &[], json!({})),
+        StakeInstruction::DeactivateDelinquent => parsed(
+            "deactivateDelinquent",
+            accounts,
+            &["stakeAccount", "voteAccount", "referenceVoteAccount"],
+            json!({}),
+        ),
+        StakeInstruction::Redelegate => parsed(
+            "redelegate",
+            accounts,
+            &["stakeAccount", "newStakeAccount", "voteAccount", "stakeConfigAccount", "stakeAuthority"],
+            json!({}),
+        ),
+    })
+}
+
+fn vote_json(vote: &Vote) -> Value {
+    json!({"slots": vote.slots, "hash": vote.hash.to_string(), "timestamp": vote.timestamp})
+}
+
+fn vote_state_update_json(update: &VoteStateUpdate) -> Value {
+    let lockouts: Vec<Value> = update
+        .lockouts
+        .iter()
+        .map(|lockout| json!({"slot": lockout.slot(), "confirmationCount": lockout.confirmation_count()}))
+        .collect();
+    json!({
+        "lockouts": lockouts,
+        "root": update.root,
+        "hash": update.hash.to_string(),
+        "timestamp": update.timestamp,
+    })
+}
+
+fn parse_vote(accounts: &[String], data: &[u8]) -> Result<Value, String> {
+    const VOTE_ACCOUNTS: &[&str] = &["voteAccount", "slotHashesSysvar", "clockSysvar", "voteAuthority"];
+    const UPDATE_ACCOUNTS: &[&str] = &["voteAccount", "voteAuthority"];
+    let instruction: VoteInstruction =
+        bincode::deserialize(data).map_err(|e| format!("Invalid Vote instruction: {}", e))?;
+    Ok(match instruction {
+        VoteInstruction::InitializeAccount(init) => parsed(
+            "initialize",
+            accounts,
+            &["voteAccount", "rentSysvar", "clockSysvar", "node"],
+            json!({
+                "authorizedVoter": init.authorized_voter.to_string(),
+                "authorizedWithdrawer": init.authorized_withdrawer.to_string(),
+                "commission": init.commission,
+            }),
+        ),
+        VoteInstruction::Authorize(new_authority, authority_type) => parsed(
+            "authorize",
+            accounts,
+            &["voteAccount", "clockSysvar", "authority"],
+            json!({"newAuthority": new_authority.to_string(), "authorityType": variant_name(&authority_type)}),
+        ),
+        VoteInstruction::Vote(vote) => parsed("vote", accounts, VOTE_ACCOUNTS, json!({"vote": vote_json(&vote)})),
+        VoteInstruction::VoteSwitch(vote, hash) => parsed(
+            "voteSwitch",
+            accounts,
+            VOTE_ACCOUNTS,
+            json!({"vote": vote_json(&vote), "hash": hash.to_string()}),
+        ),
+        VoteInstruction::Withdraw(lamports) => parsed(
+            "withdraw",
+            accounts,
+            &["voteAccount", "destination", "withdrawAuthority"],
+            json!({"lamports": lamports}),
+        ),
+        VoteInstruction::UpdateValidatorIdentity => parsed(
+            "updateValidatorIdentity",
+            accounts,
+            &["voteAccount", "newValidatorIdentity", "withdrawAuthority"],
+            json!({}),
+        ),
+        VoteInstruction::UpdateCommission(commission) => parsed(
+            "updateCommission",
+            accounts,
+            &["voteAccount", "withdrawAuthority"],
+            json!({"commission": commission}),
+        ),
+        VoteInstruction::AuthorizeChecked(authority_type) => parsed(
+            "authorizeChecked",
+            accounts,
+            &["voteAccount", "clockSysvar", "authority", "newAuthority"],
+            json!({"authorityType": variant_name(&authority_type)}),
+        ),
+        VoteInstruction::UpdateVoteState(update) => parsed(
+            "updateVoteState",
+            accounts,
+            UPDATE_ACCOUNTS,
+            json!({"voteStateUpdate": vote_state_update_json(&update)}),
+        ),
+        VoteInstruction::UpdateVoteStateSwitch(update, hash) => parsed(
+            "updateVoteStateSwitch",
+            accounts,
+            UPDATE_ACCOUNTS,
+            json!({"voteStateUpdate": vote_state_update_json(&update), "hash": hash.to_string()}),
+        ),
+        VoteInstruction::CompactUpdateVoteState(update) => parsed(
+            "compactUpdateVoteState",
+            accounts,
+            UPDATE_ACCOUNTS,
+            json!({"voteStateUpdate": vote_state_update_json(&update)}),
+        ),
+        VoteInstruction::CompactUpdateVoteStateSwitch(update, hash) => parsed(
+            "compactUpdateVoteStateSwitch",
+            accounts,
+            UPDATE_ACCOUNTS,
+            json!({"voteStateUpdate": vote_state_update_json(&update), "hash": hash.to_string()}),
+        ),
+        VoteInstruction::AuthorizeWithSeed(args) => parsed(
+            "authorizeWithSeed",
+            accounts,
+            &["voteAccount", "clockSysvar", "authorityBase"],
+            json!({
+                "authorityType": variant_name(&args.authorization_type),
+                "authorityOwner": args.current_authority_derived_key_owner.to_string(),
+                "authoritySeed": args.current_authority_derived_key_seed,
+                "newAuthority": args.new_authority.to_string(),
+            }),
+        ),
+        VoteInstruction::AuthorizeCheckedWithSeed(args) => parsed(
+            "authorizeCheckedWithSeed",
+            accounts,
+            &["voteAccount", "clockSysvar", "authorityBase", "newAuthority"],
+            json!({
+                "authorityType": variant_name(&args.authorization_type),
+                "authorityOwner": args.current_authority_derived_key_owner.to_string(),
+                "authoritySeed": args.current_authority_derived_key_seed,
+            }),
+        ),
+    })
+}
+
+fn parse_lookup_table(accounts: &[String], data: &[u8]) -> Result<Value, String> {
+    const AUTHORITY_ACCOUNTS: &[&str] = &["lookupTableAccount", "lookupTableAuthority"];
+    let instruction: LookupTableInstruction =
+        bincode::deserialize(data).map_err(|e| format!("Invalid Address Lookup Table instruction: {}", e))?;
+    Ok(match instruction {
+        LookupTableInstruction::CreateLookupTable { recent_slot, bump_seed } => parsed(
+            "createLookupTable",
+            accounts,
+            &["lookupTableAccount", "lookupTableAuthority", "payerAccount", "systemProgram"],
+            json!({"recentSlot": recent_slot, "bumpSeed": bump_seed}),
+        ),
+        LookupTableInstruction::FreezeLookupTable => {
+            parsed("freezeLookupTable", accounts, AUTHORITY_ACCOUNTS, json!({}))
+        }
+        LookupTableInstruction::ExtendLookupTable { new_addresses } => parsed(
+            "extendLookupTable",
+            accounts,
+            &["lookupTableAccount", "lookupTableAuthority", "payerAccount", "systemProgram"],
+            json!({"newAddresses": new_addresses.iter().map(|a| a.to_string()).collect::<Vec<_>>()}),
+        ),
+        LookupTableInstruction::DeactivateLookupTable => {
+            parsed("deactivateLookupTable", accounts, AUTHORITY_ACCOUNTS, json!({}))
+        }
+        LookupTableInstruction::CloseLookupTable => parsed(
+            "closeLookupTable",
+            accounts,
+            &["lookupTableAccount", "lookupTableAuthority", "recipient"],
+            json!({}),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::system_instruction;
+
+    fn accounts(count: u8) -> Vec<String> {
+        (1..=count).map(|i| Pubkey::new_from_array([i; 32]).to_string()).collect()
+    }
+
+    #[test]
+    fn keeps_accounts_and_data_fields_apart() {
+        let keys = accounts(3);
+        let base_field = Pubkey::new_from_array([9; 32]);
+        let instruction = system_instruction::create_account_with_seed(
+            &Pubkey::new_from_array([1; 32]),
+            &Pubkey::new_from_array([2; 32]),
+            &base_field,
+            "seed",
+            1,
+            0,
+            &system_program::id(),
+        );
+        let decoded = decode_instruction(&system_program::id(), keys.clone(), &instruction.data);
+        let parsed = decoded.parsed.unwrap();
+        assert_eq!(parsed["type"], "createAccountWithSeed");
+        assert_eq!(parsed["accounts"]["base"], keys[2]);
+        assert_eq!(parsed["info"]["base"], base_field.to_string());
+        assert_eq!(parsed["info"]["lamports"], 1);
+    }
+
+    #[test]
+    fn names_extra_accounts_by_program() {
+        let transfer = system_instruction::transfer(&Pubkey::new_unique(), &Pubkey::new_unique(), 5);
+        let parsed = decode_instruction(&system_program::id(), accounts(3), &transfer.data).parsed.unwrap();
+        assert_eq!(parsed["accounts"]["remainingAccounts"], json!([accounts(3)[2]]));
+        assert!(parsed["accounts"].get("signers").is_none());
+
+        let data = Token::Transfer { amount: 7 }.pack();
+        let parsed = decode_instruction(&spl_token::id(), accounts(5), &data).parsed.unwrap();
+        assert_eq!(parsed["accounts"]["authority"], accounts(5)[2]);
+        assert_eq!(parsed["accounts"]["signers"], json!(accounts(5)[3..]));
+        assert_eq!(parsed["info"]["amount"], 7);
+    }
+
+    #[test]
+    fn reports_unparseable_data_for_known_programs() {
+        let decoded = decode_instruction(&system_program::id(), accounts(1), &[0xff]);
+        assert!(decoded.parsed.is_none());
+        assert!(decoded.error.unwrap().starts_with("Invalid System instruction"));
+    }
+}
@@ -0,0 +1,69 @@
+use axum::{Json, extract::{Path, State}};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::auth::{ApiKeyInfo, ApiKeyStore, Scope};
+use crate::error::{ApiError, ApiJson};
+use crate::handlers::SuccessResponse;
+use crate::openapi::ApiSchema;
+
+#[derive(Deserialize, ApiSchema)]
+pub struct CreateApiKeyRequest {
+    /// Who or what the key is for, e.g. `billing-service`.
+    #[schema(example = "billing-service")]
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct CreateApiKeyResponse {
+    /// The key to send in `X-API-Key`. Only its hash is stored, so this is
+    /// the one time it is shown.
+    pub key: String,
+    #[serde(flatten)]
+    pub info: ApiKeyInfo,
+}
+
+pub async fn create_api_key(
+    State(store): State<Arc<ApiKeyStore>>,
+    ApiJson(payload): ApiJson<CreateApiKeyRequest>,
+) -> Result<Json<SuccessResponse<CreateApiKeyResponse>>, ApiError> {
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::missing_fields().at("name"));
+    }
+    if payload.scopes.is_empty() {
+        return Err(ApiError::missing_fields().at("scopes"));
+    }
+
+    let (key, info) = store.create(name.to_string(), payload.scopes)?;
+    tracing::info!("Created API key {} ({})", info.id, info.name);
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: CreateApiKeyResponse { key, info },
+    }))
+}
+
+pub async fn list_api_keys(
+    State(store): State<Arc<ApiKeyStore>>,
+) -> Result<Json<SuccessResponse<Vec<ApiKeyInfo>>>, ApiError> {
+    let keys = store.list()?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: keys,
+    }))
+}
+
+pub async fn delete_api_key(
+    State(store): State<Arc<ApiKeyStore>>,
+    Path(key_id): Path<String>,
+) -> Result<Json<SuccessResponse<ApiKeyInfo>>, ApiError> {
+    let key = store.delete(&key_id)?;
+    tracing::info!("Deleted API key {} ({})", key.id, key.name);
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: key,
+    }))
+}
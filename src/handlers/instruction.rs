@@ -0,0 +1,62 @@
+use axum::Json;
+use serde::Deserialize;
+use crate::decoder::{self, DecodedInstruction};
+use crate::error::{ApiError, ApiJson, ErrorCode};
+use crate::handlers::SuccessResponse;
+use crate::utils::{decode_base64, validate_pubkey};
+use crate::openapi::ApiSchema;
+
+/// Takes the same shape the instruction builders return, so their output can
+/// be passed straight back in.
+#[derive(Deserialize, ApiSchema)]
+pub struct DecodeInstructionRequest {
+    #[schema(format = "base58", example = "11111111111111111111111111111111")]
+    pub program_id: String,
+    #[serde(default)]
+    pub accounts: Vec<AccountInput>,
+    /// Base64 encoded instruction data.
+    #[schema(format = "base64", example = "AgAAAKCGAQAAAAAA")]
+    pub instruction_data: String,
+}
+
+/// An account as a bare pubkey or as an `AccountMetaResponse`-style object.
+#[derive(Deserialize, ApiSchema)]
+#[serde(untagged)]
+pub enum AccountInput {
+    Pubkey(String),
+    Meta { pubkey: String },
+}
+
+pub async fn decode_instruction(
+    ApiJson(payload): ApiJson<DecodeInstructionRequest>,
+) -> Result<Json<SuccessResponse<DecodedInstruction>>, ApiError> {
+    let program_id = validate_pubkey(&payload.program_id)
+        .map_err(|e| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid program_id: {}", e)).at("program_id"))?;
+
+    let accounts = payload
+        .accounts
+        .into_iter()
+        .enumerate()
+        .map(|(i, account)| {
+            let pubkey = match account {
+                AccountInput::Pubkey(pubkey) | AccountInput::Meta { pubkey } => pubkey,
+            };
+            validate_pubkey(&pubkey)
+                .map(|pubkey| pubkey.to_string())
+                .map_err(|e| {
+                    ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid account {}: {}", pubkey, e))
+                        .at(format!("accounts[{}]", i))
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let data = decode_base64(&payload.instruction_data)
+        .map_err(|e| {
+            ApiError::new(ErrorCode::InvalidEncoding, format!("Invalid instruction_data: {}", e)).at("instruction_data")
+        })?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: decoder::decode_instruction(&program_id, accounts, &data),
+    }))
+}
@@ -1,36 +1,454 @@
-use axum::{Json, http::StatusCode};
-use serde::Serialize;
-use solana_sdk::signature::{Keypair, Signer};
-use crate::utils::{encode_base58};
+use axum::{Json, body::{Body, Bytes}, extract::{FromRequest, Request}, http::HeaderMap};
+use bip39::{Language, Mnemonic, MnemonicType};
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::{keypair_from_seed, Keypair, Signer};
+use crate::error::{ApiError, ApiJson, ErrorCode};
+use crate::handlers::SuccessResponse;
+use crate::keystore::{decrypt_keypair, encrypt_keypair, Keystore};
+use crate::utils::{encode_base58, keypair_from_mnemonic, validate_private_key, SecretFormat};
+use crate::openapi::ApiSchema;
 
-#[derive(Serialize)]
-pub struct SuccessResponse<T> {
-    pub success: bool,
-    pub data: T,
+/// Maximum number of accounts that can be derived in a single request.
+const MAX_DERIVED_ACCOUNTS: u32 = 100;
+
+/// Optional body for `/keypair`. With no body a random keypair is generated,
+/// exactly as before. Setting `word_count` or `mnemonic` switches to BIP39
+/// derivation along `m/44'/501'/{account}'/{change}'`.
+#[derive(Deserialize, Default, ApiSchema)]
+pub struct GenerateKeypairRequest {
+    /// Generate a fresh mnemonic with this many words (12 or 24).
+    #[schema(example = "12")]
+    pub word_count: Option<usize>,
+    /// Re-derive accounts from an existing mnemonic instead of generating one.
+    pub mnemonic: Option<String>,
+    pub passphrase: Option<String>,
+    #[serde(default)]
+    pub account: u32,
+    #[serde(default)]
+    pub change: u32,
+    /// Number of consecutive accounts to derive, starting at `account`.
+    pub count: Option<u32>,
+    #[serde(default)]
+    pub format: KeypairFormat,
+    /// Required for the `keystore` format.
+    pub password: Option<String>,
 }
 
-#[derive(Serialize)]
-pub struct ErrorResponse {
-    pub success: bool,
-    pub error: String,
+/// Output format for generated secrets. `base58` is what `/keypair` has always
+/// returned; `keygen_json` additionally returns the solana-keygen `id.json`
+/// byte array so it can be saved straight to disk; `keystore` returns the
+/// secret only as a password-encrypted envelope.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, ApiSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum KeypairFormat {
+    #[default]
+    Base58,
+    KeygenJson,
+    Keystore,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ApiSchema)]
 pub struct KeypairResponse {
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
+    pub pubkey: String,
+    /// Omitted in `keystore` format, where the secret only leaves encrypted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(format = "base58")]
+    pub secret: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keypair_json: Option<Vec<u8>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keystore: Option<Keystore>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mnemonic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "m/44'/501'/0'/0'")]
+    pub derivation_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accounts: Option<Vec<DerivedAccount>>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct DerivedAccount {
+    #[schema(example = "m/44'/501'/0'/0'")]
+    pub derivation_path: String,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
+    pub pubkey: String,
+    #[schema(format = "base58")]
+    pub secret: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keypair_json: Option<Vec<u8>>,
+}
+
+#[derive(Deserialize, ApiSchema)]
+pub struct ExportKeypairRequest {
+    pub secret: String,
+    pub passphrase: Option<String>,
+    #[schema(example = "m/44'/501'/0'/0'")]
+    pub derivation_path: Option<String>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct ExportKeypairResponse {
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
+    pub pubkey: String,
+    /// Contents of a solana-keygen `id.json` file.
+    pub keypair_json: Vec<u8>,
+    pub secret_format: SecretFormat,
+}
+
+/// Pasted form of `/keypair/import`; a raw `id.json` upload is accepted too.
+#[derive(Deserialize, ApiSchema)]
+pub struct ImportKeypairRequest {
+    pub keypair: KeypairFileContents,
+}
+
+#[derive(Deserialize, ApiSchema)]
+#[serde(untagged)]
+pub enum KeypairFileContents {
+    Bytes(Vec<u8>),
+    Text(String),
+}
+
+#[derive(Deserialize, ApiSchema)]
+pub struct DecryptKeypairRequest {
+    pub keystore: Keystore,
+    pub password: String,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct ImportKeypairResponse {
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
     pub pubkey: String,
+    #[schema(format = "base58")]
     pub secret: String,
 }
 
-pub async fn generate_keypair() -> Result<Json<SuccessResponse<KeypairResponse>>, (StatusCode, Json<ErrorResponse>)> {
-    let keypair = Keypair::new();
-    let pubkey = keypair.pubkey().to_string();
-    let secret = encode_base58(&keypair.to_bytes());
-    
+fn keypair_json(bytes: &[u8; 64], format: KeypairFormat) -> Option<Vec<u8>> {
+    (format == KeypairFormat::KeygenJson).then(|| bytes.to_vec())
+}
+
+/// Fields that only apply to mnemonic derivation, with whether `payload` sets them.
+fn derivation_fields(payload: &GenerateKeypairRequest) -> [(&'static str, bool); 4] {
+    [
+        ("passphrase", payload.passphrase.is_some()),
+        ("account", payload.account != 0),
+        ("change", payload.change != 0),
+        ("count", payload.count.is_some()),
+    ]
+}
+
+pub async fn generate_keypair(
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<SuccessResponse<KeypairResponse>>, ApiError> {
+    // Plain `POST /keypair` with an empty body keeps working, whatever its
+    // content type.
+    let mut payload = if body.iter().all(u8::is_ascii_whitespace) {
+        GenerateKeypairRequest::default()
+    } else {
+        let mut request = Request::new(Body::from(body));
+        *request.headers_mut() = headers;
+        let ApiJson(payload) = ApiJson::from_request(request, &()).await?;
+        payload
+    };
+
+    let phrase = match (payload.mnemonic.take(), payload.word_count) {
+        (Some(_), Some(_)) => {
+            return Err(ApiError::invalid_request("Provide either mnemonic or word_count, not both").at("word_count"));
+        }
+        (Some(phrase), None) => phrase,
+        (None, Some(word_count)) => {
+            let mnemonic_type = match word_count {
+                12 => MnemonicType::Words12,
+                24 => MnemonicType::Words24,
+                _ => return Err(ApiError::invalid_request("word_count must be 12 or 24").at("word_count")),
+            };
+            Mnemonic::new(mnemonic_type, Language::English).into_phrase()
+        }
+        (None, None) => {
+            if let Some((field, _)) = derivation_fields(&payload).into_iter().find(|(_, set)| *set) {
+                return Err(ApiError::invalid_request(format!(
+                    "{} only applies to mnemonic derivation; set mnemonic or word_count",
+                    field
+                ))
+                .at(field));
+            }
+            let keypair = Keypair::new();
+            let bytes = keypair.to_bytes();
+            if payload.format == KeypairFormat::Keystore {
+                let password = payload
+                    .password
+                    .ok_or_else(|| {
+                        ApiError::new(ErrorCode::MissingField, "password is required for the keystore format").at("password")
+                    })?;
+                let keystore = encrypt_keypair(&bytes, &password)
+                    .await
+                    .map_err(|e| ApiError::invalid_request(e).at("password"))?;
+                return Ok(Json(SuccessResponse {
+                    success: true,
+                    data: KeypairResponse {
+                        pubkey: keypair.pubkey().to_string(),
+                        secret: None,
+                        keypair_json: None,
+                        keystore: Some(keystore),
+                        mnemonic: None,
+                        derivation_path: None,
+                        accounts: None,
+                    },
+                }));
+            }
+            return Ok(Json(SuccessResponse {
+                success: true,
+                data: KeypairResponse {
+                    pubkey: keypair.pubkey().to_string(),
+                    secret: Some(encode_base58(&bytes)),
+                    keypair_json: keypair_json(&bytes, payload.format),
+                    keystore: None,
+                    mnemonic: None,
+                    derivation_path: None,
+                    accounts: None,
+                },
+            }));
+        }
+    };
+
+    if payload.format == KeypairFormat::Keystore {
+        return Err(ApiError::invalid_request("The keystore format cannot be combined with mnemonic derivation").at("format"));
+    }
+
+    let count = payload.count.unwrap_or(1);
+    if count == 0 || count > MAX_DERIVED_ACCOUNTS {
+        return Err(ApiError::invalid_request(format!("count must be between 1 and {}", MAX_DERIVED_ACCOUNTS)).at("count"));
+    }
+    let last_account = payload
+        .account
+        .checked_add(count - 1)
+        .ok_or_else(|| ApiError::invalid_request("account index out of range").at("account"))?;
+
+    let passphrase = payload.passphrase.unwrap_or_default();
+    let mut accounts = Vec::with_capacity(count as usize);
+    for account in payload.account..=last_account {
+        let derivation_path = format!("m/44'/501'/{}'/{}'", account, payload.change);
+        let bytes = keypair_from_mnemonic(&phrase, &passphrase, &derivation_path)
+            .map_err(|e| ApiError::new(ErrorCode::InvalidMnemonic, e).at("mnemonic"))?;
+        accounts.push(DerivedAccount {
+            derivation_path,
+            pubkey: encode_base58(&bytes[32..]),
+            secret: encode_base58(&bytes),
+            keypair_json: keypair_json(&bytes, payload.format),
+        });
+    }
+
+    let first = &accounts[0];
     Ok(Json(SuccessResponse {
         success: true,
         data: KeypairResponse {
-            pubkey,
-            secret,
+            pubkey: first.pubkey.clone(),
+            secret: Some(first.secret.clone()),
+            keypair_json: first.keypair_json.clone(),
+            keystore: None,
+            mnemonic: Some(phrase),
+            derivation_path: Some(first.derivation_path.clone()),
+            accounts: Some(accounts),
+        },
+    }))
+}
+
+pub async fn export_keypair(
+    ApiJson(payload): ApiJson<ExportKeypairRequest>,
+) -> Result<Json<SuccessResponse<ExportKeypairResponse>>, ApiError> {
+    let decoded = validate_private_key(
+        &payload.secret,
+        payload.passphrase.as_deref(),
+        payload.derivation_path.as_deref(),
+    )
+    .map_err(|e| e.at("secret"))?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: ExportKeypairResponse {
+            pubkey: encode_base58(&decoded.bytes[32..]),
+            keypair_json: decoded.bytes.to_vec(),
+            secret_format: decoded.format,
+        },
+    }))
+}
+
+/// Accepts either `{"keypair": [..]}` / `{"keypair": "[..]"}` or the raw
+/// contents of an `id.json` file as the request body, e.g.
+/// `curl --data-binary @~/.config/solana/id.json`.
+pub async fn import_keypair(
+    body: Bytes,
+) -> Result<Json<SuccessResponse<ImportKeypairResponse>>, ApiError> {
+    let bytes = if let Ok(file) = serde_json::from_slice::<Vec<u8>>(&body) {
+        file
+    } else {
+        let payload: ImportKeypairRequest = serde_json::from_slice(&body)
+            .map_err(|e| {
+                ApiError::new(ErrorCode::InvalidJson, format!("Expected an id.json byte array or {{\"keypair\": ...}}: {}", e))
+            })?;
+        match payload.keypair {
+            KeypairFileContents::Bytes(bytes) => bytes,
+            KeypairFileContents::Text(text) => serde_json::from_str(text.trim())
+                .map_err(|e| ApiError::new(ErrorCode::InvalidSecretKey, format!("Invalid keypair file: {}", e)).at("keypair"))?,
+        }
+    };
+
+    if bytes.len() != 64 {
+        return Err(ApiError::new(
+            ErrorCode::InvalidSecretLength,
+            format!("Keypair file must contain 64 bytes, got {}", bytes.len()),
+        ));
+    }
+    let keypair = keypair_from_seed(&bytes[..32])
+        .map_err(|e| ApiError::new(ErrorCode::InvalidSecretKey, format!("Invalid keypair file: {}", e)))?;
+    if keypair.pubkey().as_ref() != &bytes[32..] {
+        return Err(ApiError::new(ErrorCode::InvalidSecretKey, "Keypair file's public key does not match its secret"));
+    }
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: ImportKeypairResponse {
+            pubkey: keypair.pubkey().to_string(),
+            secret: encode_base58(&keypair.to_bytes()),
+        },
+    }))
+}
+
+pub async fn decrypt_keystore(
+    ApiJson(payload): ApiJson<DecryptKeypairRequest>,
+) -> Result<Json<SuccessResponse<ImportKeypairResponse>>, ApiError> {
+    let bytes = decrypt_keypair(&payload.keystore, &payload.password)
+        .await
+        .map_err(|e| ApiError::invalid_request(format!("Invalid keystore: {}", e)).at("keystore"))?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: ImportKeypairResponse {
+            pubkey: payload.keystore.pubkey,
+            secret: encode_base58(&bytes),
         },
     }))
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::header::CONTENT_TYPE;
+
+    async fn generate(content_type: Option<&str>, body: &str) -> Result<KeypairResponse, ApiError> {
+        let mut headers = HeaderMap::new();
+        if let Some(content_type) = content_type {
+            headers.insert(CONTENT_TYPE, content_type.parse().unwrap());
+        }
+        generate_keypair(headers, Bytes::from(body.to_string())).await.map(|Json(response)| response.data)
+    }
+
+    #[tokio::test]
+    async fn an_empty_body_generates_a_random_keypair() {
+        for (content_type, body) in [(None, ""), (Some("application/json"), ""), (Some("application/json"), " \n")] {
+            let keypair = generate(content_type, body).await.unwrap_or_else(|e| panic!("{:?}: {}", content_type, e));
+            let secret = keypair.secret.expect("base58 format returns the secret");
+            assert_eq!(encode_base58(&bs58::decode(secret).into_vec().unwrap()[32..]), keypair.pubkey);
+            assert!(keypair.mnemonic.is_none() && keypair.accounts.is_none());
+        }
+        let keypair = generate(Some("application/json"), r#"{"format":"keygen_json"}"#).await.ok();
+        assert_eq!(keypair.and_then(|keypair| keypair.keypair_json).map(|bytes| bytes.len()), Some(64));
+    }
+
+    #[tokio::test]
+    async fn a_body_must_still_be_json() {
+        let error = generate(None, r#"{"word_count":12}"#).await.err().map(|e| e.code);
+        assert_eq!(error, Some(ErrorCode::UnsupportedMediaType));
+        let error = generate(Some("application/json"), "{").await.err().map(|e| e.code);
+        assert_eq!(error, Some(ErrorCode::InvalidJson));
+    }
+
+    #[tokio::test]
+    async fn derivation_fields_need_a_mnemonic() {
+        for (body, field) in [
+            (r#"{"passphrase":""}"#, "passphrase"),
+            (r#"{"account":1}"#, "account"),
+            (r#"{"change":1}"#, "change"),
+            (r#"{"count":2}"#, "count"),
+        ] {
+            let error = generate(Some("application/json"), body).await.err().expect("ignored field accepted");
+            assert_eq!((error.code, error.field.as_deref()), (ErrorCode::InvalidRequest, Some(field)));
+        }
+        let derived = generate(Some("application/json"), r#"{"word_count":12,"account":1,"count":2}"#).await.ok();
+        let paths = derived.and_then(|keypair| keypair.accounts).map(|accounts| {
+            accounts.into_iter().map(|account| account.derivation_path).collect::<Vec<_>>()
+        });
+        assert_eq!(paths, Some(vec!["m/44'/501'/1'/0'".to_string(), "m/44'/501'/2'/0'".to_string()]));
+    }
+
+    async fn import(body: String) -> Result<ImportKeypairResponse, ApiError> {
+        import_keypair(Bytes::from(body)).await.map(|Json(response)| response.data)
+    }
+
+    async fn export(secret: &str) -> Result<ExportKeypairResponse, ApiError> {
+        let payload = ExportKeypairRequest {
+            secret: secret.to_string(),
+            passphrase: None,
+            derivation_path: None,
+        };
+        export_keypair(ApiJson(payload)).await.map(|Json(response)| response.data)
+    }
+
+    fn id_json(keypair: &Keypair) -> String {
+        serde_json::to_string(&keypair.to_bytes().to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn keypair_files_round_trip_through_base58() {
+        let keypair = Keypair::new();
+        let imported = import(id_json(&keypair)).await.unwrap();
+        assert_eq!(imported.pubkey, keypair.pubkey().to_string());
+        assert_eq!(imported.secret, keypair.to_base58_string());
+
+        let exported = export(&imported.secret).await.unwrap();
+        assert_eq!(exported.secret_format, SecretFormat::Base58Keypair);
+        assert_eq!(exported.pubkey, imported.pubkey);
+        assert_eq!(serde_json::to_string(&exported.keypair_json).unwrap(), id_json(&keypair));
+
+        let exported = export(&id_json(&keypair)).await.unwrap();
+        assert_eq!(exported.secret_format, SecretFormat::JsonByteArray);
+        assert_eq!(exported.keypair_json, keypair.to_bytes());
+    }
+
+    #[tokio::test]
+    async fn pasted_and_uploaded_files_are_accepted() {
+        let keypair = Keypair::new();
+        let file = id_json(&keypair);
+        let bodies = [
+            file.clone(),
+            format!("{}\n", file),
+            format!(r#"{{"keypair":{}}}"#, file),
+            serde_json::json!({ "keypair": format!(" {}\n", file) }).to_string(),
+        ];
+        for body in bodies {
+            let imported = import(body.clone()).await.unwrap_or_else(|e| panic!("{}: {}", body, e));
+            assert_eq!(imported.pubkey, keypair.pubkey().to_string());
+        }
+
+        let error = import(r#"{"keypair":"not json"}"#.to_string()).await.err().unwrap();
+        assert_eq!((error.code, error.field.as_deref()), (ErrorCode::InvalidSecretKey, Some("keypair")));
+        let error = import(r#"{"secret":[1,2]}"#.to_string()).await.err().unwrap();
+        assert_eq!(error.code, ErrorCode::InvalidJson);
+    }
+
+    #[tokio::test]
+    async fn malformed_keypair_files_are_rejected() {
+        let bytes = Keypair::new().to_bytes();
+        let short = serde_json::to_string(&bytes[..63]).unwrap();
+        assert_eq!(import(short).await.err().map(|e| e.code), Some(ErrorCode::InvalidSecretLength));
+
+        let mut mismatched = bytes;
+        mismatched[32..].copy_from_slice(Keypair::new().pubkey().as_ref());
+        let mismatched = serde_json::to_string(&mismatched.to_vec()).unwrap();
+        let error = import(mismatched).await.err().unwrap();
+        assert_eq!(error.code, ErrorCode::InvalidSecretKey);
+        assert_eq!(error.message, "Keypair file's public key does not match its secret");
+    }
+}
@@ -1,148 +1,314 @@
-use axum::{Json, http::StatusCode};
+use axum::{Json, extract::State};
 use serde::{Deserialize, Serialize};
-use ed25519_dalek::{Keypair as Ed25519Keypair, PublicKey, SecretKey, Signature, Signer, Verifier};
-use crate::utils::{encode_base58, encode_base64, decode_base64, decode_base58, validate_private_key};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use ed25519_dalek::{verify_batch, Keypair as Ed25519Keypair, PublicKey, SecretKey, Signature, Signer, Verifier};
+use solana_sdk::pubkey::Pubkey;
+use std::sync::Arc;
+use crate::error::{ApiError, ApiJson, ErrorCode};
+use crate::handlers::vault::{resolve_signing_key, SigningKey};
+use crate::handlers::SuccessResponse;
+use crate::metrics::Metrics;
+use crate::offchain::{split_signed_envelope, MessageFormat, OffchainMessage, SIGNING_DOMAIN};
+use crate::utils::{
+    encode_base58, encode_base64, decode_base64, decode_base58, validate_pubkey, MessageEncoding, SecretFormat,
+    SignatureEncoding,
+};
+use crate::vault::KeyVault;
+use crate::openapi::ApiSchema;
 
-#[derive(Serialize)]
-pub struct SuccessResponse<T> {
-    pub success: bool,
-    pub data: T,
-}
-
-#[derive(Serialize)]
-pub struct ErrorResponse {
-    pub success: bool,
-    pub error: String,
-}
-
-#[derive(Deserialize)]
+#[derive(Deserialize, ApiSchema)]
 pub struct SignMessageRequest {
+    #[schema(example = "Hello, Solana!")]
     pub message: String,
-    pub secret: String,
+    /// How `message` is encoded; `utf8` signs the string's bytes as-is.
+    #[serde(default)]
+    pub message_encoding: MessageEncoding,
+    #[serde(default)]
+    pub signature_encoding: SignatureEncoding,
+    /// Either `secret` or the ID of a vault key in `key_id` is required.
+    pub secret: Option<String>,
+    pub key_id: Option<String>,
+    /// BIP39 passphrase, only used when `secret` is a mnemonic.
+    pub passphrase: Option<String>,
+    /// Derivation path, only used when `secret` is a mnemonic.
+    pub derivation_path: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ApiSchema)]
 pub struct SignMessageResponse {
     pub signature: String,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
     pub public_key: String,
     pub message: String,
+    pub message_encoding: MessageEncoding,
+    pub signature_encoding: SignatureEncoding,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_format: Option<SecretFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ApiSchema)]
 pub struct VerifyMessageRequest {
+    #[schema(example = "Hello, Solana!")]
     pub message: String,
     pub signature: String,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
     pub pubkey: String,
+    #[serde(default)]
+    pub message_encoding: MessageEncoding,
+    #[serde(default)]
+    pub signature_encoding: SignatureEncoding,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ApiSchema)]
 pub struct VerifyMessageResponse {
     pub valid: bool,
     pub message: String,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
     pub pubkey: String,
 }
 
-pub async fn sign_message(
-    Json(payload): Json<SignMessageRequest>,
-) -> Result<Json<SuccessResponse<SignMessageResponse>>, (StatusCode, Json<ErrorResponse>)> {
-    if payload.message.is_empty() || payload.secret.is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: "Missing required fields".to_string(),
-            }),
-        ));
-    }
-
-    let secret_key_bytes = validate_private_key(&payload.secret)
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid private key: {}", e),
-            }),
-        ))?;
-
-    let secret_key = SecretKey::from_bytes(&secret_key_bytes[..32])
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid secret key: {}", e),
-            }),
-        ))?;
+/// Upper bound on items per batch request.
+const MAX_BATCH_ITEMS: usize = 5_000;
+
+#[derive(Deserialize, ApiSchema)]
+pub struct VerifyBatchRequest {
+    pub items: Vec<VerifyBatchItem>,
+    /// Applies to every item.
+    #[serde(default)]
+    pub message_encoding: MessageEncoding,
+    #[serde(default)]
+    pub signature_encoding: SignatureEncoding,
+}
+
+#[derive(Deserialize, ApiSchema)]
+pub struct VerifyBatchItem {
+    pub message: String,
+    pub signature: String,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
+    pub pubkey: String,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct VerifyBatchResponse {
+    /// True when every item verified.
+    pub valid: bool,
+    pub total: usize,
+    pub valid_count: usize,
+    pub invalid_count: usize,
+    /// Whether one batch check covered every well-formed item and passed, so none
+    /// was checked on its own.
+    pub batch_verified: bool,
+    /// One entry per item, in request order.
+    pub results: Vec<VerifyBatchResult>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct VerifyBatchResult {
+    pub index: usize,
+    pub valid: bool,
+    /// Set when the item couldn't be decoded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
 
+/// Signs a message in the Solana off-chain message format. `signers` lists every
+/// expected signer in order; it defaults to just the signing key, which must be
+/// one of them.
+#[derive(Deserialize, ApiSchema)]
+pub struct SignOffchainMessageRequest {
+    pub message: String,
+    /// 32 bytes, base58 encoded. Defaults to all zeroes.
+    #[schema(format = "base58")]
+    pub application_domain: Option<String>,
+    /// Chosen from the message contents when omitted.
+    pub format: Option<MessageFormat>,
+    #[serde(default)]
+    #[schema(format = "base58", example = r#"["FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT"]"#)]
+    pub signers: Vec<String>,
+    #[serde(default)]
+    pub signature_encoding: SignatureEncoding,
+    pub secret: Option<String>,
+    pub key_id: Option<String>,
+    pub passphrase: Option<String>,
+    pub derivation_path: Option<String>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct SignOffchainMessageResponse {
+    pub signature: String,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
+    pub public_key: String,
+    /// The serialized off-chain message that was signed, base64 encoded.
+    #[schema(format = "base64")]
+    pub envelope: String,
+    #[schema(format = "base58")]
+    pub application_domain: String,
+    pub format: MessageFormat,
+    #[schema(format = "base58", example = r#"["FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT"]"#)]
+    pub signers: Vec<String>,
+    pub signature_encoding: SignatureEncoding,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_format: Option<SecretFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+}
+
+/// Verifies either a bare serialized message with one `signature` and `pubkey`,
+/// or a signed envelope (signature count, signatures, message) as wallets
+/// produce it, in which case every signer's signature is checked.
+#[derive(Deserialize, ApiSchema)]
+pub struct VerifyOffchainMessageRequest {
+    /// Base64 encoded message or signed envelope.
+    #[schema(format = "base64")]
+    pub envelope: String,
+    pub signature: Option<String>,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
+    pub pubkey: Option<String>,
+    /// Encoding of `signature` and of the signatures in the response.
+    #[serde(default)]
+    pub signature_encoding: SignatureEncoding,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct VerifyOffchainMessageResponse {
+    /// True when every checked signature is valid.
+    pub valid: bool,
+    pub signatures: Vec<OffchainSignatureResult>,
+    pub message: String,
+    #[schema(format = "base58")]
+    pub application_domain: String,
+    pub format: MessageFormat,
+    #[schema(format = "base58", example = r#"["FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT"]"#)]
+    pub signers: Vec<String>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct OffchainSignatureResult {
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
+    pub pubkey: String,
+    pub signature: String,
+    pub valid: bool,
+}
+
+fn resolve_keypair(
+    vault: &KeyVault,
+    secret: Option<&str>,
+    key_id: Option<&str>,
+    passphrase: Option<&str>,
+    derivation_path: Option<&str>,
+) -> Result<(Ed25519Keypair, SigningKey), ApiError> {
+    let signing_key = resolve_signing_key(vault, secret, key_id, passphrase, derivation_path)?;
+
+    let secret_key = SecretKey::from_bytes(&signing_key.bytes[..32])
+        .map_err(|e| ApiError::new(ErrorCode::InvalidSecretKey, format!("Invalid secret key: {}", e)).at("secret"))?;
     let public_key = PublicKey::from(&secret_key);
     let keypair = Ed25519Keypair {
         secret: secret_key,
         public: public_key,
     };
+    Ok((keypair, signing_key))
+}
 
-    let message_bytes = payload.message.as_bytes();
-    let signature = keypair.sign(message_bytes);
+pub async fn sign_message(
+    State(vault): State<Arc<KeyVault>>,
+    State(metrics): State<Arc<Metrics>>,
+    ApiJson(payload): ApiJson<SignMessageRequest>,
+) -> Result<Json<SuccessResponse<SignMessageResponse>>, ApiError> {
+    if payload.message.is_empty() {
+        return Err(ApiError::missing_fields().at("message"));
+    }
+
+    let (keypair, signing_key) = resolve_keypair(
+        &vault,
+        payload.secret.as_deref(),
+        payload.key_id.as_deref(),
+        payload.passphrase.as_deref(),
+        payload.derivation_path.as_deref(),
+    )?;
+    let public_key = keypair.public;
+
+    let message_bytes = payload
+        .message_encoding
+        .decode(&payload.message)
+        .map_err(|e| ApiError::new(ErrorCode::InvalidEncoding, format!("Invalid message: {}", e)).at("message"))?;
+    let signature = keypair.sign(&message_bytes);
+    metrics.record_signatures("message", 1);
 
     Ok(Json(SuccessResponse {
         success: true,
         data: SignMessageResponse {
-            signature: encode_base64(&signature.to_bytes()),
+            signature: payload.signature_encoding.encode(&signature.to_bytes()),
             public_key: encode_base58(&public_key.to_bytes()),
             message: payload.message,
+            message_encoding: payload.message_encoding,
+            signature_encoding: payload.signature_encoding,
+            secret_format: signing_key.secret_format,
+            key_id: signing_key.key_id,
         },
     }))
 }
 
-pub async fn verify_message(
-    Json(payload): Json<VerifyMessageRequest>,
-) -> Result<Json<SuccessResponse<VerifyMessageResponse>>, (StatusCode, Json<ErrorResponse>)> {
-    if payload.message.is_empty() || payload.signature.is_empty() || payload.pubkey.is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: "Missing required fields".to_string(),
-            }),
-        ));
-    }
-
-    let public_key_bytes = decode_base58(&payload.pubkey)
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid public key: {}", e),
-            }),
-        ))?;
-
-    let public_key = PublicKey::from_bytes(&public_key_bytes)
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid public key: {}", e),
-            }),
-        ))?;
-
-    let signature_bytes = decode_base64(&payload.signature)
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid signature: {}", e),
-            }),
-        ))?;
-
+/// Decodes a base58 `pubkey` and an encoded ed25519 `signature`. Errors are
+/// placed at the `pubkey` and `signature` fields.
+fn parse_signature(
+    pubkey: &str,
+    signature: &str,
+    signature_encoding: SignatureEncoding,
+) -> Result<(PublicKey, Signature), ApiError> {
+    let invalid_pubkey =
+        |e: String| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid public key: {}", e)).at("pubkey");
+    let invalid_signature = |code: ErrorCode, e: String| {
+        ApiError::new(code, format!("Invalid signature: {}", e)).at("signature")
+    };
+    let public_key_bytes = decode_base58(pubkey).map_err(invalid_pubkey)?;
+    let public_key = PublicKey::from_bytes(&public_key_bytes).map_err(|e| invalid_pubkey(e.to_string()))?;
+    let signature_bytes = signature_encoding
+        .decode(signature)
+        .map_err(|e| invalid_signature(ErrorCode::InvalidEncoding, e))?;
     let signature = Signature::from_bytes(&signature_bytes)
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid signature: {}", e),
-            }),
-        ))?;
+        .map_err(|e| invalid_signature(ErrorCode::InvalidSignature, e.to_string()))?;
+    Ok((public_key, signature))
+}
+
+/// Checks an ed25519 `signature` over `message` against a base58 `pubkey`.
+/// Malformed keys or signatures are errors; a well-formed signature that
+/// doesn't verify is `Ok(false)`.
+pub fn verify_signature(
+    pubkey: &str,
+    signature: &str,
+    signature_encoding: SignatureEncoding,
+    message: &[u8],
+) -> Result<bool, ApiError> {
+    let (public_key, signature) = parse_signature(pubkey, signature, signature_encoding)?;
+    Ok(public_key.verify(message, &signature).is_ok())
+}
 
-    let message_bytes = payload.message.as_bytes();
-    let valid = public_key.verify(message_bytes, &signature).is_ok();
+pub async fn verify_message(
+    ApiJson(payload): ApiJson<VerifyMessageRequest>,
+) -> Result<Json<SuccessResponse<VerifyMessageResponse>>, ApiError> {
+    let missing = [
+        ("message", &payload.message),
+        ("signature", &payload.signature),
+        ("pubkey", &payload.pubkey),
+    ]
+    .into_iter()
+    .find(|(_, value)| value.is_empty());
+    if let Some((field, _)) = missing {
+        return Err(ApiError::missing_fields().at(field));
+    }
+
+    let message_bytes = payload
+        .message_encoding
+        .decode(&payload.message)
+        .map_err(|e| ApiError::new(ErrorCode::InvalidEncoding, format!("Invalid message: {}", e)).at("message"))?;
+    let valid = verify_signature(
+        &payload.pubkey,
+        &payload.signature,
+        payload.signature_encoding,
+        &message_bytes,
+    )?;
 
     Ok(Json(SuccessResponse {
         success: true,
@@ -152,4 +318,507 @@ pub async fn verify_message(
             pubkey: payload.pubkey,
         },
     }))
-} 
\ No newline at end of file
+}
+
+pub async fn sign_offchain_message(
+    State(vault): State<Arc<KeyVault>>,
+    State(metrics): State<Arc<Metrics>>,
+    ApiJson(payload): ApiJson<SignOffchainMessageRequest>,
+) -> Result<Json<SuccessResponse<SignOffchainMessageResponse>>, ApiError> {
+    let (keypair, signing_key) = resolve_keypair(
+        &vault,
+        payload.secret.as_deref(),
+        payload.key_id.as_deref(),
+        payload.passphrase.as_deref(),
+        payload.derivation_path.as_deref(),
+    )?;
+    let signer = Pubkey::from(keypair.public.to_bytes());
+
+    let application_domain = match &payload.application_domain {
+        Some(domain) => validate_pubkey(domain)
+            .map_err(|e| {
+                ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid application_domain: {}", e))
+                    .at("application_domain")
+            })?
+            .to_bytes(),
+        None => [0u8; 32],
+    };
+
+    let signers = if payload.signers.is_empty() {
+        vec![signer]
+    } else {
+        payload
+            .signers
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                validate_pubkey(s).map_err(|e| {
+                    ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid signer {}: {}", s, e))
+                        .at(format!("signers[{}]", i))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    if !signers.contains(&signer) {
+        return Err(ApiError::invalid_request(format!("{} is not in the signers list", signer)).at("signers"));
+    }
+
+    let offchain = OffchainMessage::new(
+        application_domain,
+        payload.format,
+        signers,
+        payload.message.into_bytes(),
+    )
+    .map_err(|e| ApiError::new(ErrorCode::InvalidMessage, e).at("message"))?;
+    let envelope = offchain.serialize();
+    let signature = keypair.sign(&envelope);
+    metrics.record_signatures("offchain_message", 1);
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: SignOffchainMessageResponse {
+            signature: payload.signature_encoding.encode(&signature.to_bytes()),
+            public_key: signer.to_string(),
+            envelope: encode_base64(&envelope),
+            application_domain: encode_base58(&offchain.application_domain),
+            format: offchain.format,
+            signers: offchain.signers.iter().map(|s| s.to_string()).collect(),
+            signature_encoding: payload.signature_encoding,
+            secret_format: signing_key.secret_format,
+            key_id: signing_key.key_id,
+        },
+    }))
+}
+
+fn check_signature(pubkey: &Pubkey, signature: &[u8], message: &[u8]) -> bool {
+    match (PublicKey::from_bytes(pubkey.as_ref()), Signature::from_bytes(signature)) {
+        (Ok(public_key), Ok(signature)) => public_key.verify(message, &signature).is_ok(),
+        _ => false,
+    }
+}
+
+pub async fn verify_offchain_message(
+    ApiJson(payload): ApiJson<VerifyOffchainMessageRequest>,
+) -> Result<Json<SuccessResponse<VerifyOffchainMessageResponse>>, ApiError> {
+    let data = decode_base64(&payload.envelope)
+        .map_err(|e| ApiError::new(ErrorCode::InvalidEncoding, format!("Invalid envelope: {}", e)).at("envelope"))?;
+
+    let (offchain, message_bytes, signatures) = if data.starts_with(SIGNING_DOMAIN) {
+        let (signature, pubkey) = match (&payload.signature, &payload.pubkey) {
+            (Some(signature), Some(pubkey)) => (signature, pubkey),
+            (None, _) => {
+                return Err(ApiError::new(
+                    ErrorCode::MissingField,
+                    "signature and pubkey are required for an unsigned message",
+                )
+                .at("signature"));
+            }
+            (_, None) => {
+                return Err(ApiError::new(
+                    ErrorCode::MissingField,
+                    "signature and pubkey are required for an unsigned message",
+                )
+                .at("pubkey"));
+            }
+        };
+        let signature = payload.signature_encoding.decode(signature).map_err(|e| {
+            ApiError::new(ErrorCode::InvalidEncoding, format!("Invalid signature: {}", e)).at("signature")
+        })?;
+        let pubkey = validate_pubkey(pubkey)
+            .map_err(|e| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid public key: {}", e)).at("pubkey"))?;
+        let offchain = OffchainMessage::deserialize(&data)
+            .map_err(|e| ApiError::new(ErrorCode::InvalidMessage, e).at("envelope"))?;
+        if !offchain.signers.contains(&pubkey) {
+            return Err(
+                ApiError::invalid_request(format!("{} is not in the message's signers list", pubkey)).at("pubkey"),
+            );
+        }
+        (offchain, data.as_slice(), vec![(pubkey, signature)])
+    } else {
+        let invalid_envelope = |e: String| ApiError::new(ErrorCode::InvalidMessage, e).at("envelope");
+        let (signatures, message_bytes) = split_signed_envelope(&data).map_err(invalid_envelope)?;
+        let offchain = OffchainMessage::deserialize(message_bytes).map_err(invalid_envelope)?;
+        if signatures.len() != offchain.signers.len() {
+            return Err(invalid_envelope(format!(
+                "Envelope has {} signatures for {} signers",
+                signatures.len(),
+                offchain.signers.len()
+            )));
+        }
+        let pairs = offchain
+            .signers
+            .iter()
+            .zip(signatures)
+            .map(|(signer, signature)| (*signer, signature.to_vec()))
+            .collect();
+        (offchain, message_bytes, pairs)
+    };
+
+    let results: Vec<OffchainSignatureResult> = signatures
+        .iter()
+        .map(|(pubkey, signature)| OffchainSignatureResult {
+            pubkey: pubkey.to_string(),
+            signature: payload.signature_encoding.encode(signature),
+            valid: check_signature(pubkey, signature, message_bytes),
+        })
+        .collect();
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: VerifyOffchainMessageResponse {
+            valid: results.iter().all(|r| r.valid),
+            signatures: results,
+            message: String::from_utf8_lossy(&offchain.message).into_owned(),
+            application_domain: encode_base58(&offchain.application_domain),
+            format: offchain.format,
+            signers: offchain.signers.iter().map(|s| s.to_string()).collect(),
+        },
+    }))
+}
+
+/// Whether `bytes` is the canonical encoding of a point with no small-order
+/// component. Batch verification is only certain to agree with
+/// `PublicKey::verify` when the public key and the signature's `R` are such
+/// points: it decompresses a non-canonical `R` that `verify` rejects, and its
+/// random coefficients can cancel a small-order error term.
+fn is_batchable_point(bytes: &[u8]) -> bool {
+    let Ok(bytes) = <[u8; 32]>::try_from(bytes) else {
+        return false;
+    };
+    let compressed = CompressedEdwardsY(bytes);
+    compressed.decompress().is_some_and(|point| point.is_torsion_free() && point.compress() == compressed)
+}
+
+/// Verifies decoded items with one batch check, falling back to individual
+/// checks only when the batch fails, to find which items are bad. Items whose
+/// key or signature isn't batchable are always checked on their own, so every
+/// item gets the answer `/message/verify` would give. Returns whether the
+/// batch check covered every item and passed.
+fn verify_decoded(decoded: &[(usize, Vec<u8>, PublicKey, Signature)], results: &mut [VerifyBatchResult]) -> bool {
+    let (batchable, alone): (Vec<_>, Vec<_>) = decoded.iter().partition(|(_, _, public_key, signature)| {
+        is_batchable_point(public_key.as_bytes()) && is_batchable_point(&signature.to_bytes()[..32])
+    });
+    for (index, message, public_key, signature) in alone.iter() {
+        results[*index].valid = public_key.verify(message, signature).is_ok();
+    }
+    if batchable.is_empty() {
+        return false;
+    }
+
+    let messages: Vec<&[u8]> = batchable.iter().map(|(_, message, _, _)| message.as_slice()).collect();
+    let public_keys: Vec<PublicKey> = batchable.iter().map(|(_, _, public_key, _)| *public_key).collect();
+    let signatures: Vec<Signature> = batchable.iter().map(|(_, _, _, signature)| *signature).collect();
+    let batch_verified = verify_batch(&messages, &signatures, &public_keys).is_ok();
+    for (index, message, public_key, signature) in batchable {
+        results[*index].valid = batch_verified || public_key.verify(message, signature).is_ok();
+    }
+    batch_verified && alone.is_empty()
+}
+
+pub async fn verify_message_batch(
+    ApiJson(payload): ApiJson<VerifyBatchRequest>,
+) -> Result<Json<SuccessResponse<VerifyBatchResponse>>, ApiError> {
+    if payload.items.is_empty() {
+        return Err(ApiError::missing_fields().at("items"));
+    }
+    if payload.items.len() > MAX_BATCH_ITEMS {
+        return Err(ApiError::invalid_request(format!("At most {} items per batch", MAX_BATCH_ITEMS)).at("items"));
+    }
+
+    // Thousands of curve operations would stall the async workers.
+    let response = tokio::task::spawn_blocking(move || {
+        let mut results: Vec<VerifyBatchResult> = (0..payload.items.len())
+            .map(|index| VerifyBatchResult {
+                index,
+                valid: false,
+                error: None,
+            })
+            .collect();
+
+        let mut decoded = Vec::with_capacity(payload.items.len());
+        for (index, item) in payload.items.iter().enumerate() {
+            let parsed = payload
+                .message_encoding
+                .decode(&item.message)
+                .map_err(|e| format!("Invalid message: {}", e))
+                .and_then(|message| {
+                    parse_signature(&item.pubkey, &item.signature, payload.signature_encoding)
+                        .map(|(public_key, signature)| (message, public_key, signature))
+                        .map_err(|e| e.message)
+                });
+            match parsed {
+                Ok((message, public_key, signature)) => decoded.push((index, message, public_key, signature)),
+                Err(e) => results[index].error = Some(e),
+            }
+        }
+
+        let batch_verified = verify_decoded(&decoded, &mut results);
+        let valid_count = results.iter().filter(|r| r.valid).count();
+        VerifyBatchResponse {
+            valid: valid_count == results.len(),
+            total: results.len(),
+            valid_count,
+            invalid_count: results.len() - valid_count,
+            batch_verified,
+            results,
+        }
+    })
+    .await
+    .map_err(|e| ApiError::internal(format!("Verification failed: {}", e)))?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: response,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curve25519_dalek::{constants::ED25519_BASEPOINT_POINT, scalar::Scalar};
+    use sha2::{Digest, Sha512};
+
+    fn keypair(seed: u8) -> Ed25519Keypair {
+        let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+        let public = PublicKey::from(&secret);
+        Ed25519Keypair { secret, public }
+    }
+
+    fn item(keypair: &Ed25519Keypair, signed: &str, message: &str) -> VerifyBatchItem {
+        VerifyBatchItem {
+            message: message.to_string(),
+            signature: encode_base64(&keypair.sign(signed.as_bytes()).to_bytes()),
+            pubkey: encode_base58(keypair.public.as_bytes()),
+        }
+    }
+
+    async fn verify_batch_of(items: Vec<VerifyBatchItem>) -> VerifyBatchResponse {
+        let payload = VerifyBatchRequest {
+            items,
+            message_encoding: MessageEncoding::Utf8,
+            signature_encoding: SignatureEncoding::Base64,
+        };
+        match verify_message_batch(ApiJson(payload)).await {
+            Ok(Json(response)) => response.data,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    fn validity(response: &VerifyBatchResponse) -> Vec<bool> {
+        response.results.iter().map(|result| result.valid).collect()
+    }
+
+    /// An item whose public key is the point of order two. The signature is
+    /// chosen so that it holds only up to that point: `PublicKey::verify`
+    /// rejects it, while a batch check accepts it about half the time.
+    fn small_order_item() -> VerifyBatchItem {
+        let mut order_two = [0xff; 32];
+        order_two[0] = 0xec;
+        order_two[31] = 0x7f;
+        let r = Scalar::from(7u64);
+        let big_r = (r * ED25519_BASEPOINT_POINT).compress();
+        (0..)
+            .map(|n| format!("claim {}", n))
+            .find_map(|message| {
+                let digest = Sha512::digest([big_r.as_bytes(), &order_two, message.as_bytes()].concat());
+                let mut wide = [0; 64];
+                wide.copy_from_slice(&digest);
+                let odd = Scalar::from_bytes_mod_order_wide(&wide).to_bytes()[0] & 1 == 1;
+                odd.then(|| VerifyBatchItem {
+                    signature: encode_base64(&[big_r.to_bytes(), r.to_bytes()].concat()),
+                    pubkey: encode_base58(&order_two),
+                    message,
+                })
+            })
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn an_all_valid_batch_passes_in_one_check() {
+        let items = (1..=3).map(|seed| item(&keypair(seed), "claim", "claim")).collect();
+        let response = verify_batch_of(items).await;
+        assert!(response.valid && response.batch_verified);
+        assert_eq!((response.valid_count, response.invalid_count), (3, 0));
+        assert_eq!(validity(&response), [true, true, true]);
+    }
+
+    #[tokio::test]
+    async fn a_failed_batch_pinpoints_the_bad_item() {
+        let items = vec![
+            item(&keypair(1), "claim 1", "claim 1"),
+            item(&keypair(2), "claim 2", "claim 3"),
+            item(&keypair(3), "claim 3", "claim 3"),
+        ];
+        let response = verify_batch_of(items).await;
+        assert!(!response.valid && !response.batch_verified);
+        assert_eq!(validity(&response), [true, false, true]);
+        assert!(response.results.iter().all(|result| result.error.is_none()));
+    }
+
+    #[tokio::test]
+    async fn undecodable_items_are_reported_without_failing_the_batch() {
+        let good = item(&keypair(1), "claim", "claim");
+        let bad_pubkey = VerifyBatchItem { pubkey: "0OIl".to_string(), ..item(&keypair(2), "claim", "claim") };
+        let bad_signature = VerifyBatchItem { signature: "AAAA".to_string(), ..item(&keypair(3), "claim", "claim") };
+        let response = verify_batch_of(vec![bad_pubkey, good, bad_signature]).await;
+        assert_eq!(validity(&response), [false, true, false]);
+        assert!(response.batch_verified);
+        let errors: Vec<bool> = response.results.iter().map(|result| result.error.is_some()).collect();
+        assert_eq!(errors, [true, false, true]);
+        assert!(response.results[0].error.as_deref().unwrap_or_default().starts_with("Invalid public key"));
+        assert!(response.results[2].error.as_deref().unwrap_or_default().starts_with("Invalid signature"));
+    }
+
+    #[tokio::test]
+    async fn batch_and_single_verification_agree_on_small_order_keys() {
+        let suspect = small_order_item();
+        let single = verify_signature(
+            &suspect.pubkey,
+            &suspect.signature,
+            SignatureEncoding::Base64,
+            suspect.message.as_bytes(),
+        );
+        assert_eq!(single.ok(), Some(false));
+        // The batch coefficients are random, so try enough times to hit one
+        // that would cancel the small-order term.
+        for _ in 0..32 {
+            let items = vec![small_order_item(), item(&keypair(1), "claim", "claim")];
+            let response = verify_batch_of(items).await;
+            assert_eq!(validity(&response), [false, true]);
+            assert!(!response.batch_verified);
+        }
+    }
+
+    const SIGNATURE_ENCODINGS: [SignatureEncoding; 3] =
+        [SignatureEncoding::Base64, SignatureEncoding::Base58, SignatureEncoding::Hex];
+
+    fn secret() -> String {
+        solana_sdk::signature::Keypair::from_bytes(&keypair(9).to_bytes()).unwrap().to_base58_string()
+    }
+
+    fn encode_message(encoding: MessageEncoding, bytes: &[u8]) -> String {
+        match encoding {
+            MessageEncoding::Utf8 => String::from_utf8(bytes.to_vec()).unwrap(),
+            MessageEncoding::Base64 => encode_base64(bytes),
+            MessageEncoding::Hex => hex::encode(bytes),
+            MessageEncoding::Base58 => encode_base58(bytes),
+        }
+    }
+
+    async fn sign(request: SignMessageRequest) -> Result<SignMessageResponse, ApiError> {
+        sign_message(State(Arc::default()), State(Arc::default()), ApiJson(request)).await.map(|Json(r)| r.data)
+    }
+
+    fn sign_request(
+        message: &str,
+        message_encoding: MessageEncoding,
+        signature_encoding: SignatureEncoding,
+    ) -> SignMessageRequest {
+        SignMessageRequest {
+            message: message.to_string(),
+            message_encoding,
+            signature_encoding,
+            secret: Some(secret()),
+            key_id: None,
+            passphrase: None,
+            derivation_path: None,
+        }
+    }
+
+    async fn verify(request: VerifyMessageRequest) -> Result<bool, ApiError> {
+        verify_message(ApiJson(request)).await.map(|Json(r)| r.data.valid)
+    }
+
+    #[tokio::test]
+    async fn messages_round_trip_in_every_encoding() {
+        let encodings = [MessageEncoding::Utf8, MessageEncoding::Base64, MessageEncoding::Hex, MessageEncoding::Base58];
+        for message_encoding in encodings {
+            // Bytes that aren't UTF-8 can only be sent encoded.
+            let bytes: &[u8] = match message_encoding {
+                MessageEncoding::Utf8 => "héllo".as_bytes(),
+                _ => b"\x00\xffhello",
+            };
+            let message = encode_message(message_encoding, bytes);
+            for signature_encoding in SIGNATURE_ENCODINGS {
+                let signed = sign(sign_request(&message, message_encoding, signature_encoding)).await.unwrap();
+                assert_eq!(signed.public_key, encode_base58(keypair(9).public.as_bytes()));
+                assert_eq!(signed.signature_encoding, signature_encoding);
+                let signature = signature_encoding.decode(&signed.signature).unwrap();
+                assert_eq!(signature, keypair(9).sign(bytes).to_bytes());
+
+                let request = |message: String| VerifyMessageRequest {
+                    message,
+                    signature: signed.signature.clone(),
+                    pubkey: signed.public_key.clone(),
+                    message_encoding,
+                    signature_encoding,
+                };
+                let label = format!("{:?}/{:?}", message_encoding, signature_encoding);
+                assert_eq!(verify(request(message.clone())).await.ok(), Some(true), "{}", label);
+                let other = encode_message(message_encoding, b"something else");
+                assert_eq!(verify(request(other)).await.ok(), Some(false), "{}", label);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn undecodable_values_name_their_field() {
+        for encoding in [MessageEncoding::Base64, MessageEncoding::Hex, MessageEncoding::Base58] {
+            let error = sign(sign_request("not valid!", encoding, SignatureEncoding::Base64)).await.err().unwrap();
+            assert_eq!((error.code, error.field.as_deref()), (ErrorCode::InvalidEncoding, Some("message")));
+        }
+
+        let signed = sign(sign_request("hi", MessageEncoding::Utf8, SignatureEncoding::Base64)).await.unwrap();
+        let request = |message_encoding, signature: &str, signature_encoding| VerifyMessageRequest {
+            message: "hi".to_string(),
+            signature: signature.to_string(),
+            pubkey: signed.public_key.clone(),
+            message_encoding,
+            signature_encoding,
+        };
+        let bad_message = request(MessageEncoding::Hex, &signed.signature, SignatureEncoding::Base64);
+        let error = verify(bad_message).await.err().unwrap();
+        assert_eq!((error.code, error.field.as_deref()), (ErrorCode::InvalidEncoding, Some("message")));
+        for signature_encoding in SIGNATURE_ENCODINGS {
+            let error = verify(request(MessageEncoding::Utf8, "0OIl!", signature_encoding)).await.err().unwrap();
+            assert_eq!((error.code, error.field.as_deref()), (ErrorCode::InvalidEncoding, Some("signature")));
+        }
+        let error = verify(request(MessageEncoding::Utf8, "AAAA", SignatureEncoding::Base64)).await.err().unwrap();
+        assert_eq!((error.code, error.field.as_deref()), (ErrorCode::InvalidSignature, Some("signature")));
+    }
+
+    #[tokio::test]
+    async fn offchain_signatures_follow_signature_encoding() {
+        for signature_encoding in SIGNATURE_ENCODINGS {
+            let request = SignOffchainMessageRequest {
+                message: "Hello wallet".to_string(),
+                application_domain: None,
+                format: None,
+                signers: Vec::new(),
+                signature_encoding,
+                secret: Some(secret()),
+                key_id: None,
+                passphrase: None,
+                derivation_path: None,
+            };
+            let signed = sign_offchain_message(State(Arc::default()), State(Arc::default()), ApiJson(request))
+                .await
+                .map(|Json(r)| r.data)
+                .unwrap();
+            assert_eq!(signed.signature_encoding, signature_encoding);
+            let envelope = decode_base64(&signed.envelope).unwrap();
+            let signature = signature_encoding.decode(&signed.signature).unwrap();
+            assert_eq!(signature, keypair(9).sign(&envelope).to_bytes());
+
+            let request = VerifyOffchainMessageRequest {
+                envelope: signed.envelope,
+                signature: Some(signed.signature.clone()),
+                pubkey: Some(signed.public_key),
+                signature_encoding,
+            };
+            let verified = verify_offchain_message(ApiJson(request)).await.map(|Json(r)| r.data).unwrap();
+            assert!(verified.valid);
+            assert_eq!(verified.signatures[0].signature, signed.signature);
+        }
+    }
+}
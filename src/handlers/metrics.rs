@@ -0,0 +1,14 @@
+use axum::{
+    extract::State,
+    http::header::CONTENT_TYPE,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use crate::metrics::Metrics;
+
+/// Prometheus text exposition format, version 0.0.4.
+const CONTENT_TYPE_PROMETHEUS: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+pub async fn export_metrics(State(metrics): State<Arc<Metrics>>) -> impl IntoResponse {
+    ([(CONTENT_TYPE, CONTENT_TYPE_PROMETHEUS)], metrics.render())
+}
@@ -1,4 +1,22 @@
 pub mod keypair;
 pub mod message;
 pub mod token;
-pub mod send; 
\ No newline at end of file
+pub mod send;
+pub mod pda;
+pub mod pubkey;
+pub mod vanity;
+pub mod vault;
+pub mod transaction;
+pub mod instruction;
+pub mod siws;
+pub mod admin;
+pub mod metrics;
+
+use serde::Serialize;
+
+/// Envelope for successful responses; failures render through `crate::error::ApiError`.
+#[derive(Serialize)]
+pub struct SuccessResponse<T> {
+    pub success: bool,
+    pub data: T,
+}
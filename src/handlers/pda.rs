@@ -0,0 +1,231 @@
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::{Pubkey, MAX_SEEDS, MAX_SEED_LEN};
+use crate::error::{ApiError, ApiJson, ErrorCode};
+use crate::handlers::SuccessResponse;
+use crate::utils::{validate_pubkey, decode_hex};
+use crate::openapi::ApiSchema;
+
+/// A single typed seed, e.g. `{"type": "utf8", "value": "vault"}`.
+/// Integers are encoded little-endian, matching Anchor's `to_le_bytes()` seeds.
+#[derive(Deserialize, ApiSchema)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum Seed {
+    Utf8(String),
+    Pubkey(String),
+    Hex(String),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+}
+
+#[derive(Deserialize, ApiSchema)]
+pub struct FindPdaRequest {
+    #[schema(format = "base58", example = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")]
+    pub program_id: String,
+    pub seeds: Vec<Seed>,
+}
+
+#[derive(Deserialize, ApiSchema)]
+pub struct CreatePdaRequest {
+    #[schema(format = "base58", example = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")]
+    pub program_id: String,
+    pub seeds: Vec<Seed>,
+    #[schema(example = "255")]
+    pub bump: u8,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct PdaResponse {
+    #[schema(format = "base58")]
+    pub address: String,
+    pub bump: u8,
+    #[schema(format = "base58", example = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")]
+    pub program_id: String,
+    /// Encoded seed bytes in hex, in the order they were hashed (bump excluded).
+    #[schema(format = "hex")]
+    pub seeds: Vec<String>,
+}
+
+fn encode_seed(index: usize, seed: &Seed) -> Result<Vec<u8>, ApiError> {
+    let invalid = |code: ErrorCode, message: String| ApiError::new(code, message).at(format!("seeds[{}]", index));
+    let bytes = match seed {
+        Seed::Utf8(value) => value.as_bytes().to_vec(),
+        Seed::Pubkey(value) => validate_pubkey(value)
+            .map_err(|e| invalid(ErrorCode::InvalidPubkey, e))?
+            .to_bytes()
+            .to_vec(),
+        Seed::Hex(value) => decode_hex(value).map_err(|e| invalid(ErrorCode::InvalidEncoding, format!("Invalid hex: {}", e)))?,
+        Seed::U8(value) => vec![*value],
+        Seed::U16(value) => value.to_le_bytes().to_vec(),
+        Seed::U32(value) => value.to_le_bytes().to_vec(),
+        Seed::U64(value) => value.to_le_bytes().to_vec(),
+    };
+    if bytes.len() > MAX_SEED_LEN {
+        return Err(invalid(
+            ErrorCode::InvalidSeed,
+            format!("Seed is {} bytes, maximum is {}", bytes.len(), MAX_SEED_LEN),
+        ));
+    }
+    Ok(bytes)
+}
+
+fn encode_seeds(seeds: &[Seed], reserved: usize) -> Result<Vec<Vec<u8>>, ApiError> {
+    if seeds.len() + reserved > MAX_SEEDS {
+        return Err(ApiError::new(
+            ErrorCode::InvalidSeed,
+            format!("Too many seeds: at most {} allowed", MAX_SEEDS - reserved),
+        )
+        .at("seeds"));
+    }
+
+    seeds.iter().enumerate().map(|(i, seed)| encode_seed(i, seed)).collect()
+}
+
+pub async fn find_pda(
+    ApiJson(payload): ApiJson<FindPdaRequest>,
+) -> Result<Json<SuccessResponse<PdaResponse>>, ApiError> {
+    let program_id = validate_pubkey(&payload.program_id)
+        .map_err(|e| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid program id: {}", e)).at("program_id"))?;
+
+    // The bump occupies one of the MAX_SEEDS slots.
+    let seeds = encode_seeds(&payload.seeds, 1)?;
+    let seed_refs: Vec<&[u8]> = seeds.iter().map(Vec::as_slice).collect();
+
+    let (address, bump) = Pubkey::try_find_program_address(&seed_refs, &program_id)
+        .ok_or_else(|| ApiError::new(ErrorCode::InvalidSeed, "Unable to find a viable bump for these seeds").at("seeds"))?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: PdaResponse {
+            address: address.to_string(),
+            bump,
+            program_id: program_id.to_string(),
+            seeds: seeds.iter().map(hex::encode).collect(),
+        },
+    }))
+}
+
+pub async fn create_pda(
+    ApiJson(payload): ApiJson<CreatePdaRequest>,
+) -> Result<Json<SuccessResponse<PdaResponse>>, ApiError> {
+    let program_id = validate_pubkey(&payload.program_id)
+        .map_err(|e| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid program id: {}", e)).at("program_id"))?;
+
+    let seeds = encode_seeds(&payload.seeds, 1)?;
+    let bump = [payload.bump];
+    let mut seed_refs: Vec<&[u8]> = seeds.iter().map(Vec::as_slice).collect();
+    seed_refs.push(&bump);
+
+    let address = Pubkey::create_program_address(&seed_refs, &program_id)
+        .map_err(|e| ApiError::new(ErrorCode::InvalidSeed, format!("Invalid bump {}: {}", payload.bump, e)).at("bump"))?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: PdaResponse {
+            address: address.to_string(),
+            bump: payload.bump,
+            program_id: program_id.to_string(),
+            seeds: seeds.iter().map(hex::encode).collect(),
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+    async fn find(seeds: Vec<Seed>) -> Result<PdaResponse, ApiError> {
+        let payload = FindPdaRequest {
+            program_id: PROGRAM_ID.to_string(),
+            seeds,
+        };
+        find_pda(ApiJson(payload)).await.map(|Json(r)| r.data)
+    }
+
+    async fn create(seeds: Vec<Seed>, bump: u8) -> Result<PdaResponse, ApiError> {
+        let payload = CreatePdaRequest {
+            program_id: PROGRAM_ID.to_string(),
+            seeds,
+            bump,
+        };
+        create_pda(ApiJson(payload)).await.map(|Json(r)| r.data)
+    }
+
+    #[tokio::test]
+    async fn seeds_are_encoded_by_type() {
+        let pubkey = "11111111111111111111111111111111";
+        let seeds = vec![
+            Seed::Utf8("vault".to_string()),
+            Seed::Pubkey(pubkey.to_string()),
+            Seed::Hex("deadBEEF".to_string()),
+            Seed::U8(7),
+            Seed::U16(0x0102),
+            Seed::U32(0x01020304),
+            Seed::U64(0x0102030405060708),
+        ];
+        let pda = find(seeds).await.unwrap();
+        let expected = [
+            hex::encode("vault"),
+            "00".repeat(32),
+            "deadbeef".to_string(),
+            "07".to_string(),
+            "0201".to_string(),
+            "04030201".to_string(),
+            "0807060504030201".to_string(),
+        ];
+        assert_eq!(pda.seeds, expected);
+
+        let seed_bytes: Vec<Vec<u8>> = pda.seeds.iter().map(|seed| hex::decode(seed).unwrap()).collect();
+        let seed_refs: Vec<&[u8]> = seed_bytes.iter().map(Vec::as_slice).collect();
+        let (address, bump) = Pubkey::find_program_address(&seed_refs, &validate_pubkey(PROGRAM_ID).unwrap());
+        assert_eq!((pda.address, pda.bump), (address.to_string(), bump));
+    }
+
+    #[tokio::test]
+    async fn bad_seeds_point_at_their_index() {
+        let err = find(vec![Seed::U8(1), Seed::Utf8("x".repeat(MAX_SEED_LEN + 8))]).await.err().unwrap();
+        assert_eq!(err.code, ErrorCode::InvalidSeed);
+        assert_eq!(err.field.as_deref(), Some("seeds[1]"));
+        assert_eq!(err.message, "Seed is 40 bytes, maximum is 32");
+        assert!(find(vec![Seed::Utf8("x".repeat(MAX_SEED_LEN))]).await.is_ok());
+
+        let err = find(vec![Seed::Hex("abc".to_string())]).await.err().unwrap();
+        assert_eq!((err.code, err.field.as_deref()), (ErrorCode::InvalidEncoding, Some("seeds[0]")));
+        let err = find(vec![Seed::U8(1), Seed::U8(2), Seed::Pubkey("nope".to_string())]).await.err().unwrap();
+        assert_eq!((err.code, err.field.as_deref()), (ErrorCode::InvalidPubkey, Some("seeds[2]")));
+    }
+
+    #[tokio::test]
+    async fn at_most_fifteen_seeds_leave_room_for_the_bump() {
+        assert!(find((0..MAX_SEEDS as u8 - 1).map(Seed::U8).collect()).await.is_ok());
+        let err = find((0..MAX_SEEDS as u8).map(Seed::U8).collect()).await.err().unwrap();
+        assert_eq!((err.code, err.field.as_deref()), (ErrorCode::InvalidSeed, Some("seeds")));
+        assert_eq!(err.message, "Too many seeds: at most 15 allowed");
+        let err = create((0..MAX_SEEDS as u8).map(Seed::U8).collect(), 255).await.err().unwrap();
+        assert_eq!(err.field.as_deref(), Some("seeds"));
+    }
+
+    #[tokio::test]
+    async fn create_rejects_a_bump_that_lands_on_the_curve() {
+        // Every bump above the canonical one was tried by find_program_address
+        // and put the address on the curve, so pick seeds where there is one.
+        let mut seed = 0;
+        let found = loop {
+            let found = find(vec![Seed::U8(seed)]).await.unwrap();
+            if found.bump < 255 {
+                break found;
+            }
+            seed += 1;
+        };
+        let created = create(vec![Seed::U8(seed)], found.bump).await.unwrap();
+        assert_eq!(created.address, found.address);
+
+        let err = create(vec![Seed::U8(seed)], found.bump + 1).await.err().unwrap();
+        assert_eq!((err.code, err.field.as_deref()), (ErrorCode::InvalidSeed, Some("bump")));
+        assert!(err.message.starts_with(&format!("Invalid bump {}: ", found.bump + 1)));
+    }
+}
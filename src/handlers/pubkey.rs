@@ -0,0 +1,132 @@
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use crate::error::{ApiError, ApiJson, ErrorCode};
+use crate::handlers::SuccessResponse;
+use crate::programs::well_known_label;
+use crate::utils::{parse_pubkey_any, pubkey_from_slice, encode_base64};
+use crate::openapi::ApiSchema;
+
+/// Either an encoded string (base58, hex or base64) or a raw JSON byte array.
+#[derive(Deserialize, ApiSchema)]
+#[serde(untagged)]
+pub enum PubkeyInput {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+#[derive(Deserialize, ApiSchema)]
+pub struct InspectPubkeyRequest {
+    pub pubkey: PubkeyInput,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct InspectPubkeyResponse {
+    #[schema(example = "base58")]
+    pub detected_format: String,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
+    pub base58: String,
+    #[schema(format = "hex")]
+    pub hex: String,
+    #[schema(format = "base64")]
+    pub base64: String,
+    pub bytes: Vec<u8>,
+    pub on_curve: bool,
+    /// Only keys on the ed25519 curve can have a private key; off-curve keys are PDAs.
+    /// Well-known program and sysvar addresses are never reported as signers.
+    pub can_sign: bool,
+    pub label: Option<String>,
+}
+
+pub async fn inspect_pubkey(
+    ApiJson(payload): ApiJson<InspectPubkeyRequest>,
+) -> Result<Json<SuccessResponse<InspectPubkeyResponse>>, ApiError> {
+    let parsed = match &payload.pubkey {
+        PubkeyInput::Text(text) => parse_pubkey_any(text),
+        PubkeyInput::Bytes(bytes) => pubkey_from_slice(bytes).map(|pk| (pk, "byte_array")),
+    };
+
+    let (pubkey, detected_format) = parsed
+        .map_err(|e| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid pubkey: {}", e)).at("pubkey"))?;
+
+    let bytes = pubkey.to_bytes();
+    let on_curve = pubkey.is_on_curve();
+    let label = well_known_label(&pubkey);
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: InspectPubkeyResponse {
+            detected_format: detected_format.to_string(),
+            base58: pubkey.to_string(),
+            hex: hex::encode(bytes),
+            base64: encode_base64(&bytes),
+            bytes: bytes.to_vec(),
+            on_curve,
+            can_sign: on_curve && label.is_none(),
+            label: label.map(str::to_string),
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    async fn inspect(pubkey: Value) -> Result<InspectPubkeyResponse, ApiError> {
+        let payload: InspectPubkeyRequest = serde_json::from_value(json!({ "pubkey": pubkey })).unwrap();
+        inspect_pubkey(ApiJson(payload)).await.map(|Json(r)| r.data)
+    }
+
+    #[tokio::test]
+    async fn every_input_format_gives_the_same_key() {
+        let pubkey = Keypair::new().pubkey();
+        let bytes = pubkey.to_bytes();
+        let inputs = [
+            (json!(pubkey.to_string()), "base58"),
+            (json!(hex::encode(bytes)), "hex"),
+            (json!(format!("0x{}", hex::encode_upper(bytes))), "hex"),
+            (json!(encode_base64(&bytes)), "base64"),
+            (json!(format!("  {}\n", pubkey)), "base58"),
+            (json!(bytes.to_vec()), "byte_array"),
+        ];
+        for (input, format) in inputs {
+            let inspected = inspect(input.clone()).await.unwrap();
+            assert_eq!(inspected.detected_format, format, "{}", input);
+            assert_eq!(inspected.base58, pubkey.to_string());
+            assert_eq!(inspected.hex, hex::encode(bytes));
+            assert_eq!(inspected.base64, encode_base64(&bytes));
+            assert_eq!(inspected.bytes, bytes);
+        }
+
+        for input in [json!("not a key"), json!(hex::encode([1u8; 31])), json!(vec![1u8; 33])] {
+            let err = inspect(input).await.err().unwrap();
+            assert_eq!((err.code, err.field.as_deref()), (ErrorCode::InvalidPubkey, Some("pubkey")));
+        }
+    }
+
+    #[tokio::test]
+    async fn only_on_curve_keys_can_sign() {
+        let wallet = inspect(json!(Keypair::new().pubkey().to_string())).await.unwrap();
+        assert!(wallet.on_curve && wallet.can_sign);
+        assert_eq!(wallet.label, None);
+
+        // The associated token account of a wallet for a mint is a PDA.
+        let program_id: Pubkey = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL".parse().unwrap();
+        let token_program: Pubkey = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".parse().unwrap();
+        let mint: Pubkey = "So11111111111111111111111111111111111111112".parse().unwrap();
+        let owner = Keypair::new().pubkey();
+        let seeds: [&[u8]; 3] = [owner.as_ref(), token_program.as_ref(), mint.as_ref()];
+        let (ata, _) = Pubkey::find_program_address(&seeds, &program_id);
+        let pda = inspect(json!(ata.to_string())).await.unwrap();
+        assert!(!pda.on_curve && !pda.can_sign);
+    }
+
+    #[tokio::test]
+    async fn well_known_addresses_are_labelled_and_never_signers() {
+        let inspected = inspect(json!(Pubkey::default().to_string())).await.unwrap();
+        assert_eq!(inspected.label.as_deref(), Some("System Program"));
+        assert!(inspected.on_curve && !inspected.can_sign);
+    }
+}
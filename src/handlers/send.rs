@@ -1,110 +1,88 @@
-use axum::{Json, http::StatusCode};
+use axum::{Json, extract::State};
 use serde::{Deserialize, Serialize};
 use solana_sdk::system_instruction;
 use spl_token::instruction as token_instruction;
 use spl_associated_token_account::get_associated_token_address;
+use std::sync::Arc;
+use crate::error::{ApiError, ApiJson, ErrorCode};
+use crate::handlers::SuccessResponse;
+use crate::metrics::Metrics;
 use crate::utils::{validate_pubkey, encode_base64};
+use crate::openapi::ApiSchema;
 
-#[derive(Serialize)]
-pub struct SuccessResponse<T> {
-    pub success: bool,
-    pub data: T,
-}
-
-#[derive(Serialize)]
-pub struct ErrorResponse {
-    pub success: bool,
-    pub error: String,
-}
-
-#[derive(Deserialize)]
+#[derive(Deserialize, ApiSchema)]
 pub struct SendSolRequest {
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
     pub from: String,
+    #[schema(format = "base58", example = "9jqAyNviuf2CcdajQyTv5YgPhCt4zHJUtmQTAdwCWaxM")]
     pub to: String,
+    #[schema(example = "100000")]
     pub lamports: u64,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ApiSchema)]
 pub struct SendSolResponse {
+    #[schema(format = "base58", example = "11111111111111111111111111111111")]
     pub program_id: String,
+    #[schema(format = "base58", example = r#"["FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT", "9jqAyNviuf2CcdajQyTv5YgPhCt4zHJUtmQTAdwCWaxM"]"#)]
     pub accounts: Vec<String>,
+    #[schema(format = "base64")]
     pub instruction_data: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ApiSchema)]
 pub struct SendTokenRequest {
+    #[schema(format = "base58", example = "9jqAyNviuf2CcdajQyTv5YgPhCt4zHJUtmQTAdwCWaxM")]
     pub destination: String,
+    #[schema(format = "base58", example = "So11111111111111111111111111111111111111112")]
     pub mint: String,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
     pub owner: String,
+    #[schema(example = "1000000")]
     pub amount: u64,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ApiSchema)]
 pub struct SendTokenResponse {
+    #[schema(format = "base58", example = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")]
     pub program_id: String,
     pub accounts: Vec<AccountMetaResponse>,
+    #[schema(format = "base64")]
     pub instruction_data: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ApiSchema)]
 pub struct AccountMetaResponse {
+    #[schema(format = "base58")]
     pub pubkey: String,
     pub is_signer: bool,
     pub is_writable: bool,
 }
 
 pub async fn send_sol(
-    Json(payload): Json<SendSolRequest>,
-) -> Result<Json<SuccessResponse<SendSolResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    State(metrics): State<Arc<Metrics>>,
+    ApiJson(payload): ApiJson<SendSolRequest>,
+) -> Result<Json<SuccessResponse<SendSolResponse>>, ApiError> {
     let from = validate_pubkey(&payload.from)
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid from address: {}", e),
-            }),
-        ))?;
+        .map_err(|e| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid from address: {}", e)).at("from"))?;
 
     let to = validate_pubkey(&payload.to)
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid to address: {}", e),
-            }),
-        ))?;
+        .map_err(|e| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid to address: {}", e)).at("to"))?;
 
     if payload.lamports == 0 {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: "Lamports must be greater than 0".to_string(),
-            }),
-        ));
+        return Err(ApiError::new(ErrorCode::AmountZero, "Lamports must be greater than 0").at("lamports"));
     }
 
     if from == to {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: "From and to addresses cannot be the same".to_string(),
-            }),
-        ));
+        return Err(ApiError::invalid_request("From and to addresses cannot be the same").at("to"));
     }
 
     if payload.lamports > 1_000_000_000_000_000 {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: "Lamport amount too large".to_string(),
-            }),
-        ));
+        return Err(ApiError::new(ErrorCode::AmountTooLarge, "Lamport amount too large").at("lamports"));
     }
 
     let instruction = system_instruction::transfer(&from, &to, payload.lamports);
+    metrics.record_lamports_requested(payload.lamports);
 
     Ok(Json(SuccessResponse {
         success: true,
@@ -117,43 +95,19 @@ pub async fn send_sol(
 }
 
 pub async fn send_token(
-    Json(payload): Json<SendTokenRequest>,
-) -> Result<Json<SuccessResponse<SendTokenResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    ApiJson(payload): ApiJson<SendTokenRequest>,
+) -> Result<Json<SuccessResponse<SendTokenResponse>>, ApiError> {
     let destination = validate_pubkey(&payload.destination)
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid destination: {}", e),
-            }),
-        ))?;
+        .map_err(|e| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid destination: {}", e)).at("destination"))?;
 
     let mint = validate_pubkey(&payload.mint)
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid mint: {}", e),
-            }),
-        ))?;
+        .map_err(|e| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid mint: {}", e)).at("mint"))?;
 
     let owner = validate_pubkey(&payload.owner)
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid owner: {}", e),
-            }),
-        ))?;
+        .map_err(|e| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid owner: {}", e)).at("owner"))?;
 
     if payload.amount == 0 {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: "Amount must be greater than 0".to_string(),
-            }),
-        ));
+        return Err(ApiError::new(ErrorCode::AmountZero, "Amount must be greater than 0").at("amount"));
     }
 
     let destination_ata = get_associated_token_address(&destination, &mint);
@@ -165,13 +119,7 @@ pub async fn send_token(
         &owner,
         &[],
         payload.amount,
-    ).map_err(|e| (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        Json(ErrorResponse {
-            success: false,
-            error: format!("Failed to create instruction: {}", e),
-        }),
-    ))?;
+    ).map_err(|e| ApiError::internal(format!("Failed to create instruction: {}", e)))?;
 
     let accounts = instruction.accounts.iter().map(|meta| AccountMetaResponse {
         pubkey: meta.pubkey.to_string(),
@@ -0,0 +1,218 @@
+use axum::{Json, extract::State};
+use chrono::{Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::error::{ApiError, ApiJson, ErrorCode};
+use crate::handlers::message::verify_signature;
+use crate::handlers::SuccessResponse;
+use crate::siws::{now_rfc3339, parse_time, SiwsMessage, SiwsNonces, MESSAGE_VERSION};
+use crate::utils::SignatureEncoding;
+use crate::openapi::ApiSchema;
+
+/// Tolerance for an `Issued At` slightly ahead of the server clock.
+const ALLOWED_CLOCK_SKEW_SECS: i64 = 60;
+
+#[derive(Serialize, ApiSchema)]
+pub struct SiwsNonceResponse {
+    pub nonce: String,
+    #[schema(format = "date-time")]
+    pub expires_at: String,
+}
+
+#[derive(Deserialize, ApiSchema)]
+pub struct BuildSiwsMessageRequest {
+    #[schema(example = "app.example.com")]
+    pub domain: String,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
+    pub address: String,
+    pub statement: Option<String>,
+    #[schema(format = "uri", example = "https://app.example.com/login")]
+    pub uri: String,
+    #[schema(example = "mainnet")]
+    pub chain_id: Option<String>,
+    /// A nonce from `/siws/nonce`; one is issued when omitted.
+    pub nonce: Option<String>,
+    /// RFC 3339 timestamps. `issued_at` defaults to now.
+    #[schema(format = "date-time")]
+    pub issued_at: Option<String>,
+    #[schema(format = "date-time")]
+    pub expiration_time: Option<String>,
+    #[schema(format = "date-time")]
+    pub not_before: Option<String>,
+    pub request_id: Option<String>,
+    #[serde(default)]
+    #[schema(format = "uri")]
+    pub resources: Vec<String>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct BuildSiwsMessageResponse {
+    pub message: String,
+    pub nonce: String,
+    #[schema(format = "date-time")]
+    pub issued_at: String,
+}
+
+#[derive(Deserialize, ApiSchema)]
+pub struct VerifySiwsRequest {
+    pub message: String,
+    /// Ed25519 signature by the message's address.
+    pub signature: String,
+    #[serde(default)]
+    pub signature_encoding: SignatureEncoding,
+    /// The domain this server expects sign-ins for.
+    #[schema(example = "app.example.com")]
+    pub domain: String,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct VerifySiwsResponse {
+    pub valid: bool,
+    /// Why verification failed; absent when `valid` is true.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
+    pub address: String,
+    pub domain: String,
+    #[schema(format = "uri")]
+    pub uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statement: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<String>,
+    pub nonce: String,
+    #[schema(format = "date-time")]
+    pub issued_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(format = "date-time")]
+    pub expiration_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[schema(format = "uri")]
+    pub resources: Vec<String>,
+}
+
+fn issue_nonce(nonces: &SiwsNonces) -> Result<SiwsNonceResponse, ApiError> {
+    let (nonce, ttl) = nonces
+        .issue()
+        .map_err(|e| ApiError::new(ErrorCode::TooManyRequests, e))?;
+    let expires_at = Utc::now() + ChronoDuration::seconds(ttl.as_secs() as i64);
+    Ok(SiwsNonceResponse {
+        nonce,
+        expires_at: expires_at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+    })
+}
+
+pub async fn siws_nonce(
+    State(nonces): State<Arc<SiwsNonces>>,
+) -> Result<Json<SuccessResponse<SiwsNonceResponse>>, ApiError> {
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: issue_nonce(&nonces)?,
+    }))
+}
+
+pub async fn build_siws_message(
+    State(nonces): State<Arc<SiwsNonces>>,
+    ApiJson(payload): ApiJson<BuildSiwsMessageRequest>,
+) -> Result<Json<SuccessResponse<BuildSiwsMessageResponse>>, ApiError> {
+    let nonce = match payload.nonce {
+        Some(nonce) => nonce,
+        None => issue_nonce(&nonces)?.nonce,
+    };
+
+    let message = SiwsMessage {
+        domain: payload.domain,
+        address: payload.address,
+        statement: payload.statement.filter(|s| !s.is_empty()),
+        uri: payload.uri,
+        version: MESSAGE_VERSION.to_string(),
+        chain_id: payload.chain_id,
+        nonce,
+        issued_at: payload.issued_at.unwrap_or_else(now_rfc3339),
+        expiration_time: payload.expiration_time,
+        not_before: payload.not_before,
+        request_id: payload.request_id,
+        resources: payload.resources,
+    };
+    message
+        .validate()
+        .map_err(|e| ApiError::new(ErrorCode::InvalidMessage, e))?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: BuildSiwsMessageResponse {
+            message: message.to_text(),
+            nonce: message.nonce,
+            issued_at: message.issued_at,
+        },
+    }))
+}
+
+/// Checks everything except the nonce, which is only redeemed once the rest
+/// has passed so a bad attempt can't burn a legitimate user's nonce.
+fn check_message(message: &SiwsMessage, payload: &VerifySiwsRequest) -> Result<(), String> {
+    let valid = verify_signature(
+        &message.address,
+        &payload.signature,
+        payload.signature_encoding,
+        payload.message.as_bytes(),
+    )
+    .map_err(|e| e.message)?;
+    if !valid {
+        return Err("Signature does not match the message address".to_string());
+    }
+    if message.domain != payload.domain {
+        return Err(format!("Message is for domain '{}'", message.domain));
+    }
+
+    let now = Utc::now();
+    let issued_at = parse_time(&message.issued_at, "issued_at")?;
+    if issued_at > now + ChronoDuration::seconds(ALLOWED_CLOCK_SKEW_SECS) {
+        return Err("Message was issued in the future".to_string());
+    }
+    if let Some(expiration_time) = &message.expiration_time {
+        if parse_time(expiration_time, "expiration_time")? <= now {
+            return Err("Message has expired".to_string());
+        }
+    }
+    if let Some(not_before) = &message.not_before {
+        if parse_time(not_before, "not_before")? > now {
+            return Err("Message is not valid yet".to_string());
+        }
+    }
+    Ok(())
+}
+
+pub async fn verify_siws(
+    State(nonces): State<Arc<SiwsNonces>>,
+    ApiJson(payload): ApiJson<VerifySiwsRequest>,
+) -> Result<Json<SuccessResponse<VerifySiwsResponse>>, ApiError> {
+    let message = SiwsMessage::parse(&payload.message)
+        .map_err(|e| ApiError::new(ErrorCode::InvalidMessage, e).at("message"))?;
+
+    let reason = match check_message(&message, &payload) {
+        Ok(()) if !nonces.consume(&message.nonce) => Some("Nonce is unknown, expired or already used".to_string()),
+        Ok(()) => None,
+        Err(reason) => Some(reason),
+    };
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: VerifySiwsResponse {
+            valid: reason.is_none(),
+            reason,
+            address: message.address,
+            domain: message.domain,
+            uri: message.uri,
+            statement: message.statement,
+            chain_id: message.chain_id,
+            nonce: message.nonce,
+            issued_at: message.issued_at,
+            expiration_time: message.expiration_time,
+            request_id: message.request_id,
+            resources: message.resources,
+        },
+    }))
+}
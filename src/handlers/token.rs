@@ -1,86 +1,71 @@
-use axum::{Json, http::StatusCode};
+use axum::Json;
 use serde::{Deserialize, Serialize};
 use spl_token::instruction as token_instruction;
+use crate::error::{ApiError, ApiJson, ErrorCode};
+use crate::handlers::SuccessResponse;
 use crate::utils::{validate_pubkey, encode_base64};
+use crate::openapi::ApiSchema;
 
-#[derive(Serialize)]
-pub struct SuccessResponse<T> {
-    pub success: bool,
-    pub data: T,
-}
-
-#[derive(Serialize)]
-pub struct ErrorResponse {
-    pub success: bool,
-    pub error: String,
-}
-
-#[derive(Deserialize)]
+#[derive(Deserialize, ApiSchema)]
 pub struct CreateTokenRequest {
     #[serde(rename = "mintAuthority")]
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
     pub mint_authority: String,
+    #[schema(format = "base58", example = "9jqAyNviuf2CcdajQyTv5YgPhCt4zHJUtmQTAdwCWaxM")]
     pub mint: String,
+    #[schema(example = "6")]
     pub decimals: u8,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ApiSchema)]
 pub struct CreateTokenResponse {
+    #[schema(format = "base58", example = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")]
     pub program_id: String,
     pub accounts: Vec<AccountMetaResponse>,
+    #[schema(format = "base64")]
     pub instruction_data: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ApiSchema)]
 pub struct MintTokenRequest {
+    #[schema(format = "base58", example = "9jqAyNviuf2CcdajQyTv5YgPhCt4zHJUtmQTAdwCWaxM")]
     pub mint: String,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
     pub destination: String,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
     pub authority: String,
+    #[schema(example = "1000000")]
     pub amount: u64,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ApiSchema)]
 pub struct MintTokenResponse {
+    #[schema(format = "base58", example = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")]
     pub program_id: String,
     pub accounts: Vec<AccountMetaResponse>,
+    #[schema(format = "base64")]
     pub instruction_data: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ApiSchema)]
 pub struct AccountMetaResponse {
+    #[schema(format = "base58")]
     pub pubkey: String,
     pub is_signer: bool,
     pub is_writable: bool,
 }
 
 pub async fn create_token(
-    Json(payload): Json<CreateTokenRequest>,
-) -> Result<Json<SuccessResponse<CreateTokenResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    ApiJson(payload): ApiJson<CreateTokenRequest>,
+) -> Result<Json<SuccessResponse<CreateTokenResponse>>, ApiError> {
     let mint_authority = validate_pubkey(&payload.mint_authority)
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid mint authority: {}", e),
-            }),
-        ))?;
+        .map_err(|e| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid mint authority: {}", e)).at("mintAuthority"))?;
 
     let mint = validate_pubkey(&payload.mint)
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid mint: {}", e),
-            }),
-        ))?;
+        .map_err(|e| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid mint: {}", e)).at("mint"))?;
 
     if payload.decimals > 9 {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: "Decimals must be between 0 and 9".to_string(),
-            }),
-        ));
+        return Err(ApiError::invalid_request("Decimals must be between 0 and 9").at("decimals"));
     }
 
     let instruction = token_instruction::initialize_mint(
@@ -89,13 +74,7 @@ pub async fn create_token(
         &mint_authority,
         Some(&mint_authority),
         payload.decimals,
-    ).map_err(|e| (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        Json(ErrorResponse {
-            success: false,
-            error: format!("Failed to create instruction: {}", e),
-        }),
-    ))?;
+    ).map_err(|e| ApiError::internal(format!("Failed to create instruction: {}", e)))?;
 
     let accounts = instruction.accounts.iter().map(|meta| AccountMetaResponse {
         pubkey: meta.pubkey.to_string(),
@@ -114,43 +93,19 @@ pub async fn create_token(
 }
 
 pub async fn mint_token(
-    Json(payload): Json<MintTokenRequest>,
-) -> Result<Json<SuccessResponse<MintTokenResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    ApiJson(payload): ApiJson<MintTokenRequest>,
+) -> Result<Json<SuccessResponse<MintTokenResponse>>, ApiError> {
     let mint = validate_pubkey(&payload.mint)
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid mint: {}", e),
-            }),
-        ))?;
+        .map_err(|e| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid mint: {}", e)).at("mint"))?;
 
     let destination = validate_pubkey(&payload.destination)
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid destination: {}", e),
-            }),
-        ))?;
+        .map_err(|e| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid destination: {}", e)).at("destination"))?;
 
     let authority = validate_pubkey(&payload.authority)
-        .map_err(|e| (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: format!("Invalid authority: {}", e),
-            }),
-        ))?;
+        .map_err(|e| ApiError::new(ErrorCode::InvalidPubkey, format!("Invalid authority: {}", e)).at("authority"))?;
 
     if payload.amount == 0 {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                success: false,
-                error: "Amount must be greater than 0".to_string(),
-            }),
-        ));
+        return Err(ApiError::new(ErrorCode::AmountZero, "Amount must be greater than 0").at("amount"));
     }
 
     let instruction = token_instruction::mint_to(
@@ -160,13 +115,7 @@ pub async fn mint_token(
         &authority,
         &[],
         payload.amount,
-    ).map_err(|e| (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        Json(ErrorResponse {
-            success: false,
-            error: format!("Failed to create instruction: {}", e),
-        }),
-    ))?;
+    ).map_err(|e| ApiError::internal(format!("Failed to create instruction: {}", e)))?;
 
     let accounts = instruction.accounts.iter().map(|meta| AccountMetaResponse {
         pubkey: meta.pubkey.to_string(),
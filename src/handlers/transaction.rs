@@ -0,0 +1,556 @@
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::system_instruction::SystemInstruction;
+use solana_sdk::system_program;
+use solana_sdk::message::VersionedMessage;
+use solana_sdk::transaction::VersionedTransaction;
+use std::sync::Arc;
+use crate::decoder::{decode_instruction, DecodedInstruction};
+use crate::error::{ApiError, ApiJson, ErrorCode};
+use crate::handlers::vault::resolve_signing_key;
+use crate::handlers::SuccessResponse;
+use crate::metrics::Metrics;
+use crate::utils::{decode_base64, encode_base64};
+use crate::vault::KeyVault;
+use crate::openapi::ApiSchema;
+
+#[derive(Deserialize, ApiSchema)]
+pub struct SignTransactionRequest {
+    /// Serialized legacy or versioned transaction, base64 encoded.
+    #[schema(format = "base64")]
+    pub transaction: String,
+    #[serde(default)]
+    pub secrets: Vec<String>,
+    #[serde(default)]
+    pub key_ids: Vec<String>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct SignTransactionResponse {
+    #[schema(format = "base64")]
+    pub transaction: String,
+    #[schema(example = "legacy")]
+    pub version: String,
+    /// Pubkeys signed for by this request.
+    #[schema(format = "base58", example = r#"["FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT"]"#)]
+    pub signed: Vec<String>,
+    /// Required signers that still have no signature.
+    #[schema(format = "base58", example = r#"["9jqAyNviuf2CcdajQyTv5YgPhCt4zHJUtmQTAdwCWaxM"]"#)]
+    pub missing_signers: Vec<String>,
+    pub complete: bool,
+}
+
+#[derive(Deserialize, ApiSchema)]
+pub struct VerifyTransactionRequest {
+    #[schema(format = "base64")]
+    pub transaction: String,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct VerifyTransactionResponse {
+    /// True when every required signature is present and valid.
+    pub valid: bool,
+    #[schema(example = "legacy")]
+    pub version: String,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
+    pub fee_payer: String,
+    pub signatures: Vec<SignatureStatus>,
+    #[schema(format = "base58")]
+    pub missing_signers: Vec<String>,
+    #[schema(format = "base58")]
+    pub invalid_signers: Vec<String>,
+    /// The recent blockhash, or the stored nonce when `durable_nonce` is set.
+    #[schema(format = "base58")]
+    pub recent_blockhash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub durable_nonce: Option<DurableNonce>,
+    pub size: usize,
+    pub max_size: usize,
+    pub fits_in_packet: bool,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct SignatureStatus {
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
+    pub pubkey: String,
+    #[schema(format = "base58")]
+    pub signature: Option<String>,
+    pub status: SignatureState,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, ApiSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureState {
+    Valid,
+    Invalid,
+    Missing,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct DurableNonce {
+    #[schema(format = "base58")]
+    pub nonce_account: Option<String>,
+    #[schema(format = "base58")]
+    pub nonce_authority: Option<String>,
+}
+
+#[derive(Deserialize, ApiSchema)]
+pub struct DecodeTransactionRequest {
+    #[schema(format = "base64")]
+    pub transaction: String,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct DecodeTransactionResponse {
+    #[schema(example = "0")]
+    pub version: String,
+    /// One entry per required signer; `null` where the slot is unsigned.
+    #[schema(format = "base58")]
+    pub signatures: Vec<Option<String>>,
+    pub header: MessageHeaderResponse,
+    pub account_keys: Vec<AccountKeyResponse>,
+    #[schema(format = "base58")]
+    pub recent_blockhash: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub address_table_lookups: Vec<AddressTableLookupResponse>,
+    pub instructions: Vec<DecodedInstruction>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct MessageHeaderResponse {
+    pub num_required_signatures: u8,
+    pub num_readonly_signed_accounts: u8,
+    pub num_readonly_unsigned_accounts: u8,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct AccountKeyResponse {
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
+    pub pubkey: String,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    /// `transaction` for static keys, `lookup_table` for v0 lookup entries.
+    #[schema(example = "transaction")]
+    pub source: &'static str,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct AddressTableLookupResponse {
+    #[schema(format = "base58")]
+    pub account_key: String,
+    pub writable_indexes: Vec<u8>,
+    pub readonly_indexes: Vec<u8>,
+}
+
+/// Decodes a base64 wire-format transaction from the `transaction` field;
+/// legacy transactions decode as `VersionedTransaction` too.
+pub fn deserialize_transaction(encoded: &str) -> Result<VersionedTransaction, ApiError> {
+    let bytes = decode_base64(encoded)
+        .map_err(|e| ApiError::new(ErrorCode::InvalidEncoding, format!("Invalid base64: {}", e)).at("transaction"))?;
+    bincode::deserialize(&bytes)
+        .map_err(|e| ApiError::new(ErrorCode::InvalidTransaction, format!("Invalid transaction: {}", e)).at("transaction"))
+}
+
+/// The static keys that must sign. The message header is client-supplied, so
+/// a signer count of zero or past the end of the keys is rejected here rather
+/// than trusted for slicing.
+fn required_signers(tx: &VersionedTransaction) -> Result<&[Pubkey], ApiError> {
+    let required = usize::from(tx.message.header().num_required_signatures);
+    let keys = tx.message.static_account_keys();
+    if keys.len() < required || required == 0 {
+        return Err(ApiError::new(ErrorCode::InvalidTransaction, "Transaction message has an invalid signer count").at("transaction"));
+    }
+    Ok(&keys[..required])
+}
+
+pub fn transaction_version(tx: &VersionedTransaction) -> String {
+    match tx.version() {
+        solana_sdk::transaction::TransactionVersion::Legacy(_) => "legacy".to_string(),
+        solana_sdk::transaction::TransactionVersion::Number(n) => n.to_string(),
+    }
+}
+
+pub async fn sign_transaction(
+    State(vault): State<Arc<KeyVault>>,
+    State(metrics): State<Arc<Metrics>>,
+    ApiJson(payload): ApiJson<SignTransactionRequest>,
+) -> Result<Json<SuccessResponse<SignTransactionResponse>>, ApiError> {
+    if payload.secrets.is_empty() && payload.key_ids.is_empty() {
+        return Err(ApiError::new(ErrorCode::MissingField, "Provide at least one secret or key_id").at("secrets"));
+    }
+
+    let mut tx = deserialize_transaction(&payload.transaction)?;
+
+    let signer_keys = required_signers(&tx)?.to_vec();
+    // Unsigned transactions are often serialized without signature slots.
+    tx.signatures.resize(signer_keys.len(), Signature::default());
+    let message_bytes = tx.message.serialize();
+
+    let keys = payload
+        .secrets
+        .iter()
+        .enumerate()
+        .map(|(i, secret)| {
+            resolve_signing_key(&vault, Some(secret), None, None, None)
+                .map_err(|e| ApiError { field: Some(format!("secrets[{}]", i)), ..e })
+        })
+        .chain(payload.key_ids.iter().enumerate().map(|(i, key_id)| {
+            resolve_signing_key(&vault, None, Some(key_id), None, None)
+                .map_err(|e| ApiError { field: Some(format!("key_ids[{}]", i)), ..e })
+        }));
+
+    let mut signed = Vec::new();
+    for key in keys {
+        let key = key?;
+        let keypair = Keypair::from_bytes(&key.bytes)
+            .map_err(|e| ApiError::new(ErrorCode::InvalidSecretKey, format!("Invalid private key: {}", e)))?;
+        let pubkey = keypair.pubkey();
+        let slot = signer_keys
+            .iter()
+            .position(|signer| *signer == pubkey)
+            .ok_or_else(|| ApiError::invalid_request(format!("{} is not a required signer of this transaction", pubkey)))?;
+        tx.signatures[slot] = keypair.sign_message(&message_bytes);
+        signed.push(pubkey.to_string());
+    }
+
+    metrics.record_signatures("transaction", signed.len());
+
+    let missing_signers: Vec<String> = signer_keys
+        .iter()
+        .zip(&tx.signatures)
+        .filter(|(_, signature)| **signature == Signature::default())
+        .map(|(pubkey, _)| pubkey.to_string())
+        .collect();
+
+    let serialized = bincode::serialize(&tx)
+        .map_err(|e| ApiError::internal(format!("Failed to serialize transaction: {}", e)))?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: SignTransactionResponse {
+            transaction: encode_base64(&serialized),
+            version: transaction_version(&tx),
+            signed,
+            complete: missing_signers.is_empty(),
+            missing_signers,
+        },
+    }))
+}
+
+/// A transaction uses a durable nonce when its first instruction is
+/// `SystemInstruction::AdvanceNonceAccount`.
+fn durable_nonce(tx: &VersionedTransaction) -> Option<DurableNonce> {
+    let keys = tx.message.static_account_keys();
+    let instruction = tx.message.instructions().first()?;
+    if keys.get(usize::from(instruction.program_id_index)) != Some(&system_program::id()) {
+        return None;
+    }
+    match bincode::deserialize::<SystemInstruction>(&instruction.data) {
+        Ok(SystemInstruction::AdvanceNonceAccount) => {
+            // Accounts may live in an address lookup table for v0 messages.
+            let account = |position: usize| {
+                instruction
+                    .accounts
+                    .get(position)
+                    .and_then(|index| keys.get(usize::from(*index)))
+                    .map(|pubkey| pubkey.to_string())
+            };
+            Some(DurableNonce {
+                nonce_account: account(0),
+                nonce_authority: account(2),
+            })
+        }
+        _ => None,
+    }
+}
+
+pub async fn verify_transaction(
+    ApiJson(payload): ApiJson<VerifyTransactionRequest>,
+) -> Result<Json<SuccessResponse<VerifyTransactionResponse>>, ApiError> {
+    let tx = deserialize_transaction(&payload.transaction)?;
+
+    let signer_keys = required_signers(&tx)?;
+    let message_bytes = tx.message.serialize();
+
+    let signatures: Vec<SignatureStatus> = signer_keys
+        .iter()
+        .enumerate()
+        .map(|(i, pubkey)| {
+            let signature = tx.signatures.get(i).filter(|s| **s != Signature::default());
+            let status = match signature {
+                None => SignatureState::Missing,
+                Some(signature) if signature.verify(pubkey.as_ref(), &message_bytes) => SignatureState::Valid,
+                Some(_) => SignatureState::Invalid,
+            };
+            SignatureStatus {
+                pubkey: pubkey.to_string(),
+                signature: signature.map(|s| s.to_string()),
+                status,
+            }
+        })
+        .collect();
+
+    let with_status = |state: SignatureState| -> Vec<String> {
+        signatures
+            .iter()
+            .filter(|s| s.status == state)
+            .map(|s| s.pubkey.clone())
+            .collect()
+    };
+    let missing_signers = with_status(SignatureState::Missing);
+    let invalid_signers = with_status(SignatureState::Invalid);
+    // Extra signatures beyond the signer count make the transaction invalid on chain.
+    let valid = missing_signers.is_empty() && invalid_signers.is_empty() && tx.signatures.len() == signer_keys.len();
+
+    // Re-serialize with every signature slot filled, so size reflects what will be sent.
+    let mut complete = tx.clone();
+    complete.signatures.resize(signer_keys.len(), Signature::default());
+    let size = bincode::serialized_size(&complete)
+        .map_err(|e| ApiError::internal(format!("Failed to size transaction: {}", e)))? as usize;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: VerifyTransactionResponse {
+            valid,
+            version: transaction_version(&tx),
+            fee_payer: signer_keys[0].to_string(),
+            recent_blockhash: tx.message.recent_blockhash().to_string(),
+            durable_nonce: durable_nonce(&tx),
+            signatures,
+            missing_signers,
+            invalid_signers,
+            size,
+            max_size: PACKET_DATA_SIZE,
+            fits_in_packet: size <= PACKET_DATA_SIZE,
+        },
+    }))
+}
+
+/// Account keys in message index order. Lookup table entries can't be resolved
+/// without an RPC call, so they appear as `lookup:<table>:<index>`, writable
+/// entries of every table first, as the runtime loads them.
+fn message_account_keys(message: &VersionedMessage) -> Vec<(String, &'static str)> {
+    let mut keys: Vec<(String, &'static str)> = message
+        .static_account_keys()
+        .iter()
+        .map(|key| (key.to_string(), "transaction"))
+        .collect();
+    if let Some(lookups) = message.address_table_lookups() {
+        let placeholders = |indexes: fn(&solana_sdk::message::v0::MessageAddressTableLookup) -> &Vec<u8>| {
+            lookups
+                .iter()
+                .flat_map(move |lookup| {
+                    indexes(lookup)
+                        .iter()
+                        .map(move |index| (format!("lookup:{}:{}", lookup.account_key, index), "lookup_table"))
+                })
+                .collect::<Vec<_>>()
+        };
+        keys.extend(placeholders(|lookup| &lookup.writable_indexes));
+        keys.extend(placeholders(|lookup| &lookup.readonly_indexes));
+    }
+    keys
+}
+
+pub async fn decode_transaction(
+    ApiJson(payload): ApiJson<DecodeTransactionRequest>,
+) -> Result<Json<SuccessResponse<DecodeTransactionResponse>>, ApiError> {
+    let tx = deserialize_transaction(&payload.transaction)?;
+    let invalid = |message: String| ApiError::new(ErrorCode::InvalidTransaction, message).at("transaction");
+    let message = &tx.message;
+    let header = message.header();
+
+    let keys = message_account_keys(message);
+    let account_keys = keys
+        .iter()
+        .enumerate()
+        .map(|(i, (pubkey, source))| AccountKeyResponse {
+            pubkey: pubkey.clone(),
+            is_signer: message.is_signer(i),
+            is_writable: message.is_maybe_writable(i),
+            source,
+        })
+        .collect();
+
+    let mut instructions = Vec::new();
+    for (position, instruction) in message.instructions().iter().enumerate() {
+        let key = |index: u8| {
+            keys.get(usize::from(index))
+                .map(|(pubkey, _)| pubkey.clone())
+                .ok_or_else(|| invalid(format!("Instruction {} references missing account {}", position, index)))
+        };
+        let program_id = message
+            .static_account_keys()
+            .get(usize::from(instruction.program_id_index))
+            .ok_or_else(|| invalid(format!("Instruction {} has an invalid program id index", position)))?;
+        let accounts = instruction.accounts.iter().map(|index| key(*index)).collect::<Result<Vec<_>, _>>()?;
+        instructions.push(decode_instruction(program_id, accounts, &instruction.data));
+    }
+
+    let required = usize::from(header.num_required_signatures);
+    let mut signatures = tx.signatures.clone();
+    signatures.resize(required.max(signatures.len()), Signature::default());
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: DecodeTransactionResponse {
+            version: transaction_version(&tx),
+            signatures: signatures
+                .iter()
+                .map(|s| (*s != Signature::default()).then(|| s.to_string()))
+                .collect(),
+            header: MessageHeaderResponse {
+                num_required_signatures: header.num_required_signatures,
+                num_readonly_signed_accounts: header.num_readonly_signed_accounts,
+                num_readonly_unsigned_accounts: header.num_readonly_unsigned_accounts,
+            },
+            account_keys,
+            recent_blockhash: message.recent_blockhash().to_string(),
+            address_table_lookups: message
+                .address_table_lookups()
+                .unwrap_or_default()
+                .iter()
+                .map(|lookup| AddressTableLookupResponse {
+                    account_key: lookup.account_key.to_string(),
+                    writable_indexes: lookup.writable_indexes.clone(),
+                    readonly_indexes: lookup.readonly_indexes.clone(),
+                })
+                .collect(),
+            instructions,
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::message::{Message, MessageHeader};
+    use solana_sdk::system_instruction;
+
+    fn encode(tx: &VersionedTransaction) -> String {
+        encode_base64(&bincode::serialize(tx).unwrap())
+    }
+
+    async fn sign(transaction: String, secrets: Vec<String>) -> Result<SignTransactionResponse, ApiError> {
+        let payload = SignTransactionRequest { transaction, secrets, key_ids: Vec::new() };
+        sign_transaction(State(Arc::default()), State(Arc::default()), ApiJson(payload))
+            .await
+            .map(|Json(response)| response.data)
+    }
+
+    #[tokio::test]
+    async fn rejects_signer_count_past_the_account_keys() {
+        let signer = Keypair::new();
+        let message = Message {
+            header: MessageHeader {
+                num_required_signatures: 3,
+                num_readonly_signed_accounts: 0,
+                num_readonly_unsigned_accounts: 0,
+            },
+            account_keys: vec![signer.pubkey()],
+            recent_blockhash: Hash::default(),
+            instructions: Vec::new(),
+        };
+        let tx = VersionedTransaction { signatures: Vec::new(), message: VersionedMessage::Legacy(message) };
+
+        let error = sign(encode(&tx), vec![signer.to_base58_string()]).await.err().expect("sign should fail");
+        assert_eq!(error.code, ErrorCode::InvalidTransaction);
+        let payload = VerifyTransactionRequest { transaction: encode(&tx) };
+        let error = verify_transaction(ApiJson(payload)).await.err().expect("verify should fail");
+        assert_eq!(error.code, ErrorCode::InvalidTransaction);
+    }
+
+    #[tokio::test]
+    async fn signs_one_of_several_required_signers() {
+        let (payer, other) = (Keypair::new(), Keypair::new());
+        let instructions = [
+            system_instruction::transfer(&payer.pubkey(), &other.pubkey(), 1),
+            system_instruction::transfer(&other.pubkey(), &payer.pubkey(), 1),
+        ];
+        let message = Message::new(&instructions, Some(&payer.pubkey()));
+        let tx = VersionedTransaction { signatures: Vec::new(), message: VersionedMessage::Legacy(message) };
+
+        let response = sign(encode(&tx), vec![payer.to_base58_string()]).await.unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(response.signed, vec![payer.pubkey().to_string()]);
+        assert_eq!(response.missing_signers, vec![other.pubkey().to_string()]);
+        assert!(!response.complete);
+    }
+
+    async fn verify(tx: &VersionedTransaction) -> VerifyTransactionResponse {
+        let payload = VerifyTransactionRequest { transaction: encode(tx) };
+        verify_transaction(ApiJson(payload)).await.map(|Json(response)| response.data).unwrap()
+    }
+
+    fn decode(transaction: &str) -> VersionedTransaction {
+        bincode::deserialize(&crate::utils::decode_base64(transaction).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn verifies_a_partly_signed_transaction() {
+        let (payer, other) = (Keypair::new(), Keypair::new());
+        let blockhash = Hash::new_unique();
+        let instructions = [
+            system_instruction::transfer(&payer.pubkey(), &other.pubkey(), 1),
+            system_instruction::transfer(&other.pubkey(), &payer.pubkey(), 1),
+        ];
+        let message = Message::new_with_blockhash(&instructions, Some(&payer.pubkey()), &blockhash);
+        let tx = VersionedTransaction { signatures: Vec::new(), message: VersionedMessage::Legacy(message) };
+        let signed = decode(&sign(encode(&tx), vec![payer.to_base58_string()]).await.unwrap().transaction);
+
+        let verified = verify(&signed).await;
+        assert!(!verified.valid);
+        assert_eq!(verified.fee_payer, payer.pubkey().to_string());
+        assert_eq!(verified.recent_blockhash, blockhash.to_string());
+        assert!(verified.durable_nonce.is_none());
+        let statuses: Vec<_> = verified.signatures.iter().map(|s| (s.pubkey.clone(), s.status)).collect();
+        assert!(statuses == [
+            (payer.pubkey().to_string(), SignatureState::Valid),
+            (other.pubkey().to_string(), SignatureState::Missing),
+        ]);
+        assert_eq!(verified.signatures[0].signature, Some(signed.signatures[0].to_string()));
+        assert_eq!(verified.signatures[1].signature, None);
+        assert_eq!(verified.missing_signers, [other.pubkey().to_string()]);
+        assert!(verified.invalid_signers.is_empty());
+
+        // Sized as if the missing signature were there.
+        let mut complete = signed.clone();
+        complete.signatures[1] = other.sign_message(&complete.message.serialize());
+        assert_eq!(verified.size, bincode::serialized_size(&complete).unwrap() as usize);
+        assert_eq!((verified.max_size, verified.fits_in_packet), (1232, true));
+
+        let verified = verify(&complete).await;
+        assert!(verified.valid && verified.missing_signers.is_empty());
+
+        let mut tampered = complete;
+        tampered.signatures[1] = payer.sign_message(&tampered.message.serialize());
+        let verified = verify(&tampered).await;
+        assert!(!verified.valid);
+        assert_eq!(verified.invalid_signers, [other.pubkey().to_string()]);
+    }
+
+    #[tokio::test]
+    async fn reports_durable_nonces_and_oversized_transactions() {
+        let (payer, nonce_account) = (Keypair::new(), Keypair::new());
+        let instructions = [
+            system_instruction::advance_nonce_account(&nonce_account.pubkey(), &payer.pubkey()),
+            system_instruction::transfer(&payer.pubkey(), &nonce_account.pubkey(), 1),
+        ];
+        let message = Message::new(&instructions, Some(&payer.pubkey()));
+        let tx = VersionedTransaction { signatures: Vec::new(), message: VersionedMessage::Legacy(message) };
+        let durable_nonce = verify(&tx).await.durable_nonce.expect("durable nonce");
+        assert_eq!(durable_nonce.nonce_account, Some(nonce_account.pubkey().to_string()));
+        assert_eq!(durable_nonce.nonce_authority, Some(payer.pubkey().to_string()));
+
+        let program_id = Pubkey::new_unique();
+        let big = solana_sdk::instruction::Instruction::new_with_bytes(program_id, &[0; 1200], Vec::new());
+        let message = Message::new(&[big], Some(&payer.pubkey()));
+        let tx = VersionedTransaction { signatures: Vec::new(), message: VersionedMessage::Legacy(message) };
+        let verified = verify(&tx).await;
+        assert!(verified.size > 1232 && !verified.fits_in_packet);
+        assert_eq!(verified.missing_signers, [payer.pubkey().to_string()]);
+    }
+}
@@ -0,0 +1,509 @@
+use axum::{Json, extract::{Path, State}};
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::{Keypair, Signer};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::error::{ApiError, ApiJson, ErrorCode};
+use crate::handlers::SuccessResponse;
+use crate::utils::encode_base58;
+use crate::openapi::ApiSchema;
+
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Longest combined prefix + suffix we accept; 58^8 is already ~10^14 attempts.
+const MAX_PATTERN_LEN: usize = 8;
+/// `max_attempts` when none is given: a few minutes' grinding on a small
+/// machine, so a forgotten job doesn't hold its threads for hours.
+const DEFAULT_MAX_ATTEMPTS: u64 = 100_000_000;
+/// Upper bound on `max_attempts`.
+const MAX_ATTEMPTS_CAP: u64 = 10_000_000_000;
+const MAX_RUNNING_JOBS: usize = 4;
+const MAX_THREADS_PER_JOB: usize = 16;
+/// Cores left free for serving requests while jobs grind.
+const RESERVED_CORES: usize = 1;
+/// Finished jobs are kept this long so their result can still be fetched.
+const FINISHED_JOB_TTL: Duration = Duration::from_secs(60 * 60);
+/// Workers publish their attempt count in batches to keep the counter uncontended.
+const ATTEMPT_BATCH: u64 = 256;
+
+#[derive(Deserialize, ApiSchema)]
+pub struct StartVanityRequest {
+    pub prefix: Option<String>,
+    pub suffix: Option<String>,
+    #[serde(default)]
+    pub case_insensitive: bool,
+    pub threads: Option<usize>,
+    pub max_attempts: Option<u64>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct StartVanityResponse {
+    pub job_id: String,
+    pub threads: usize,
+    pub max_attempts: u64,
+    pub expected_attempts: f64,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, ApiSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VanityJobStatus {
+    Running,
+    Found,
+    Cancelled,
+    Exhausted,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct VanityJobResponse {
+    pub job_id: String,
+    pub status: VanityJobStatus,
+    pub attempts: u64,
+    pub attempts_per_second: f64,
+    pub elapsed_seconds: f64,
+    /// Rough time until a match at the current rate; `null` once the job has stopped.
+    pub estimated_seconds_remaining: Option<f64>,
+    pub expected_attempts: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(format = "base58")]
+    pub pubkey: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(format = "base58")]
+    pub secret: Option<String>,
+}
+
+struct VanityPattern {
+    prefix: String,
+    suffix: String,
+    case_insensitive: bool,
+}
+
+impl VanityPattern {
+    fn matches(&self, address: &str) -> bool {
+        if self.case_insensitive {
+            let prefix_ok = address
+                .get(..self.prefix.len())
+                .is_some_and(|p| p.eq_ignore_ascii_case(&self.prefix));
+            let suffix_ok = address
+                .len()
+                .checked_sub(self.suffix.len())
+                .and_then(|start| address.get(start..))
+                .is_some_and(|s| s.eq_ignore_ascii_case(&self.suffix));
+            prefix_ok && suffix_ok
+        } else {
+            address.starts_with(&self.prefix) && address.ends_with(&self.suffix)
+        }
+    }
+
+    /// Mean number of keys to try before a match, treating address characters
+    /// as uniformly distributed (the leading character of real addresses isn't
+    /// quite, so this is an estimate).
+    fn expected_attempts(&self) -> f64 {
+        self.prefix
+            .chars()
+            .chain(self.suffix.chars())
+            .map(|c| 58.0 / self.accepted_variants(c) as f64)
+            .product()
+    }
+
+    fn accepted_variants(&self, c: char) -> usize {
+        if self.case_insensitive {
+            BASE58_ALPHABET
+                .chars()
+                .filter(|a| a.eq_ignore_ascii_case(&c))
+                .count()
+        } else {
+            usize::from(BASE58_ALPHABET.contains(c))
+        }
+    }
+}
+
+struct VanityJob {
+    threads: usize,
+    expected_attempts: f64,
+    max_attempts: u64,
+    started: Instant,
+    attempts: AtomicU64,
+    stop: AtomicBool,
+    outcome: Mutex<Option<VanityOutcome>>,
+}
+
+struct VanityOutcome {
+    status: VanityJobStatus,
+    finished: Instant,
+    keypair: Option<Keypair>,
+}
+
+impl VanityJob {
+    fn finish(&self, status: VanityJobStatus, keypair: Option<Keypair>) {
+        let mut outcome = self.outcome.lock().unwrap();
+        if outcome.is_none() {
+            *outcome = Some(VanityOutcome {
+                status,
+                finished: Instant::now(),
+                keypair,
+            });
+        }
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    fn is_running(&self) -> bool {
+        self.outcome.lock().unwrap().is_none()
+    }
+}
+
+/// Registry of vanity grinding jobs. Each job runs on its own pool of OS
+/// threads so grinding never blocks the async runtime.
+#[derive(Default)]
+pub struct VanityJobs {
+    jobs: Mutex<HashMap<String, Arc<VanityJob>>>,
+}
+
+/// Grinding threads allowed across all running jobs together: the machine's
+/// cores less `RESERVED_CORES`, at least one and at most `MAX_THREADS_PER_JOB`.
+fn grinding_thread_budget() -> usize {
+    let cores = thread::available_parallelism().map_or(1, usize::from);
+    cores.saturating_sub(RESERVED_CORES).clamp(1, MAX_THREADS_PER_JOB)
+}
+
+fn grind(job: Arc<VanityJob>, pattern: Arc<VanityPattern>) {
+    let mut pending = 0;
+    while !job.stop.load(Ordering::Relaxed) {
+        let keypair = Keypair::new();
+        pending += 1;
+        if pattern.matches(&keypair.pubkey().to_string()) {
+            job.attempts.fetch_add(pending, Ordering::Relaxed);
+            job.finish(VanityJobStatus::Found, Some(keypair));
+            return;
+        }
+        if pending == ATTEMPT_BATCH {
+            let total = job.attempts.fetch_add(pending, Ordering::Relaxed) + pending;
+            pending = 0;
+            if total >= job.max_attempts {
+                job.finish(VanityJobStatus::Exhausted, None);
+                return;
+            }
+        }
+    }
+    job.attempts.fetch_add(pending, Ordering::Relaxed);
+}
+
+pub async fn start_vanity(
+    State(registry): State<Arc<VanityJobs>>,
+    ApiJson(payload): ApiJson<StartVanityRequest>,
+) -> Result<Json<SuccessResponse<StartVanityResponse>>, ApiError> {
+    let pattern = VanityPattern {
+        prefix: payload.prefix.unwrap_or_default(),
+        suffix: payload.suffix.unwrap_or_default(),
+        case_insensitive: payload.case_insensitive,
+    };
+
+    if pattern.prefix.is_empty() && pattern.suffix.is_empty() {
+        return Err(ApiError::new(ErrorCode::MissingField, "Provide a prefix and/or suffix").at("prefix"));
+    }
+    if pattern.prefix.len() + pattern.suffix.len() > MAX_PATTERN_LEN {
+        return Err(ApiError::invalid_request(format!(
+            "Prefix and suffix may be at most {} characters combined",
+            MAX_PATTERN_LEN
+        )));
+    }
+    if let Some(c) = pattern
+        .prefix
+        .chars()
+        .chain(pattern.suffix.chars())
+        .find(|c| pattern.accepted_variants(*c) == 0)
+    {
+        let field = if pattern.prefix.contains(c) { "prefix" } else { "suffix" };
+        return Err(ApiError::new(ErrorCode::InvalidEncoding, format!("'{}' can never appear in a base58 address", c)).at(field));
+    }
+
+    let max_attempts = payload.max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS);
+    if max_attempts == 0 || max_attempts > MAX_ATTEMPTS_CAP {
+        return Err(ApiError::invalid_request(format!("max_attempts must be between 1 and {}", MAX_ATTEMPTS_CAP)).at("max_attempts"));
+    }
+
+    let thread_budget = grinding_thread_budget();
+    let job_id = hex::encode(rand::random::<[u8; 16]>());
+
+    let job = {
+        let mut jobs = registry.jobs.lock().unwrap();
+        jobs.retain(|_, job| {
+            job.outcome
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_none_or(|outcome| outcome.finished.elapsed() < FINISHED_JOB_TTL)
+        });
+        let running: Vec<_> = jobs.values().filter(|job| job.is_running()).collect();
+        if running.len() >= MAX_RUNNING_JOBS {
+            return Err(ApiError::new(
+                ErrorCode::TooManyRequests,
+                format!("At most {} vanity jobs may run at once", MAX_RUNNING_JOBS),
+            ));
+        }
+        let free_threads = thread_budget.saturating_sub(running.iter().map(|job| job.threads).sum());
+        if free_threads == 0 {
+            return Err(ApiError::new(
+                ErrorCode::TooManyRequests,
+                format!("No grinding threads are free; running vanity jobs use all {}", thread_budget),
+            ));
+        }
+
+        let job = Arc::new(VanityJob {
+            threads: payload.threads.unwrap_or(free_threads).clamp(1, free_threads),
+            expected_attempts: pattern.expected_attempts(),
+            max_attempts,
+            started: Instant::now(),
+            attempts: AtomicU64::new(0),
+            stop: AtomicBool::new(false),
+            outcome: Mutex::new(None),
+        });
+        jobs.insert(job_id.clone(), job.clone());
+        job
+    };
+    let threads = job.threads;
+
+    let pattern = Arc::new(pattern);
+    for _ in 0..threads {
+        let job = job.clone();
+        let pattern = pattern.clone();
+        thread::spawn(move || grind(job, pattern));
+    }
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: StartVanityResponse {
+            job_id,
+            threads,
+            max_attempts,
+            expected_attempts: job.expected_attempts,
+        },
+    }))
+}
+
+fn job_response(job_id: String, job: &VanityJob) -> VanityJobResponse {
+    let attempts = job.attempts.load(Ordering::Relaxed);
+    let outcome = job.outcome.lock().unwrap();
+    let elapsed = outcome
+        .as_ref()
+        .map_or_else(|| job.started.elapsed(), |o| o.finished.duration_since(job.started))
+        .as_secs_f64();
+    let rate = if elapsed > 0.0 { attempts as f64 / elapsed } else { 0.0 };
+    let status = outcome.as_ref().map_or(VanityJobStatus::Running, |o| o.status);
+
+    let estimated_seconds_remaining = (status == VanityJobStatus::Running && rate > 0.0)
+        .then(|| (job.expected_attempts - attempts as f64).max(0.0) / rate);
+    let keypair = outcome.as_ref().and_then(|o| o.keypair.as_ref());
+
+    VanityJobResponse {
+        job_id,
+        status,
+        attempts,
+        attempts_per_second: rate,
+        elapsed_seconds: elapsed,
+        estimated_seconds_remaining,
+        expected_attempts: job.expected_attempts,
+        pubkey: keypair.map(|k| k.pubkey().to_string()),
+        secret: keypair.map(|k| encode_base58(&k.to_bytes())),
+    }
+}
+
+fn find_job(registry: &VanityJobs, job_id: &str) -> Result<Arc<VanityJob>, ApiError> {
+    registry
+        .jobs
+        .lock()
+        .unwrap()
+        .get(job_id)
+        .cloned()
+        .ok_or_else(|| ApiError::new(ErrorCode::NotFound, "Vanity job not found"))
+}
+
+pub async fn vanity_status(
+    State(registry): State<Arc<VanityJobs>>,
+    Path(job_id): Path<String>,
+) -> Result<Json<SuccessResponse<VanityJobResponse>>, ApiError> {
+    let job = find_job(&registry, &job_id)?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: job_response(job_id, &job),
+    }))
+}
+
+pub async fn cancel_vanity(
+    State(registry): State<Arc<VanityJobs>>,
+    Path(job_id): Path<String>,
+) -> Result<Json<SuccessResponse<VanityJobResponse>>, ApiError> {
+    let job = find_job(&registry, &job_id)?;
+    job.finish(VanityJobStatus::Cancelled, None);
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: job_response(job_id, &job),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pattern no grinding thread will hit while a test runs.
+    const NEVER: &str = "zzzzzzzz";
+
+    fn pattern(prefix: &str, suffix: &str, case_insensitive: bool) -> VanityPattern {
+        VanityPattern {
+            prefix: prefix.to_string(),
+            suffix: suffix.to_string(),
+            case_insensitive,
+        }
+    }
+
+    fn request(prefix: &str) -> StartVanityRequest {
+        StartVanityRequest {
+            prefix: Some(prefix.to_string()),
+            suffix: None,
+            case_insensitive: false,
+            threads: Some(1),
+            max_attempts: None,
+        }
+    }
+
+    async fn start(registry: &Arc<VanityJobs>, payload: StartVanityRequest) -> Result<StartVanityResponse, ApiError> {
+        start_vanity(State(registry.clone()), ApiJson(payload)).await.map(|Json(r)| r.data)
+    }
+
+    async fn status(registry: &Arc<VanityJobs>, job_id: &str) -> VanityJobResponse {
+        vanity_status(State(registry.clone()), Path(job_id.to_string())).await.map(|Json(r)| r.data).unwrap()
+    }
+
+    async fn cancel(registry: &Arc<VanityJobs>, job_id: &str) -> VanityJobResponse {
+        cancel_vanity(State(registry.clone()), Path(job_id.to_string())).await.map(|Json(r)| r.data).unwrap()
+    }
+
+    async fn wait_until_stopped(registry: &Arc<VanityJobs>, job_id: &str) -> VanityJobResponse {
+        for _ in 0..1000 {
+            let job = status(registry, job_id).await;
+            if job.status != VanityJobStatus::Running {
+                return job;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("vanity job {} still running", job_id);
+    }
+
+    #[test]
+    fn patterns_match_prefix_and_suffix() {
+        let address = "SoLxyz9";
+        assert!(pattern("SoL", "", false).matches(address));
+        assert!(pattern("", "z9", false).matches(address));
+        assert!(pattern("SoL", "yz9", false).matches(address));
+        assert!(!pattern("Sol", "", false).matches(address));
+        assert!(!pattern("SoL", "Z9", false).matches(address));
+
+        assert!(pattern("sol", "YZ9", true).matches(address));
+        assert!(!pattern("sox", "", true).matches(address));
+        assert!(!pattern("SoLxyz9a", "", true).matches(address));
+        assert!(!pattern("", "aSoLxyz9", true).matches(address));
+    }
+
+    #[test]
+    fn expected_attempts_counts_accepted_variants() {
+        assert_eq!(pattern("A", "", false).expected_attempts(), 58.0);
+        assert_eq!(pattern("Ab", "c", false).expected_attempts(), 58.0f64.powi(3));
+        // 'a' and 'A' both count, but base58 has no 'l' and '1' has no case.
+        assert_eq!(pattern("a", "", true).expected_attempts(), 29.0);
+        assert_eq!(pattern("L1", "", true).expected_attempts(), 58.0 * 58.0);
+    }
+
+    #[tokio::test]
+    async fn finds_a_matching_keypair() {
+        let registry = Arc::default();
+        let started = start(&registry, request("A")).await.unwrap();
+        assert_eq!(started.threads, 1);
+        assert_eq!(started.max_attempts, DEFAULT_MAX_ATTEMPTS);
+        assert_eq!(started.expected_attempts, 58.0);
+
+        let job = wait_until_stopped(&registry, &started.job_id).await;
+        assert!(job.status == VanityJobStatus::Found);
+        assert!(job.attempts >= 1);
+        assert_eq!(job.estimated_seconds_remaining, None);
+        let pubkey = job.pubkey.unwrap();
+        assert!(pubkey.starts_with('A'));
+        let keypair = Keypair::from_bytes(&bs58::decode(job.secret.unwrap()).into_vec().unwrap()).unwrap();
+        assert_eq!(keypair.pubkey().to_string(), pubkey);
+    }
+
+    #[tokio::test]
+    async fn stops_at_max_attempts() {
+        let registry = Arc::default();
+        let payload = StartVanityRequest {
+            max_attempts: Some(1),
+            ..request(NEVER)
+        };
+        let started = start(&registry, payload).await.unwrap();
+
+        let job = wait_until_stopped(&registry, &started.job_id).await;
+        assert!(job.status == VanityJobStatus::Exhausted);
+        assert_eq!(job.attempts, ATTEMPT_BATCH);
+        assert!(job.pubkey.is_none() && job.secret.is_none());
+
+        for max_attempts in [0, MAX_ATTEMPTS_CAP + 1] {
+            let payload = StartVanityRequest {
+                max_attempts: Some(max_attempts),
+                ..request(NEVER)
+            };
+            let err = start(&registry, payload).await.err().unwrap();
+            assert_eq!(err.field.as_deref(), Some("max_attempts"));
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelled_jobs_stop_and_free_their_threads() {
+        let registry = Arc::default();
+        let started = start(&registry, request(NEVER)).await.unwrap();
+        assert!(status(&registry, &started.job_id).await.status == VanityJobStatus::Running);
+
+        let cancelled = cancel(&registry, &started.job_id).await;
+        assert!(cancelled.status == VanityJobStatus::Cancelled);
+        let attempts = status(&registry, &started.job_id).await.attempts;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let job = status(&registry, &started.job_id).await;
+        assert!(job.status == VanityJobStatus::Cancelled);
+        assert!(job.attempts - attempts <= ATTEMPT_BATCH);
+
+        let err = vanity_status(State(registry.clone()), Path("missing".to_string())).await.err().unwrap();
+        assert_eq!(err.code, ErrorCode::NotFound);
+    }
+
+    #[tokio::test]
+    async fn running_jobs_share_the_thread_budget() {
+        let registry = Arc::default();
+        let budget = grinding_thread_budget();
+        let mut job_ids = Vec::new();
+        let mut threads = 0;
+        let refused = loop {
+            let payload = StartVanityRequest {
+                threads: Some(MAX_THREADS_PER_JOB),
+                ..request(NEVER)
+            };
+            match start(&registry, payload).await {
+                Ok(started) => {
+                    threads += started.threads;
+                    job_ids.push(started.job_id);
+                }
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(refused.code, ErrorCode::TooManyRequests);
+        assert!(threads <= budget);
+        assert!(threads == budget || job_ids.len() == MAX_RUNNING_JOBS);
+
+        for job_id in &job_ids {
+            cancel(&registry, job_id).await;
+        }
+        let started = start(&registry, request(NEVER)).await.unwrap();
+        cancel(&registry, &started.job_id).await;
+    }
+}
@@ -0,0 +1,162 @@
+use axum::{Json, extract::{Path, State}};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use crate::error::{ApiError, ApiJson};
+use crate::handlers::SuccessResponse;
+use crate::utils::{validate_private_key, SecretFormat};
+use crate::vault::{KeyInfo, KeyVault};
+use crate::openapi::ApiSchema;
+
+/// Creates a vault key. Without `secret` a new keypair is generated; with it
+/// the given key (in any format `validate_private_key` accepts) is imported.
+#[derive(Deserialize, ApiSchema)]
+pub struct CreateVaultKeyRequest {
+    pub label: Option<String>,
+    pub secret: Option<String>,
+    pub passphrase: Option<String>,
+    pub derivation_path: Option<String>,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct CreateVaultKeyResponse {
+    #[serde(flatten)]
+    pub key: KeyInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imported_format: Option<SecretFormat>,
+}
+
+/// A signing key resolved from either a request `secret` or a vault `key_id`.
+pub struct SigningKey {
+    pub bytes: [u8; 64],
+    pub secret_format: Option<SecretFormat>,
+    pub key_id: Option<String>,
+}
+
+/// Resolves exactly one of `secret` or `key_id` to a keypair. Shared by every
+/// endpoint that signs, so they all accept vault keys the same way.
+pub fn resolve_signing_key(
+    vault: &KeyVault,
+    secret: Option<&str>,
+    key_id: Option<&str>,
+    passphrase: Option<&str>,
+    derivation_path: Option<&str>,
+) -> Result<SigningKey, ApiError> {
+    match (secret.filter(|s| !s.is_empty()), key_id.filter(|k| !k.is_empty())) {
+        (Some(_), Some(_)) => Err(ApiError::invalid_request("Provide either secret or key_id, not both").at("key_id")),
+        (None, None) => Err(ApiError::missing_fields().at("secret")),
+        (Some(secret), None) => {
+            let decoded = validate_private_key(secret, passphrase, derivation_path).map_err(|e| e.at("secret"))?;
+            Ok(SigningKey {
+                bytes: decoded.bytes,
+                secret_format: Some(decoded.format),
+                key_id: None,
+            })
+        }
+        (None, Some(key_id)) => {
+            let bytes = vault
+                .signing_keypair(key_id)
+                .map_err(|e| ApiError::from(e).at("key_id"))?;
+            Ok(SigningKey {
+                bytes,
+                secret_format: None,
+                key_id: Some(key_id.to_string()),
+            })
+        }
+    }
+}
+
+pub async fn create_vault_key(
+    State(vault): State<Arc<KeyVault>>,
+    ApiJson(payload): ApiJson<CreateVaultKeyRequest>,
+) -> Result<Json<SuccessResponse<CreateVaultKeyResponse>>, ApiError> {
+    let imported = match &payload.secret {
+        Some(secret) => Some(
+            validate_private_key(secret, payload.passphrase.as_deref(), payload.derivation_path.as_deref())
+                .map_err(|e| e.at("secret"))?,
+        ),
+        None => None,
+    };
+
+    let key = vault
+        .create(payload.label, imported.as_ref().map(|d| d.bytes))
+        .map_err(ApiError::from)?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: CreateVaultKeyResponse {
+            key,
+            imported_format: imported.map(|d| d.format),
+        },
+    }))
+}
+
+pub async fn list_vault_keys(
+    State(vault): State<Arc<KeyVault>>,
+) -> Result<Json<SuccessResponse<Vec<KeyInfo>>>, ApiError> {
+    let keys = vault.list().map_err(ApiError::from)?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: keys,
+    }))
+}
+
+pub async fn get_vault_key(
+    State(vault): State<Arc<KeyVault>>,
+    Path(key_id): Path<String>,
+) -> Result<Json<SuccessResponse<KeyInfo>>, ApiError> {
+    let key = vault.get(&key_id).map_err(ApiError::from)?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: key,
+    }))
+}
+
+pub async fn rotate_vault_key(
+    State(vault): State<Arc<KeyVault>>,
+    Path(key_id): Path<String>,
+) -> Result<Json<SuccessResponse<KeyInfo>>, ApiError> {
+    let key = vault.rotate(&key_id).map_err(ApiError::from)?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: key,
+    }))
+}
+
+pub async fn disable_vault_key(
+    State(vault): State<Arc<KeyVault>>,
+    Path(key_id): Path<String>,
+) -> Result<Json<SuccessResponse<KeyInfo>>, ApiError> {
+    let key = vault.set_disabled(&key_id, true).map_err(ApiError::from)?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: key,
+    }))
+}
+
+pub async fn enable_vault_key(
+    State(vault): State<Arc<KeyVault>>,
+    Path(key_id): Path<String>,
+) -> Result<Json<SuccessResponse<KeyInfo>>, ApiError> {
+    let key = vault.set_disabled(&key_id, false).map_err(ApiError::from)?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: key,
+    }))
+}
+
+pub async fn delete_vault_key(
+    State(vault): State<Arc<KeyVault>>,
+    Path(key_id): Path<String>,
+) -> Result<Json<SuccessResponse<KeyInfo>>, ApiError> {
+    let key = vault.delete(&key_id).map_err(ApiError::from)?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        data: key,
+    }))
+}
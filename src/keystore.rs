@@ -0,0 +1,244 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::{keypair_from_seed, Signer};
+use tokio::sync::Semaphore;
+use crate::utils::{decode_base64, encode_base64};
+use crate::openapi::ApiSchema;
+
+pub const KEYSTORE_VERSION: u32 = 1;
+pub const KDF_NAME: &str = "argon2id";
+pub const CIPHER_NAME: &str = "xchacha20poly1305";
+
+/// Argon2id cost used for new keystores (OWASP's minimum recommendation).
+const DEFAULT_MEMORY_KIB: u32 = 19 * 1024;
+const DEFAULT_ITERATIONS: u32 = 2;
+const DEFAULT_PARALLELISM: u32 = 1;
+
+/// Ceilings for parameters read back from an envelope, kept close to the
+/// defaults so a crafted keystore can't make one request much dearer than
+/// decrypting one of ours.
+const MAX_MEMORY_KIB: u32 = 64 * 1024;
+const MAX_ITERATIONS: u32 = 4;
+const MAX_PARALLELISM: u32 = 4;
+
+/// Key derivations running at once; further requests wait for a slot, which
+/// bounds the memory Argon2 holds to this many times `MAX_MEMORY_KIB`.
+const MAX_CONCURRENT_KDF: usize = 4;
+static KDF_SLOTS: Semaphore = Semaphore::const_new(MAX_CONCURRENT_KDF);
+
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+/// Password-encrypted secret key envelope. The pubkey is stored in clear and
+/// bound to the ciphertext as associated data.
+#[derive(Serialize, Deserialize, Clone, ApiSchema)]
+pub struct Keystore {
+    pub version: u32,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
+    pub pubkey: String,
+    pub kdf: KdfParams,
+    pub cipher: CipherParams,
+    #[schema(format = "base64")]
+    pub ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, ApiSchema)]
+pub struct KdfParams {
+    #[schema(example = "argon2id")]
+    pub name: String,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    #[schema(format = "base64")]
+    pub salt: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, ApiSchema)]
+pub struct CipherParams {
+    #[schema(example = "xchacha20poly1305")]
+    pub name: String,
+    #[schema(format = "base64")]
+    pub nonce: String,
+}
+
+pub fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+/// Derives a 256-bit key from a password with Argon2id. The work runs on the
+/// blocking pool, at most `MAX_CONCURRENT_KDF` derivations at a time, so it
+/// never stalls the async runtime.
+pub async fn derive_key(
+    password: &[u8],
+    salt: &[u8],
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+) -> Result<[u8; 32], String> {
+    let params = Params::new(memory_kib, iterations, parallelism, Some(32))
+        .map_err(|e| format!("Invalid KDF parameters: {}", e))?;
+    let (password, salt) = (password.to_vec(), salt.to_vec());
+    let slot = KDF_SLOTS.acquire().await.map_err(|e| e.to_string())?;
+    // The slot moves into the task, so it is held until Argon2 finishes even
+    // if the request is dropped first.
+    tokio::task::spawn_blocking(move || {
+        let _slot = slot;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(&password, &salt, &mut key)
+            .map_err(|e| format!("Key derivation failed: {}", e))?;
+        Ok(key)
+    })
+    .await
+    .map_err(|e| format!("Key derivation failed: {}", e))?
+}
+
+/// Encrypts `plaintext` under a 256-bit key, returning `(nonce, ciphertext)`.
+pub fn seal(key: &[u8; 32], plaintext: &[u8], aad: &[u8]) -> Result<([u8; 24], Vec<u8>), String> {
+    let nonce = random_bytes::<24>();
+    let ciphertext = XChaCha20Poly1305::new(&Key::from(*key))
+        .encrypt(&XNonce::from(nonce), Payload { msg: plaintext, aad })
+        .map_err(|_| "Encryption failed".to_string())?;
+    Ok((nonce, ciphertext))
+}
+
+pub fn open(key: &[u8; 32], nonce: &[u8], ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>, String> {
+    let nonce: [u8; 24] = nonce
+        .try_into()
+        .map_err(|_| "Nonce must be 24 bytes".to_string())?;
+    XChaCha20Poly1305::new(&Key::from(*key))
+        .decrypt(&XNonce::from(nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_| "Decryption failed: wrong password or corrupted data".to_string())
+}
+
+fn associated_data(version: u32, pubkey: &str) -> Vec<u8> {
+    format!("solana-keystore:v{}:{}", version, pubkey).into_bytes()
+}
+
+/// Encrypts a 64-byte keypair (seed followed by pubkey) with `password`.
+pub async fn encrypt_keypair(keypair: &[u8; 64], password: &str) -> Result<Keystore, String> {
+    if password.len() < MIN_PASSWORD_LEN {
+        return Err(format!("Password must be at least {} characters", MIN_PASSWORD_LEN));
+    }
+
+    let pubkey = bs58::encode(&keypair[32..]).into_string();
+    let salt = random_bytes::<16>();
+    let key = derive_key(
+        password.as_bytes(),
+        &salt,
+        DEFAULT_MEMORY_KIB,
+        DEFAULT_ITERATIONS,
+        DEFAULT_PARALLELISM,
+    )
+    .await?;
+    let (nonce, ciphertext) = seal(&key, keypair, &associated_data(KEYSTORE_VERSION, &pubkey))?;
+
+    Ok(Keystore {
+        version: KEYSTORE_VERSION,
+        pubkey,
+        kdf: KdfParams {
+            name: KDF_NAME.to_string(),
+            memory_kib: DEFAULT_MEMORY_KIB,
+            iterations: DEFAULT_ITERATIONS,
+            parallelism: DEFAULT_PARALLELISM,
+            salt: encode_base64(&salt),
+        },
+        cipher: CipherParams {
+            name: CIPHER_NAME.to_string(),
+            nonce: encode_base64(&nonce),
+        },
+        ciphertext: encode_base64(&ciphertext),
+    })
+}
+
+/// Decrypts a keystore and checks the recovered key matches its pubkey.
+pub async fn decrypt_keypair(keystore: &Keystore, password: &str) -> Result<[u8; 64], String> {
+    if keystore.version != KEYSTORE_VERSION {
+        return Err(format!("Unsupported keystore version {}", keystore.version));
+    }
+    if keystore.kdf.name != KDF_NAME {
+        return Err(format!("Unsupported KDF '{}'", keystore.kdf.name));
+    }
+    if keystore.cipher.name != CIPHER_NAME {
+        return Err(format!("Unsupported cipher '{}'", keystore.cipher.name));
+    }
+    let kdf = &keystore.kdf;
+    if kdf.memory_kib > MAX_MEMORY_KIB || kdf.iterations > MAX_ITERATIONS || kdf.parallelism > MAX_PARALLELISM {
+        return Err("KDF parameters exceed server limits".to_string());
+    }
+
+    let salt = decode_base64(&kdf.salt).map_err(|e| format!("Invalid salt: {}", e))?;
+    let nonce = decode_base64(&keystore.cipher.nonce).map_err(|e| format!("Invalid nonce: {}", e))?;
+    let ciphertext = decode_base64(&keystore.ciphertext).map_err(|e| format!("Invalid ciphertext: {}", e))?;
+
+    let key = derive_key(password.as_bytes(), &salt, kdf.memory_kib, kdf.iterations, kdf.parallelism).await?;
+    let plaintext = open(&key, &nonce, &ciphertext, &associated_data(keystore.version, &keystore.pubkey))?;
+
+    let keypair: [u8; 64] = plaintext
+        .as_slice()
+        .try_into()
+        .map_err(|_| "Decrypted key has the wrong length".to_string())?;
+    let derived = keypair_from_seed(&keypair[..32]).map_err(|e| format!("Invalid key: {}", e))?;
+    if derived.pubkey().as_ref() != &keypair[32..] || derived.pubkey().to_string() != keystore.pubkey {
+        return Err("Decrypted key does not match the keystore pubkey".to_string());
+    }
+    Ok(keypair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWORD: &str = "correct horse";
+
+    fn keypair() -> [u8; 64] {
+        keypair_from_seed(&[3u8; 32]).unwrap().to_bytes()
+    }
+
+    #[tokio::test]
+    async fn round_trips_a_keypair() {
+        let keystore = encrypt_keypair(&keypair(), PASSWORD).await.unwrap();
+        assert_eq!(keystore.pubkey, bs58::encode(&keypair()[32..]).into_string());
+        assert_eq!(keystore.kdf.memory_kib, DEFAULT_MEMORY_KIB);
+        assert_eq!(decrypt_keypair(&keystore, PASSWORD).await.unwrap(), keypair());
+
+        // Through JSON, as clients store it.
+        let json = serde_json::to_string(&keystore).unwrap();
+        let parsed: Keystore = serde_json::from_str(&json).unwrap();
+        assert_eq!(decrypt_keypair(&parsed, PASSWORD).await.unwrap(), keypair());
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_password_and_tampering() {
+        let keystore = encrypt_keypair(&keypair(), PASSWORD).await.unwrap();
+        assert!(decrypt_keypair(&keystore, "wrong password").await.is_err());
+
+        // The pubkey is associated data, so swapping it breaks decryption.
+        let mut swapped = keystore.clone();
+        swapped.pubkey = bs58::encode([1u8; 32]).into_string();
+        assert!(decrypt_keypair(&swapped, PASSWORD).await.is_err());
+
+        let mut flipped = keystore.clone();
+        let mut ciphertext = decode_base64(&flipped.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        flipped.ciphertext = encode_base64(&ciphertext);
+        assert!(decrypt_keypair(&flipped, PASSWORD).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn refuses_short_passwords_and_costly_kdf_parameters() {
+        assert!(encrypt_keypair(&keypair(), "short").await.is_err());
+
+        let keystore = encrypt_keypair(&keypair(), PASSWORD).await.unwrap();
+        let mut costly = keystore.clone();
+        costly.kdf.memory_kib = MAX_MEMORY_KIB + 1;
+        assert_eq!(decrypt_keypair(&costly, PASSWORD).await.unwrap_err(), "KDF parameters exceed server limits");
+        let mut costly = keystore;
+        costly.kdf.iterations = MAX_ITERATIONS + 1;
+        assert_eq!(decrypt_keypair(&costly, PASSWORD).await.unwrap_err(), "KDF parameters exceed server limits");
+    }
+}
@@ -1,15 +1,36 @@
+mod audit;
+mod auth;
+mod config;
+mod cors;
+mod error;
 mod routes;
 mod handlers;
+mod openapi;
+mod ratelimit;
+mod decoder;
+mod keystore;
+mod metrics;
+mod offchain;
+mod programs;
+mod siws;
+mod state;
+mod telemetry;
+mod tls;
 mod utils;
+mod vault;
 
 use axum::{
+    extract::DefaultBodyLimit,
+    middleware,
     routing::get,
     response::Json,
 };
-use tower_http::cors::{CorsLayer, Any};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use std::time::Duration;
+use tower_http::timeout::TimeoutLayer;
+use crate::config::{Config, ConfigError};
+use crate::openapi::ApiSchema;
 
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, ApiSchema)]
 struct HealthResponse {
     success: bool,
     data: String,
@@ -17,30 +38,216 @@ struct HealthResponse {
 
 #[tokio::main]
 async fn main() {
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
-    // Configure CORS
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any)
-        .allow_credentials(true);
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(ConfigError::Help) => {
+            print!("{}", config::USAGE);
+            return;
+        }
+        Err(ConfigError::Invalid(errors)) => {
+            for error in errors {
+                eprintln!("config error: {}", error);
+            }
+            std::process::exit(2);
+        }
+    };
+
+    if let Some(path) = &config.verify_audit_log {
+        match audit::verify(path) {
+            Ok(summary) => {
+                println!(
+                    "{}: {} records, hash chain intact; head hash {}",
+                    path.display(),
+                    summary.records,
+                    summary.head_hash
+                );
+                return;
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(new_key) = &config.create_api_key {
+        let created = auth::ApiKeyStore::open(&config.auth.keys_path)
+            .and_then(|store| store.create(new_key.name.clone(), new_key.scopes.clone()).map_err(|e| e.to_string()));
+        match created {
+            Ok((key, info)) => {
+                let scopes: Vec<&str> = info.scopes.iter().map(|scope| scope.name()).collect();
+                eprintln!(
+                    "Created API key {} ({}) with scopes {} in {}; it is shown only once:",
+                    info.id,
+                    info.name,
+                    scopes.join(","),
+                    config.auth.keys_path.display()
+                );
+                println!("{}", key);
+                return;
+            }
+            Err(e) => {
+                eprintln!("Failed to create API key: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Initialize tracing; the provider is held so spans keep being exported.
+    let _tracer_provider = match telemetry::init(&config.log, &config.telemetry) {
+        Ok(provider) => provider,
+        Err(e) => {
+            eprintln!("Failed to initialize tracing: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(endpoint) = &config.telemetry.otlp_endpoint {
+        tracing::info!("Exporting traces to {}", endpoint);
+    }
+
+    let vault = if config.features.vault {
+        vault::KeyVault::from_config(&config.vault).unwrap_or_else(|e| {
+            tracing::error!("Failed to open key vault: {}", e);
+            std::process::exit(1);
+        })
+    } else {
+        vault::KeyVault::default()
+    };
+    if config.features.vault && !vault.is_enabled() {
+        tracing::info!("Key vault disabled: VAULT_MASTER_KEY is not set");
+    }
+    let api_keys = if config.auth.enabled {
+        let store = auth::ApiKeyStore::open(&config.auth.keys_path).unwrap_or_else(|e| {
+            tracing::error!("Failed to open API key file: {}", e);
+            std::process::exit(1);
+        });
+        if store.is_empty() {
+            tracing::warn!(
+                "No API keys in {}; every request except /health and the API docs will be refused until one is created with --create-api-key",
+                config.auth.keys_path.display()
+            );
+        }
+        store
+    } else {
+        tracing::warn!("API key authentication is disabled; every route is open");
+        auth::ApiKeyStore::default()
+    };
+    let api_keys = std::sync::Arc::new(api_keys);
+    let metrics = std::sync::Arc::new(metrics::Metrics::default());
+    let audit_log = if config.audit.enabled {
+        audit::AuditLog::open(&config.audit.path, config.limits.max_body_bytes).unwrap_or_else(|e| {
+            tracing::error!("Failed to open audit log: {}", e);
+            std::process::exit(1);
+        })
+    } else {
+        tracing::warn!("Audit logging is disabled");
+        audit::AuditLog::default()
+    };
+    let state = state::AppState {
+        vault: std::sync::Arc::new(vault),
+        api_keys: api_keys.clone(),
+        metrics: metrics.clone(),
+        audit: std::sync::Arc::new(audit_log),
+        ..Default::default()
+    };
+
+    let cors_policy = std::sync::Arc::new(
+        cors::CorsPolicy::from_config(&config.cors).expect("CORS config is checked by Config::validate"),
+    );
+
+    let rate_limiter = if config.rate_limit.enabled {
+        match ratelimit::RateLimiter::from_config(&config.rate_limit) {
+            Ok(limiter) => Some(std::sync::Arc::new(limiter)),
+            Err(e) => {
+                tracing::error!("Failed to load quota usage: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        tracing::warn!("Rate limiting is disabled");
+        None
+    };
 
     // Build our application with routes
-    let app = routes::app_router()
+    let mut app = routes::app_router(state, &config.features)
         .route("/health", get(health_check))
-        .layer(cors);
+        .layer(DefaultBodyLimit::max(config.limits.max_body_bytes))
+        .layer(TimeoutLayer::new(Duration::from_secs(config.limits.request_timeout_secs)));
+    if let Some(limiter) = rate_limiter {
+        limiter.clone().spawn_maintenance();
+        app = app.layer(middleware::from_fn_with_state(limiter, ratelimit::limit));
+    }
+    if config.auth.enabled {
+        app = app.layer(middleware::from_fn_with_state(api_keys, auth::authenticate));
+    }
+    app = app.layer(middleware::from_fn_with_state(cors_policy, cors::cors));
+    if config.features.metrics {
+        app = app.layer(middleware::from_fn_with_state(metrics, metrics::track));
+    }
+    if config.log.access_log {
+        app = app.layer(middleware::from_fn(telemetry::access_log));
+    }
+    let app = app.layer(middleware::from_fn(telemetry::request_context));
 
     // Run it
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await.unwrap();
-    tracing::info!("Server running on http://127.0.0.1:3000");
-    axum::serve(listener, app).await.unwrap();
+    let addr = config.listen_addr();
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("Failed to bind {}: {}", addr, e);
+            std::process::exit(1);
+        }
+    };
+    if config.tls.enabled() {
+        let tls = match tls::TlsState::load(config.tls.clone()) {
+            Ok(tls) => std::sync::Arc::new(tls),
+            Err(e) => {
+                tracing::error!("Failed to load TLS certificates: {}", e);
+                std::process::exit(1);
+            }
+        };
+        tls.clone().spawn_reloader();
+        tracing::info!("Server running on https://{} (cluster: {})", addr, config.cluster);
+        tls::serve(listener, app, tls, shutdown_signal()).await;
+    } else {
+        tracing::warn!("TLS is not configured; serving plain HTTP");
+        tracing::info!("Server running on http://{} (cluster: {})", addr, config.cluster);
+        let service = app.into_make_service_with_connect_info::<std::net::SocketAddr>();
+        if let Err(e) = axum::serve(listener, service).with_graceful_shutdown(shutdown_signal()).await {
+            tracing::error!("Server error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Completes on Ctrl-C or, on Unix, SIGTERM, starting a graceful shutdown:
+/// no new connections are accepted and open ones finish their requests.
+async fn shutdown_signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+    tracing::info!("Shutdown signal received");
 }
 
 async fn health_check() -> Json<HealthResponse> {
@@ -48,4 +255,4 @@ async fn health_check() -> Json<HealthResponse> {
         success: true,
         data: "Server is healthy".to_string(),
     })
-} 
\ No newline at end of file
+}
@@ -0,0 +1,337 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use crate::error::ErrorCode;
+
+const PREFIX: &str = "solana_api";
+
+/// Upper bounds of the latency histogram buckets, in seconds.
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Label for requests that matched no route, so unknown paths can't grow
+/// the label set.
+const UNMATCHED: &str = "unmatched";
+
+#[derive(Default)]
+struct Histogram {
+    /// Non-cumulative counts per bucket; the last slot is `+Inf`.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+/// Route labels: the method and the route template, e.g. `/vault/keys/:key_id`.
+type RouteKey = (String, String);
+
+#[derive(Default)]
+struct Registry {
+    requests: BTreeMap<(String, String, u16), u64>,
+    latency: BTreeMap<RouteKey, Histogram>,
+    in_flight: BTreeMap<RouteKey, i64>,
+    errors: BTreeMap<String, u64>,
+    lamports_requested: u64,
+    signatures: BTreeMap<&'static str, u64>,
+}
+
+/// Counters exported at `/metrics` in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    registry: Mutex<Registry>,
+}
+
+impl Metrics {
+    /// Lamports in a `/send/sol` instruction that was built.
+    pub fn record_lamports_requested(&self, lamports: u64) {
+        let mut registry = self.registry.lock().unwrap();
+        registry.lamports_requested = registry.lamports_requested.saturating_add(lamports);
+    }
+
+    /// Signatures produced by a signing endpoint; `kind` is `message`,
+    /// `offchain_message` or `transaction`.
+    pub fn record_signatures(&self, kind: &'static str, count: usize) {
+        *self.registry.lock().unwrap().signatures.entry(kind).or_default() += count as u64;
+    }
+
+    fn start(&self, route: &RouteKey) {
+        *self.registry.lock().unwrap().in_flight.entry(route.clone()).or_default() += 1;
+    }
+
+    fn finish(&self, route: &RouteKey, outcome: Option<(u16, Option<ErrorCode>)>, seconds: f64) {
+        let mut registry = self.registry.lock().unwrap();
+        *registry.in_flight.entry(route.clone()).or_default() -= 1;
+        let Some((status, code)) = outcome else {
+            return;
+        };
+        *registry.requests.entry((route.0.clone(), route.1.clone(), status)).or_default() += 1;
+        registry.latency.entry(route.clone()).or_default().observe(seconds);
+        if let Some(code) = code {
+            *registry.errors.entry(code.name()).or_default() += 1;
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let registry = self.registry.lock().unwrap();
+        let mut out = String::new();
+
+        header(&mut out, "http_requests_total", "counter", "HTTP requests by method, route and status.");
+        for ((method, route, status), count) in &registry.requests {
+            let _ = writeln!(
+                out,
+                "{}_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                PREFIX, escape(method), escape(route), status, count
+            );
+        }
+
+        header(&mut out, "http_request_duration_seconds", "histogram", "HTTP request latency by method and route.");
+        for ((method, route), histogram) in &registry.latency {
+            let labels = format!("method=\"{}\",route=\"{}\"", escape(method), escape(route));
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(&histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(out, "{}_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", PREFIX, labels, bound, cumulative);
+            }
+            let _ = writeln!(out, "{}_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", PREFIX, labels, histogram.count);
+            let _ = writeln!(out, "{}_http_request_duration_seconds_sum{{{}}} {}", PREFIX, labels, histogram.sum);
+            let _ = writeln!(out, "{}_http_request_duration_seconds_count{{{}}} {}", PREFIX, labels, histogram.count);
+        }
+
+        header(&mut out, "http_requests_in_flight", "gauge", "HTTP requests being served, by method and route.");
+        for ((method, route), count) in &registry.in_flight {
+            let _ = writeln!(
+                out,
+                "{}_http_requests_in_flight{{method=\"{}\",route=\"{}\"}} {}",
+                PREFIX, escape(method), escape(route), count
+            );
+        }
+
+        header(&mut out, "errors_total", "counter", "Error responses by error code.");
+        for (code, count) in &registry.errors {
+            let _ = writeln!(out, "{}_errors_total{{code=\"{}\"}} {}", PREFIX, code, count);
+        }
+
+        header(&mut out, "send_sol_lamports_total", "counter", "Lamports in SOL transfer instructions built by /send/sol.");
+        let _ = writeln!(out, "{}_send_sol_lamports_total {}", PREFIX, registry.lamports_requested);
+
+        header(&mut out, "signatures_total", "counter", "Signatures produced, by kind of payload signed.");
+        for (kind, count) in &registry.signatures {
+            let _ = writeln!(out, "{}_signatures_total{{kind=\"{}\"}} {}", PREFIX, kind, count);
+        }
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} {}", PREFIX, name, kind);
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Keeps the in-flight gauge right when a request is dropped before it
+/// completes, e.g. because the client went away.
+struct InFlight<'a> {
+    metrics: &'a Metrics,
+    route: RouteKey,
+    started: Instant,
+    outcome: Option<(u16, Option<ErrorCode>)>,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.metrics
+            .finish(&self.route, self.outcome.take(), self.started.elapsed().as_secs_f64());
+    }
+}
+
+/// Records each request's route, status, latency and error code. Error codes
+/// come from the `ErrorCode` extension `ApiError` puts on its responses.
+pub async fn track(State(metrics): State<Arc<Metrics>>, request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(UNMATCHED, MatchedPath::as_str)
+        .to_string();
+    let route = (request.method().to_string(), route);
+    metrics.start(&route);
+    let mut in_flight = InFlight {
+        metrics: &metrics,
+        route,
+        started: Instant::now(),
+        outcome: None,
+    };
+
+    let response = next.run(request).await;
+    in_flight.outcome = Some((response.status().as_u16(), response.extensions().get::<ErrorCode>().copied()));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ApiError;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tokio::sync::oneshot;
+    use tower::ServiceExt;
+
+    fn app(metrics: &Arc<Metrics>) -> Router {
+        Router::new()
+            .route("/vault/keys/:key_id", get(|| async { "ok" }))
+            .route("/fail", get(|| async { ApiError::invalid_request("nope") }))
+            .layer(middleware::from_fn_with_state(metrics.clone(), track))
+    }
+
+    async fn call(app: Router, path: &str) {
+        app.oneshot(Request::get(path).body(Body::empty()).unwrap()).await.unwrap();
+    }
+
+    /// Checks every line is a comment or `name{labels} value` with a numeric
+    /// value, and returns the samples.
+    fn parse(text: &str) -> Vec<(String, f64)> {
+        let mut samples = Vec::new();
+        for line in text.lines() {
+            if let Some(comment) = line.strip_prefix("# ") {
+                let mut words = comment.splitn(3, ' ');
+                assert!(matches!(words.next(), Some("HELP" | "TYPE")), "bad comment: {}", line);
+                assert!(words.next().is_some_and(|name| name.starts_with(PREFIX)), "bad comment: {}", line);
+                continue;
+            }
+            let (series, value) = line.rsplit_once(' ').unwrap_or_else(|| panic!("no value: {}", line));
+            let value = match value {
+                "+Inf" => f64::INFINITY,
+                value => value.parse().unwrap_or_else(|_| panic!("bad value: {}", line)),
+            };
+            let name_end = series.find('{').unwrap_or(series.len());
+            let name = &series[..name_end];
+            assert!(name.starts_with(PREFIX) && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_'));
+            if name_end < series.len() {
+                assert!(series.ends_with('}'), "unterminated labels: {}", line);
+                let labels = &series[name_end + 1..series.len() - 1];
+                for label in labels.split(',') {
+                    let (key, value) = label.split_once('=').unwrap_or_else(|| panic!("bad label: {}", line));
+                    assert!(key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_'));
+                    assert!(value.len() >= 2 && value.starts_with('"') && value.ends_with('"'));
+                }
+            }
+            samples.push((series.to_string(), value));
+        }
+        samples
+    }
+
+    fn sample(samples: &[(String, f64)], series: &str) -> Option<f64> {
+        samples.iter().find(|(name, _)| name == series).map(|(_, value)| *value)
+    }
+
+    #[tokio::test]
+    async fn render_is_prometheus_text() {
+        let metrics = Arc::new(Metrics::default());
+        call(app(&metrics), "/vault/keys/abc").await;
+        call(app(&metrics), "/fail").await;
+        metrics.record_lamports_requested(1_500);
+        metrics.record_signatures("message", 2);
+
+        let samples = parse(&metrics.render());
+        assert_eq!(sample(&samples, "solana_api_send_sol_lamports_total"), Some(1_500.0));
+        assert_eq!(sample(&samples, "solana_api_signatures_total{kind=\"message\"}"), Some(2.0));
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let metrics = Metrics::default();
+        let route = ("GET".to_string(), "/x".to_string());
+        for seconds in [0.001, 0.02, 0.02, 0.3, 60.0] {
+            metrics.start(&route);
+            metrics.finish(&route, Some((200, None)), seconds);
+        }
+
+        let samples = parse(&metrics.render());
+        let buckets: Vec<f64> = samples
+            .iter()
+            .filter(|(name, _)| name.starts_with("solana_api_http_request_duration_seconds_bucket"))
+            .map(|(_, value)| *value)
+            .collect();
+        assert_eq!(buckets.len(), LATENCY_BUCKETS.len() + 1);
+        assert!(buckets.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(buckets[0], 1.0);
+        assert_eq!(buckets[2], 3.0);
+
+        let labels = "{method=\"GET\",route=\"/x\"";
+        let count = sample(&samples, &format!("solana_api_http_request_duration_seconds_count{}}}", labels));
+        assert_eq!(count, Some(5.0));
+        let inf = sample(&samples, &format!("solana_api_http_request_duration_seconds_bucket{},le=\"+Inf\"}}", labels));
+        assert_eq!(inf, count);
+        assert_eq!(buckets[LATENCY_BUCKETS.len() - 1], 4.0);
+    }
+
+    #[tokio::test]
+    async fn track_labels_routes_by_template_and_counts_error_codes() {
+        let metrics = Arc::new(Metrics::default());
+        call(app(&metrics), "/vault/keys/key-one").await;
+        call(app(&metrics), "/vault/keys/key-two").await;
+        call(app(&metrics), "/no/such/path").await;
+        call(app(&metrics), "/fail").await;
+
+        let text = metrics.render();
+        assert!(!text.contains("key-one") && !text.contains("key-two") && !text.contains("/no/such/path"));
+        let samples = parse(&text);
+        let requests = |route: &str, status: u16| {
+            let labels = format!("method=\"GET\",route=\"{}\",status=\"{}\"", route, status);
+            sample(&samples, &format!("solana_api_http_requests_total{{{}}}", labels))
+        };
+        assert_eq!(requests("/vault/keys/:key_id", 200), Some(2.0));
+        assert_eq!(requests(UNMATCHED, 404), Some(1.0));
+        assert_eq!(requests("/fail", 400), Some(1.0));
+        assert_eq!(sample(&samples, "solana_api_errors_total{code=\"INVALID_REQUEST\"}"), Some(1.0));
+        assert_eq!(samples.iter().filter(|(name, _)| name.starts_with("solana_api_errors_total")).count(), 1);
+    }
+
+    #[tokio::test]
+    async fn dropped_requests_leave_the_in_flight_gauge() {
+        let metrics = Arc::new(Metrics::default());
+        let (entered, handler_entered) = oneshot::channel();
+        let entered = Arc::new(Mutex::new(Some(entered)));
+        let app = Router::new()
+            .route(
+                "/slow",
+                get(move || async move {
+                    if let Some(entered) = entered.lock().unwrap().take() {
+                        let _ = entered.send(());
+                    }
+                    std::future::pending::<()>().await
+                }),
+            )
+            .layer(middleware::from_fn_with_state(metrics.clone(), track));
+        let in_flight = || {
+            let series = "solana_api_http_requests_in_flight{method=\"GET\",route=\"/slow\"}";
+            sample(&parse(&metrics.render()), series)
+        };
+
+        let request = tokio::spawn(call(app, "/slow"));
+        handler_entered.await.unwrap();
+        assert_eq!(in_flight(), Some(1.0));
+
+        request.abort();
+        assert!(request.await.unwrap_err().is_cancelled());
+        assert_eq!(in_flight(), Some(0.0));
+        assert!(!metrics.render().contains("solana_api_http_requests_total{"));
+    }
+}
@@ -1,126 +0,0 @@
-use serde::{Deserialize, Serialize};
-use solana_sdk::pubkey::Pubkey;
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ApiResponse<T> {
-    pub success: bool,
-    pub data: T,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ErrorResponse {
-    pub success: bool,
-    pub error: String,
-}
-
-// Keypair Response
-#[derive(Debug, Serialize, Deserialize)]
-pub struct KeypairResponse {
-    pub pubkey: String,
-    pub secret: String,
-}
-
-// Token Create Request
-#[derive(Debug, Deserialize)]
-pub struct CreateTokenRequest {
-    pub mint_authority: String,
-    pub mint: String,
-    pub decimals: u8,
-}
-
-// Token Create Response
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CreateTokenResponse {
-    pub program_id: String,
-    pub accounts: Vec<AccountMeta>,
-    pub instruction_data: String,
-}
-
-// Token Mint Request
-#[derive(Debug, Deserialize)]
-pub struct MintTokenRequest {
-    pub mint: String,
-    pub destination: String,
-    pub authority: String,
-    pub amount: u64,
-}
-
-// Token Mint Response
-#[derive(Debug, Serialize, Deserialize)]
-pub struct MintTokenResponse {
-    pub program_id: String,
-    pub accounts: Vec<AccountMeta>,
-    pub instruction_data: String,
-}
-
-// Message Sign Request
-#[derive(Debug, Deserialize)]
-pub struct SignMessageRequest {
-    pub message: String,
-    pub secret: String,
-}
-
-// Message Sign Response
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SignMessageResponse {
-    pub signature: String,
-    pub public_key: String,
-    pub message: String,
-}
-
-// Message Verify Request
-#[derive(Debug, Deserialize)]
-pub struct VerifyMessageRequest {
-    pub message: String,
-    pub signature: String,
-    pub pubkey: String,
-}
-
-// Message Verify Response
-#[derive(Debug, Serialize, Deserialize)]
-pub struct VerifyMessageResponse {
-    pub valid: bool,
-    pub message: String,
-    pub pubkey: String,
-}
-
-// Send SOL Request
-#[derive(Debug, Deserialize)]
-pub struct SendSolRequest {
-    pub from: String,
-    pub to: String,
-    pub lamports: u64,
-}
-
-// Send SOL Response
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SendSolResponse {
-    pub program_id: String,
-    pub accounts: Vec<String>,
-    pub instruction_data: String,
-}
-
-// Send Token Request
-#[derive(Debug, Deserialize)]
-pub struct SendTokenRequest {
-    pub destination: String,
-    pub mint: String,
-    pub owner: String,
-    pub amount: u64,
-}
-
-// Send Token Response
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SendTokenResponse {
-    pub program_id: String,
-    pub accounts: Vec<AccountMeta>,
-    pub instruction_data: String,
-}
-
-// Account Meta for Solana instructions
-#[derive(Debug, Serialize, Deserialize)]
-pub struct AccountMeta {
-    pub pubkey: String,
-    pub is_signer: bool,
-    pub is_writable: bool,
-} 
\ No newline at end of file
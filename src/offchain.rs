@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::offchain_message::{is_printable_ascii, is_utf8, OffchainMessage as SdkOffchainMessage};
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::pubkey::Pubkey;
+use crate::openapi::ApiSchema;
+
+/// `\xffsolana offchain`; can never be the start of a valid transaction.
+pub const SIGNING_DOMAIN: &[u8] = SdkOffchainMessage::SIGNING_DOMAIN;
+pub const HEADER_VERSION: u8 = 0;
+pub const MAX_SIGNERS: usize = u8::MAX as usize;
+
+/// Text encoding of the message body. The two limited formats must fit, header
+/// included, in a transaction packet so hardware wallets can display them.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, ApiSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageFormat {
+    RestrictedAscii,
+    LimitedUtf8,
+    ExtendedUtf8,
+}
+
+impl MessageFormat {
+    fn from_byte(byte: u8) -> Result<Self, String> {
+        match byte {
+            0 => Ok(Self::RestrictedAscii),
+            1 => Ok(Self::LimitedUtf8),
+            2 => Ok(Self::ExtendedUtf8),
+            other => Err(format!("Unknown message format {}", other)),
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::RestrictedAscii => 0,
+            Self::LimitedUtf8 => 1,
+            Self::ExtendedUtf8 => 2,
+        }
+    }
+}
+
+/// An off-chain message as defined by the Solana off-chain message signing
+/// proposal: signing domain, header version, application domain, format,
+/// signer list and a length-prefixed body. Signers sign the serialized bytes.
+#[derive(Clone, Debug)]
+pub struct OffchainMessage {
+    pub application_domain: [u8; 32],
+    pub format: MessageFormat,
+    pub signers: Vec<Pubkey>,
+    pub message: Vec<u8>,
+}
+
+fn header_len(signers: usize) -> usize {
+    SIGNING_DOMAIN.len() + 1 + 32 + 1 + 1 + 32 * signers + 2
+}
+
+impl OffchainMessage {
+    /// Builds a message, choosing the most restrictive format the body fits
+    /// when `format` is `None`.
+    pub fn new(
+        application_domain: [u8; 32],
+        format: Option<MessageFormat>,
+        signers: Vec<Pubkey>,
+        message: Vec<u8>,
+    ) -> Result<Self, String> {
+        let fits_packet = header_len(signers.len()) + message.len() <= PACKET_DATA_SIZE;
+        let format = match format {
+            Some(format) => format,
+            None if fits_packet && is_printable_ascii(&message) => MessageFormat::RestrictedAscii,
+            None if fits_packet => MessageFormat::LimitedUtf8,
+            None => MessageFormat::ExtendedUtf8,
+        };
+        let offchain = Self {
+            application_domain,
+            format,
+            signers,
+            message,
+        };
+        offchain.validate()?;
+        Ok(offchain)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.message.is_empty() {
+            return Err("Message must not be empty".to_string());
+        }
+        if self.signers.is_empty() || self.signers.len() > MAX_SIGNERS {
+            return Err(format!("Message must have between 1 and {} signers", MAX_SIGNERS));
+        }
+        let total_len = header_len(self.signers.len()) + self.message.len();
+        match self.format {
+            MessageFormat::RestrictedAscii | MessageFormat::LimitedUtf8 if total_len > PACKET_DATA_SIZE => {
+                return Err(format!(
+                    "Messages in {:?} format are limited to {} bytes including the header",
+                    self.format, PACKET_DATA_SIZE
+                ));
+            }
+            MessageFormat::ExtendedUtf8 if self.message.len() > u16::MAX as usize => {
+                return Err(format!("Message is longer than {} bytes", u16::MAX));
+            }
+            _ => {}
+        }
+        match self.format {
+            MessageFormat::RestrictedAscii if !is_printable_ascii(&self.message) => {
+                Err("Restricted ASCII messages may only contain printable ASCII characters".to_string())
+            }
+            MessageFormat::LimitedUtf8 | MessageFormat::ExtendedUtf8 if !is_utf8(&self.message) => {
+                Err("Message is not valid UTF-8".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The bytes each signer signs.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(header_len(self.signers.len()) + self.message.len());
+        data.extend_from_slice(SIGNING_DOMAIN);
+        data.push(HEADER_VERSION);
+        data.extend_from_slice(&self.application_domain);
+        data.push(self.format.to_byte());
+        data.push(self.signers.len() as u8);
+        for signer in &self.signers {
+            data.extend_from_slice(signer.as_ref());
+        }
+        data.extend_from_slice(&(self.message.len() as u16).to_le_bytes());
+        data.extend_from_slice(&self.message);
+        data
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { data };
+        if reader.take(SIGNING_DOMAIN.len())? != SIGNING_DOMAIN {
+            return Err("Missing off-chain message signing domain".to_string());
+        }
+        let version = reader.take(1)?[0];
+        if version != HEADER_VERSION {
+            return Err(format!("Unsupported off-chain message version {}", version));
+        }
+        let application_domain: [u8; 32] = reader.take(32)?.try_into().expect("32 bytes");
+        let format = MessageFormat::from_byte(reader.take(1)?[0])?;
+        let signer_count = usize::from(reader.take(1)?[0]);
+        let signers = (0..signer_count)
+            .map(|_| reader.take(32).map(|bytes| Pubkey::try_from(bytes).expect("32 bytes")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let length = reader.take(2)?;
+        let length = usize::from(u16::from_le_bytes([length[0], length[1]]));
+        let message = reader.take(length)?.to_vec();
+        if !reader.data.is_empty() {
+            return Err("Trailing bytes after off-chain message".to_string());
+        }
+
+        let offchain = Self {
+            application_domain,
+            format,
+            signers,
+            message,
+        };
+        offchain.validate()?;
+        Ok(offchain)
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() < len {
+            return Err("Off-chain message is truncated".to_string());
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+}
+
+/// Splits a signed envelope (`u8` signature count, the signatures, then the
+/// message) into its signatures and message bytes. Signatures are in signer order.
+pub fn split_signed_envelope(data: &[u8]) -> Result<(Vec<[u8; 64]>, &[u8]), String> {
+    let mut reader = Reader { data };
+    let count = usize::from(reader.take(1)?[0]);
+    let signatures = (0..count)
+        .map(|_| reader.take(64).map(|bytes| bytes.try_into().expect("64 bytes")))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((signatures, reader.data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer(byte: u8) -> Pubkey {
+        Pubkey::new_from_array([byte; 32])
+    }
+
+    #[test]
+    fn serializes_the_header_fields_in_order() {
+        let offchain = OffchainMessage::new([7; 32], None, vec![signer(1), signer(2)], b"hi".to_vec()).unwrap();
+        let mut expected = b"\xffsolana offchain".to_vec();
+        expected.push(HEADER_VERSION);
+        expected.extend_from_slice(&[7; 32]);
+        expected.push(0); // restricted ASCII
+        expected.push(2);
+        expected.extend_from_slice(&[1; 32]);
+        expected.extend_from_slice(&[2; 32]);
+        expected.extend_from_slice(&[2, 0]);
+        expected.extend_from_slice(b"hi");
+        assert_eq!(offchain.serialize(), expected);
+
+        let parsed = OffchainMessage::deserialize(&expected).unwrap();
+        assert_eq!(parsed.format, MessageFormat::RestrictedAscii);
+        assert_eq!(parsed.signers, vec![signer(1), signer(2)]);
+        assert_eq!(parsed.message, b"hi");
+    }
+
+    #[test]
+    fn picks_the_most_restrictive_format_that_fits() {
+        let format = |message: Vec<u8>| OffchainMessage::new([0; 32], None, vec![signer(1)], message).unwrap().format;
+        assert_eq!(format(b"plain text".to_vec()), MessageFormat::RestrictedAscii);
+        assert_eq!(format("caf\u{e9}".as_bytes().to_vec()), MessageFormat::LimitedUtf8);
+        assert_eq!(format(vec![b'a'; PACKET_DATA_SIZE]), MessageFormat::ExtendedUtf8);
+    }
+
+    #[test]
+    fn rejects_bodies_that_break_the_chosen_format() {
+        let new = |format, message: &[u8]| OffchainMessage::new([0; 32], Some(format), vec![signer(1)], message.to_vec());
+        assert!(new(MessageFormat::RestrictedAscii, "caf\u{e9}".as_bytes()).is_err());
+        assert!(new(MessageFormat::LimitedUtf8, &[0xff, 0xfe]).is_err());
+        assert!(new(MessageFormat::LimitedUtf8, &vec![b'a'; PACKET_DATA_SIZE]).is_err());
+        assert!(new(MessageFormat::ExtendedUtf8, b"").is_err());
+        assert!(OffchainMessage::new([0; 32], None, Vec::new(), b"hi".to_vec()).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_or_padded_envelopes() {
+        let data = OffchainMessage::new([0; 32], None, vec![signer(1)], b"hello".to_vec()).unwrap().serialize();
+        assert!(OffchainMessage::deserialize(&data[..data.len() - 1]).is_err());
+        let mut padded = data.clone();
+        padded.push(0);
+        assert!(OffchainMessage::deserialize(&padded).is_err());
+        let mut wrong_domain = data;
+        wrong_domain[0] = 0;
+        assert!(OffchainMessage::deserialize(&wrong_domain).is_err());
+    }
+
+    #[test]
+    fn splits_signed_envelopes() {
+        let mut data = vec![2];
+        data.extend_from_slice(&[1; 64]);
+        data.extend_from_slice(&[2; 64]);
+        data.extend_from_slice(b"message");
+        let (signatures, message) = split_signed_envelope(&data).unwrap();
+        assert_eq!(signatures, vec![[1; 64], [2; 64]]);
+        assert_eq!(message, b"message");
+        assert!(split_signed_envelope(&data[..64]).is_err());
+    }
+}
@@ -0,0 +1,737 @@
+use axum::{
+    http::header::{CONTENT_SECURITY_POLICY, CONTENT_TYPE},
+    response::Html,
+    routing::get,
+    Router,
+};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use crate::auth::{ApiKeyInfo, Scope};
+use crate::config::FeatureToggles;
+use crate::error::ErrorResponse;
+use crate::handlers::{
+    admin::*, instruction::*, keypair::*, message::*, pda::*, pubkey::*, send::*, siws::*, token::*, transaction::*,
+    vanity::*, vault::*,
+};
+use crate::vault::KeyInfo;
+
+pub use api_schema_derive::ApiSchema;
+
+/// A type that can describe itself as an OpenAPI schema. Usually derived;
+/// named types register themselves under `components` and return a `$ref`.
+pub trait ApiSchema {
+    fn schema(components: &mut Components) -> Value;
+}
+
+/// Schemas of the named types reachable from the documented operations.
+#[derive(Default)]
+pub struct Components {
+    schemas: BTreeMap<String, Value>,
+}
+
+impl Components {
+    /// Registers `name` on first use and returns a reference to it. The
+    /// placeholder inserted before `build` runs keeps recursive types finite.
+    pub fn reference(&mut self, name: &str, build: impl FnOnce(&mut Self) -> Value) -> Value {
+        if !self.schemas.contains_key(name) {
+            self.schemas.insert(name.to_string(), Value::Null);
+            let schema = build(self);
+            self.schemas.insert(name.to_string(), schema);
+        }
+        json!({ "$ref": format!("#/components/schemas/{}", name) })
+    }
+}
+
+/// Object schema under construction, used by the derive.
+pub struct Object {
+    description: Option<&'static str>,
+    properties: Map<String, Value>,
+    required: Vec<&'static str>,
+    flattened: Vec<Value>,
+}
+
+impl Object {
+    pub fn new(description: Option<&'static str>) -> Self {
+        Self {
+            description,
+            properties: Map::new(),
+            required: Vec::new(),
+            flattened: Vec::new(),
+        }
+    }
+
+    pub fn property(&mut self, name: &'static str, required: bool, schema: Value) {
+        if required {
+            self.required.push(name);
+        }
+        self.properties.insert(name.to_string(), schema);
+    }
+
+    pub fn flatten(&mut self, schema: Value) {
+        self.flattened.push(schema);
+    }
+
+    pub fn finish(self) -> Value {
+        let mut object = json!({ "type": "object", "properties": self.properties });
+        if !self.required.is_empty() {
+            object["required"] = json!(self.required);
+        }
+        if self.flattened.is_empty() {
+            return describe(object, self.description, None, None);
+        }
+        let mut all_of = self.flattened;
+        all_of.push(object);
+        describe(json!({ "allOf": all_of }), self.description, None, None)
+    }
+}
+
+/// Adds a description, format and example to a schema. A `$ref` is wrapped
+/// since siblings of `$ref` are ignored; on arrays the format applies to the
+/// items. Examples for non-string schemas are written as JSON.
+pub fn describe(
+    mut schema: Value,
+    description: Option<&str>,
+    format: Option<&str>,
+    example: Option<&str>,
+) -> Value {
+    if description.is_none() && format.is_none() && example.is_none() {
+        return schema;
+    }
+    if schema.get("$ref").is_some() {
+        schema = json!({ "allOf": [schema] });
+    }
+    if let Some(format) = format {
+        let target = match schema.get_mut("items") {
+            Some(items) => items,
+            None => &mut schema,
+        };
+        target["format"] = json!(format);
+    }
+    if let Some(example) = example {
+        schema["example"] = match schema.get("type").and_then(Value::as_str) {
+            Some("string") => json!(example),
+            _ => serde_json::from_str(example).unwrap_or_else(|_| json!(example)),
+        };
+    }
+    if let Some(description) = description {
+        schema["description"] = json!(description);
+    }
+    schema
+}
+
+pub fn nullable(schema: Value) -> Value {
+    let mut schema = if schema.get("$ref").is_some() { json!({ "allOf": [schema] }) } else { schema };
+    schema["nullable"] = json!(true);
+    schema
+}
+
+pub fn string_enum(description: Option<&str>, values: &[&str]) -> Value {
+    describe(json!({ "type": "string", "enum": values }), description, None, None)
+}
+
+pub fn one_of(description: Option<&str>, schemas: Vec<Value>) -> Value {
+    describe(json!({ "oneOf": schemas }), description, None, None)
+}
+
+/// One variant of an internally or adjacently tagged enum.
+pub fn tagged(tag: &'static str, name: &'static str, content: Option<(&'static str, Value)>) -> Value {
+    let mut object = Object::new(None);
+    object.property(tag, true, string_enum(None, &[name]));
+    if let Some((field, schema)) = content {
+        object.property(field, true, schema);
+    }
+    object.finish()
+}
+
+macro_rules! primitive_schema {
+    ($($ty:ty => $schema:tt),* $(,)?) => {
+        $(
+            impl ApiSchema for $ty {
+                fn schema(_: &mut Components) -> Value {
+                    json!($schema)
+                }
+            }
+        )*
+    };
+}
+
+primitive_schema! {
+    String => { "type": "string" },
+    bool => { "type": "boolean" },
+    u8 => { "type": "integer", "format": "uint8", "minimum": 0, "maximum": 255 },
+    u16 => { "type": "integer", "format": "uint16", "minimum": 0, "maximum": 65535 },
+    u32 => { "type": "integer", "format": "uint32", "minimum": 0 },
+    u64 => { "type": "integer", "format": "uint64", "minimum": 0 },
+    usize => { "type": "integer", "format": "uint64", "minimum": 0 },
+    i64 => { "type": "integer", "format": "int64" },
+    f64 => { "type": "number", "format": "double" },
+    Value => { "description": "Any JSON value." },
+}
+
+impl ApiSchema for &str {
+    fn schema(_: &mut Components) -> Value {
+        json!({ "type": "string" })
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for Option<T> {
+    fn schema(components: &mut Components) -> Value {
+        nullable(T::schema(components))
+    }
+}
+
+impl<T: ApiSchema> ApiSchema for Vec<T> {
+    fn schema(components: &mut Components) -> Value {
+        json!({ "type": "array", "items": T::schema(components) })
+    }
+}
+
+type SchemaFn = fn(&mut Components) -> Value;
+
+/// An operation in the document; request and response types are given as
+/// type parameters so their schemas come from the types the handlers use.
+struct Operation {
+    method: &'static str,
+    path: &'static str,
+    tag: &'static str,
+    summary: &'static str,
+    request: Option<(SchemaFn, bool)>,
+    response: SchemaFn,
+    envelope: bool,
+    content_type: &'static str,
+    /// API key scope the route group needs; `None` for public routes.
+    scope: Option<Scope>,
+}
+
+fn operation<T: ApiSchema>(method: &'static str, path: &'static str, tag: &'static str, summary: &'static str) -> Operation {
+    Operation {
+        method,
+        path,
+        tag,
+        summary,
+        request: None,
+        response: T::schema,
+        envelope: true,
+        content_type: "application/json",
+        scope: None,
+    }
+}
+
+impl Operation {
+    fn body<B: ApiSchema>(mut self) -> Self {
+        self.request = Some((B::schema, true));
+        self
+    }
+
+    fn optional_body<B: ApiSchema>(mut self) -> Self {
+        self.request = Some((B::schema, false));
+        self
+    }
+
+    /// The response is returned as-is rather than in the success envelope.
+    fn bare(mut self) -> Self {
+        self.envelope = false;
+        self
+    }
+
+    /// The response is plain text, such as the Prometheus exposition format.
+    fn text(mut self) -> Self {
+        self.envelope = false;
+        self.content_type = "text/plain";
+        self
+    }
+
+    fn render(&self, components: &mut Components) -> Value {
+        let data = (self.response)(components);
+        let response = if self.envelope {
+            json!({
+                "type": "object",
+                "required": ["success", "data"],
+                "properties": { "success": { "type": "boolean", "enum": [true] }, "data": data },
+            })
+        } else {
+            data
+        };
+        let error = ErrorResponse::schema(components);
+
+        let mut rendered = json!({
+            "tags": [self.tag],
+            "summary": self.summary,
+            "operationId": operation_id(self.method, self.path),
+            "responses": {
+                "200": { "description": "Success", "content": { self.content_type: { "schema": response } } },
+                "default": { "description": "Error", "content": { "application/json": { "schema": error } } },
+            },
+            "security": [],
+        });
+        if let Some(scope) = self.scope {
+            rendered["security"] = json!([{ "ApiKey": [] }]);
+            rendered["description"] = json!(format!("Requires an API key with the `{}` scope.", scope.name()));
+        }
+        let parameters: Vec<Value> = path_parameters(self.path)
+            .map(|name| json!({ "name": name, "in": "path", "required": true, "schema": { "type": "string" } }))
+            .collect();
+        if !parameters.is_empty() {
+            rendered["parameters"] = json!(parameters);
+        }
+        if let Some((request, required)) = self.request {
+            rendered["requestBody"] = json!({
+                "required": required,
+                "content": { "application/json": { "schema": request(components) } },
+            });
+        }
+        rendered
+    }
+}
+
+fn path_parameters(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/')
+        .filter_map(|segment| segment.strip_prefix('{').and_then(|segment| segment.strip_suffix('}')))
+}
+
+/// `post /vault/keys/{key_id}/rotate` becomes `post_vault_keys_key_id_rotate`.
+fn operation_id(method: &str, path: &str) -> String {
+    let mut id = method.to_string();
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        id.push('_');
+        id.push_str(segment.trim_start_matches('{').trim_end_matches('}'));
+    }
+    id
+}
+
+/// Request bodies `/keypair/import` accepts: the pasted form or a raw
+/// `id.json` byte array.
+struct ImportKeypairBody;
+
+impl ApiSchema for ImportKeypairBody {
+    fn schema(components: &mut Components) -> Value {
+        let bytes = describe(
+            Vec::<u8>::schema(components),
+            Some("Contents of a solana-keygen `id.json` file."),
+            None,
+            None,
+        );
+        one_of(None, vec![ImportKeypairRequest::schema(components), bytes])
+    }
+}
+
+/// Sets the scope on a group of operations, mirroring the route groups in
+/// `routes::app_router`.
+fn scoped(scope: Scope, operations: Vec<Operation>) -> impl Iterator<Item = Operation> {
+    operations.into_iter().map(move |operation| Operation { scope: Some(scope), ..operation })
+}
+
+/// Builds the OpenAPI document for the routes `app_router` mounts with
+/// `features`, plus `/health`.
+pub fn document(features: &FeatureToggles) -> Value {
+    let mut build = vec![
+        operation::<CreateTokenResponse>("post", "/token/create", "Tokens", "Build an SPL token InitializeMint instruction")
+            .body::<CreateTokenRequest>(),
+        operation::<MintTokenResponse>("post", "/token/mint", "Tokens", "Build an SPL token MintTo instruction")
+            .body::<MintTokenRequest>(),
+        operation::<VerifyMessageResponse>("post", "/message/verify", "Messages", "Verify an Ed25519 message signature")
+            .body::<VerifyMessageRequest>(),
+        operation::<VerifyBatchResponse>("post", "/message/verify/batch", "Messages", "Verify many message signatures at once")
+            .body::<VerifyBatchRequest>(),
+        operation::<VerifyOffchainMessageResponse>("post", "/message/offchain/verify", "Messages", "Verify a Solana off-chain message")
+            .body::<VerifyOffchainMessageRequest>(),
+        operation::<SendSolResponse>("post", "/send/sol", "Instructions", "Build a SOL transfer instruction")
+            .body::<SendSolRequest>(),
+        operation::<SendTokenResponse>("post", "/send/token", "Instructions", "Build an SPL token transfer instruction")
+            .body::<SendTokenRequest>(),
+        operation::<VerifyTransactionResponse>("post", "/transaction/verify", "Transactions", "Check a transaction's signatures and size")
+            .body::<VerifyTransactionRequest>(),
+        operation::<DecodeTransactionResponse>("post", "/transaction/decode", "Transactions", "Decode a serialized transaction")
+            .body::<DecodeTransactionRequest>(),
+        operation::<crate::decoder::DecodedInstruction>("post", "/instruction/decode", "Instructions", "Decode a single instruction")
+            .body::<DecodeInstructionRequest>(),
+        operation::<PdaResponse>("post", "/pda/find", "Addresses", "Find a program derived address and its bump")
+            .body::<FindPdaRequest>(),
+        operation::<PdaResponse>("post", "/pda/create", "Addresses", "Create a program address with a given bump")
+            .body::<CreatePdaRequest>(),
+        operation::<InspectPubkeyResponse>("post", "/pubkey/inspect", "Addresses", "Inspect a pubkey's encodings and curve")
+            .body::<InspectPubkeyRequest>(),
+    ];
+    let sign = vec![
+        operation::<SignMessageResponse>("post", "/message/sign", "Messages", "Sign a message with Ed25519")
+            .body::<SignMessageRequest>(),
+        operation::<SignOffchainMessageResponse>("post", "/message/offchain/sign", "Messages", "Sign a Solana off-chain message")
+            .body::<SignOffchainMessageRequest>(),
+        operation::<SignTransactionResponse>("post", "/transaction/sign", "Transactions", "Add signatures to a serialized transaction")
+            .body::<SignTransactionRequest>(),
+    ];
+    let mut keygen = vec![
+        operation::<KeypairResponse>("post", "/keypair", "Keys", "Generate a keypair, optionally from a BIP39 mnemonic")
+            .optional_body::<GenerateKeypairRequest>(),
+        operation::<ExportKeypairResponse>("post", "/keypair/export", "Keys", "Export a secret as a solana-keygen id.json")
+            .body::<ExportKeypairRequest>(),
+        operation::<ImportKeypairResponse>("post", "/keypair/import", "Keys", "Import a solana-keygen id.json")
+            .body::<ImportKeypairBody>(),
+        operation::<ImportKeypairResponse>("post", "/keypair/decrypt", "Keys", "Decrypt a password-encrypted keystore")
+            .body::<DecryptKeypairRequest>(),
+    ];
+    let mut admin = vec![
+        operation::<Vec<ApiKeyInfo>>("get", "/admin/api-keys", "Admin", "List API keys"),
+        operation::<CreateApiKeyResponse>("post", "/admin/api-keys", "Admin", "Create an API key")
+            .body::<CreateApiKeyRequest>(),
+        operation::<ApiKeyInfo>("delete", "/admin/api-keys/{key_id}", "Admin", "Delete an API key"),
+    ];
+    if features.vanity {
+        keygen.extend([
+            operation::<StartVanityResponse>("post", "/keypair/vanity", "Vanity", "Start a vanity address job")
+                .body::<StartVanityRequest>(),
+            operation::<VanityJobResponse>("get", "/keypair/vanity/{job_id}", "Vanity", "Get a vanity job's progress"),
+            operation::<VanityJobResponse>("delete", "/keypair/vanity/{job_id}", "Vanity", "Cancel a vanity job"),
+        ]);
+    }
+    if features.siws {
+        build.extend([
+            operation::<SiwsNonceResponse>("post", "/siws/nonce", "Sign-In With Solana", "Issue a single-use nonce"),
+            operation::<BuildSiwsMessageResponse>("post", "/siws/message", "Sign-In With Solana", "Build a sign-in message")
+                .body::<BuildSiwsMessageRequest>(),
+            operation::<VerifySiwsResponse>("post", "/siws/verify", "Sign-In With Solana", "Verify a signed sign-in message")
+                .body::<VerifySiwsRequest>(),
+        ]);
+    }
+    if features.vault {
+        keygen.extend([
+            operation::<Vec<KeyInfo>>("get", "/vault/keys", "Vault", "List vault keys"),
+            operation::<CreateVaultKeyResponse>("post", "/vault/keys", "Vault", "Generate or import a vault key")
+                .body::<CreateVaultKeyRequest>(),
+            operation::<KeyInfo>("get", "/vault/keys/{key_id}", "Vault", "Get a vault key"),
+            operation::<KeyInfo>("post", "/vault/keys/{key_id}/rotate", "Vault", "Rotate a vault key to a new keypair"),
+        ]);
+        admin.extend([
+            operation::<KeyInfo>("delete", "/vault/keys/{key_id}", "Vault", "Delete a vault key"),
+            operation::<KeyInfo>("post", "/vault/keys/{key_id}/disable", "Vault", "Disable signing with a vault key"),
+            operation::<KeyInfo>("post", "/vault/keys/{key_id}/enable", "Vault", "Re-enable a vault key"),
+        ]);
+    }
+    let mut metrics = Vec::new();
+    if features.metrics {
+        metrics.push(operation::<String>("get", "/metrics", "Service", "Prometheus metrics").text());
+    }
+    let operations: Vec<Operation> = scoped(Scope::Build, build)
+        .chain(scoped(Scope::Sign, sign))
+        .chain(scoped(Scope::Keygen, keygen))
+        .chain(scoped(Scope::Metrics, metrics))
+        .chain(scoped(Scope::Admin, admin))
+        .chain([operation::<crate::HealthResponse>("get", "/health", "Service", "Health check").bare()])
+        .collect();
+
+    let mut components = Components::default();
+    let mut paths = Map::new();
+    for operation in &operations {
+        let rendered = operation.render(&mut components);
+        let item = paths.entry(operation.path).or_insert_with(|| json!({}));
+        item[operation.method] = rendered;
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Solana HTTP Server",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Keypair, token, message, transaction and address utilities for Solana. \
+                Every response is `{\"success\": true, \"data\": ...}` or an `ErrorResponse`. \
+                String formats: `base58` for pubkeys, secrets and signatures unless an encoding field \
+                says otherwise, `base64` for serialized transactions and instruction data.",
+        },
+        "paths": paths,
+        "components": {
+            "schemas": components.schemas,
+            "securitySchemes": {
+                "ApiKey": {
+                    "type": "apiKey",
+                    "in": "header",
+                    "name": "X-API-Key",
+                    "description": "An API key whose scopes cover the operation; `admin` covers every scope. \
+                        `Authorization: Bearer <key>` is accepted too.",
+                },
+            },
+        },
+    })
+}
+
+/// Swagger UI 5.17.14's distribution files, vendored unmodified (see
+/// `assets/swagger-ui/LICENSE`). Serving them ourselves keeps third-party
+/// script off a page on the API's origin.
+const SWAGGER_UI_CSS: &str = include_str!("../assets/swagger-ui/swagger-ui.css");
+const SWAGGER_UI_BUNDLE: &str = include_str!("../assets/swagger-ui/swagger-ui-bundle.js");
+
+const DOCS_PAGE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>Solana HTTP Server API</title>
+  <link rel="stylesheet" href="/docs/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="/docs/swagger-ui-bundle.js"></script>
+  <script src="/docs/swagger-initializer.js"></script>
+</body>
+</html>
+"##;
+
+const DOCS_INITIALIZER: &str = r##"window.onload = () => {
+  window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui", validatorUrl: null });
+};
+"##;
+
+/// Only same-origin scripts may run on the docs page; Swagger UI sets
+/// inline styles and uses data: URIs for its icons.
+const DOCS_CSP: &str =
+    "default-src 'self'; style-src 'self' 'unsafe-inline'; img-src 'self' data:; frame-ancestors 'none'";
+
+/// `/openapi.json` and a Swagger UI page at `/docs`. The document is built
+/// once, for the enabled features.
+pub fn router<S: Clone + Send + Sync + 'static>(features: &FeatureToggles) -> Router<S> {
+    let spec: Arc<str> = document(features).to_string().into();
+    let asset = |content_type: &'static str, body: &'static str| {
+        get(move || async move { ([(CONTENT_TYPE, content_type)], body) })
+    };
+    Router::new()
+        .route(
+            "/openapi.json",
+            get(move || async move { ([(CONTENT_TYPE, "application/json")], spec.to_string()) }),
+        )
+        .route("/docs", get(|| async { ([(CONTENT_SECURITY_POLICY, DOCS_CSP)], Html(DOCS_PAGE)) }))
+        .route("/docs/swagger-ui.css", asset("text/css; charset=utf-8", SWAGGER_UI_CSS))
+        .route("/docs/swagger-ui-bundle.js", asset("text/javascript; charset=utf-8", SWAGGER_UI_BUNDLE))
+        .route("/docs/swagger-initializer.js", asset("text/javascript; charset=utf-8", DOCS_INITIALIZER))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+
+    /// An owned account.
+    #[derive(Serialize, ApiSchema)]
+    #[serde(rename_all = "camelCase")]
+    struct Account {
+        /// Who may close it.
+        owner_key: String,
+        #[serde(rename = "lamports")]
+        balance: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        close_authority: Option<String>,
+        memo: Option<String>,
+        #[serde(skip)]
+        cached: u8,
+        #[serde(flatten)]
+        extra: Extra,
+        status: Status,
+    }
+
+    #[derive(Serialize, ApiSchema)]
+    struct Extra {
+        #[schema(format = "base64", example = "AQID")]
+        data: String,
+    }
+
+    #[derive(Serialize, ApiSchema)]
+    #[serde(rename_all = "snake_case")]
+    enum Status {
+        InProgress,
+        Done,
+    }
+
+    #[derive(Serialize, ApiSchema)]
+    #[serde(tag = "kind", content = "value", rename_all = "camelCase")]
+    enum Adjacent {
+        Plain,
+        TotalAmount(u64),
+        Named { a_b: bool },
+    }
+
+    #[derive(Serialize, ApiSchema)]
+    #[serde(tag = "type")]
+    enum Internal {
+        Transfer { lamports: u64 },
+        Wrapped(Extra),
+        Empty,
+    }
+
+    #[derive(Serialize, ApiSchema)]
+    #[serde(untagged)]
+    enum Either {
+        Text(String),
+        Bytes(Vec<u8>),
+        Point { x: i64, y: i64 },
+    }
+
+    fn resolve<'a>(schema: &'a Value, components: &'a Components) -> &'a Value {
+        match schema["$ref"].as_str() {
+            Some(reference) => &components.schemas[reference.rsplit('/').next().unwrap()],
+            None => schema,
+        }
+    }
+
+    /// Property names an object schema declares, through `allOf` and `$ref`.
+    fn property_names(schema: &Value, components: &Components) -> Vec<String> {
+        let schema = resolve(schema, components);
+        let own = schema["properties"].as_object().into_iter().flat_map(|properties| properties.keys().cloned());
+        let inherited = schema["allOf"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|part| property_names(part, components));
+        own.chain(inherited).collect()
+    }
+
+    /// Checks `value` against the subset of OpenAPI the derive emits. With
+    /// `strict`, objects may not carry undeclared properties.
+    fn conforms(value: &Value, schema: &Value, components: &Components, strict: bool) -> Result<(), String> {
+        let schema = resolve(schema, components);
+        if value.is_null() {
+            return if schema["nullable"] == json!(true) { Ok(()) } else { Err(format!("null for {}", schema)) };
+        }
+        if let Some(parts) = schema["allOf"].as_array() {
+            for part in parts {
+                conforms(value, part, components, false)?;
+            }
+        } else if let Some(alternatives) = schema["oneOf"].as_array() {
+            let matching = alternatives.iter().filter(|alt| conforms(value, alt, components, strict).is_ok()).count();
+            if matching != 1 {
+                return Err(format!("{} matches {} alternatives of {}", value, matching, schema));
+            }
+        }
+        if let Some(values) = schema["enum"].as_array() {
+            if !values.contains(value) {
+                return Err(format!("{} is not one of {:?}", value, values));
+            }
+        }
+        let type_matches = match schema["type"].as_str() {
+            Some("object") => {
+                let object = value.as_object().ok_or_else(|| format!("{} is not an object", value))?;
+                for name in schema["required"].as_array().into_iter().flatten() {
+                    if !object.contains_key(name.as_str().unwrap()) {
+                        return Err(format!("{} lacks required {}", value, name));
+                    }
+                }
+                for (name, property) in schema["properties"].as_object().unwrap() {
+                    if let Some(field) = object.get(name) {
+                        conforms(field, property, components, true)?;
+                    }
+                }
+                true
+            }
+            Some("array") => {
+                let items = value.as_array().ok_or_else(|| format!("{} is not an array", value))?;
+                items.iter().try_for_each(|item| conforms(item, &schema["items"], components, true))?;
+                true
+            }
+            Some("string") => value.is_string(),
+            Some("integer") => value.is_i64() || value.is_u64(),
+            Some("number") => value.is_number(),
+            Some("boolean") => value.is_boolean(),
+            _ => true,
+        };
+        if !type_matches {
+            return Err(format!("{} is not a {}", value, schema["type"]));
+        }
+        if strict && value.is_object() && (schema.get("properties").is_some() || schema.get("allOf").is_some()) {
+            let declared = property_names(schema, components);
+            if let Some(extra) = value.as_object().unwrap().keys().find(|key| !declared.contains(key)) {
+                return Err(format!("{} is not declared in {}", extra, schema));
+            }
+        }
+        Ok(())
+    }
+
+    fn assert_conforms<T: Serialize + ApiSchema>(value: &T) {
+        let mut components = Components::default();
+        let schema = T::schema(&mut components);
+        let value = serde_json::to_value(value).unwrap();
+        if let Err(e) = conforms(&value, &schema, &components, true) {
+            panic!("{} does not conform: {}\nschemas: {:#?}", value, e, components.schemas);
+        }
+    }
+
+    fn account(close_authority: Option<&str>, memo: Option<&str>) -> Account {
+        Account {
+            owner_key: "owner".to_string(),
+            balance: 5,
+            close_authority: close_authority.map(String::from),
+            memo: memo.map(String::from),
+            cached: 1,
+            extra: Extra { data: "AQID".to_string() },
+            status: if memo.is_some() { Status::Done } else { Status::InProgress },
+        }
+    }
+
+    #[test]
+    fn structs_follow_serde_renames_and_optional_fields() {
+        let mut components = Components::default();
+        assert_eq!(Account::schema(&mut components), json!({ "$ref": "#/components/schemas/Account" }));
+        let schema = &components.schemas["Account"];
+        assert_eq!(schema["description"], "An owned account.");
+        let object = &schema["allOf"][1];
+        let mut names: Vec<&String> = object["properties"].as_object().unwrap().keys().collect();
+        names.sort();
+        assert_eq!(names, ["closeAuthority", "lamports", "memo", "ownerKey", "status"]);
+        assert_eq!(object["required"], json!(["ownerKey", "lamports", "status"]));
+        assert_eq!(object["properties"]["ownerKey"]["description"], "Who may close it.");
+        assert_eq!(object["properties"]["memo"]["nullable"], true);
+        let data = &components.schemas["Extra"]["properties"]["data"];
+        assert_eq!((&data["format"], &data["example"]), (&json!("base64"), &json!("AQID")));
+        assert_eq!(components.schemas["Status"]["enum"], json!(["in_progress", "done"]));
+
+        let skipped = account(None, None);
+        assert_eq!(skipped.cached, 1);
+        assert!(serde_json::to_value(&skipped).unwrap().get("cached").is_none());
+        assert_conforms(&skipped);
+        assert_conforms(&account(Some("closer"), Some("note")));
+    }
+
+    #[test]
+    fn enums_follow_serde_tagging() {
+        for value in [Adjacent::Plain, Adjacent::TotalAmount(3), Adjacent::Named { a_b: true }] {
+            assert_conforms(&value);
+        }
+        let wrapped = Internal::Wrapped(Extra { data: String::new() });
+        for value in [Internal::Transfer { lamports: 1 }, wrapped, Internal::Empty] {
+            assert_conforms(&value);
+        }
+        for value in [Either::Text("a".to_string()), Either::Bytes(vec![1, 2]), Either::Point { x: -1, y: 2 }] {
+            assert_conforms(&value);
+        }
+        let mut components = Components::default();
+        Adjacent::schema(&mut components);
+        let tags: Vec<&Value> = components.schemas["Adjacent"]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variant| &variant["properties"]["kind"]["enum"][0])
+            .collect();
+        assert_eq!(tags, ["plain", "totalAmount", "named"]);
+    }
+
+    #[test]
+    fn the_checker_catches_drift() {
+        let mut components = Components::default();
+        let schema = Account::schema(&mut components);
+        let mut value = serde_json::to_value(account(None, None)).unwrap();
+        value["unexpected"] = json!(1);
+        assert!(conforms(&value, &schema, &components, true).is_err());
+        let mut value = serde_json::to_value(account(None, None)).unwrap();
+        value.as_object_mut().unwrap().remove("ownerKey");
+        assert!(conforms(&value, &schema, &components, true).is_err());
+        value["ownerKey"] = json!(7);
+        assert!(conforms(&value, &schema, &components, true).is_err());
+    }
+
+    #[test]
+    fn every_documented_schema_resolves() {
+        let document = document(&FeatureToggles::default());
+        let text = document.to_string();
+        let schemas = document["components"]["schemas"].as_object().unwrap();
+        for reference in text.split("\"#/components/schemas/").skip(1) {
+            let name = &reference[..reference.find('"').unwrap()];
+            assert!(schemas.get(name).is_some_and(|schema| !schema.is_null()), "{} is not defined", name);
+        }
+    }
+}
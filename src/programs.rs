@@ -0,0 +1,63 @@
+use solana_sdk::pubkey::Pubkey;
+
+/// Programs and sysvars that support regularly gets asked about.
+const WELL_KNOWN_ADDRESSES: &[(&str, &str)] = &[
+    ("11111111111111111111111111111111", "System Program"),
+    ("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", "SPL Token Program"),
+    ("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb", "SPL Token-2022 Program"),
+    ("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL", "Associated Token Account Program"),
+    ("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr", "Memo Program"),
+    ("Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo", "Memo Program (v1)"),
+    ("ComputeBudget111111111111111111111111111111", "Compute Budget Program"),
+    ("Stake11111111111111111111111111111111111111", "Stake Program"),
+    ("Vote111111111111111111111111111111111111111", "Vote Program"),
+    ("Config1111111111111111111111111111111111111", "Config Program"),
+    ("AddressLookupTab1e1111111111111111111111111", "Address Lookup Table Program"),
+    ("BPFLoader1111111111111111111111111111111111", "BPF Loader (deprecated)"),
+    ("BPFLoader2111111111111111111111111111111111", "BPF Loader"),
+    ("BPFLoaderUpgradeab1e11111111111111111111111", "BPF Upgradeable Loader"),
+    ("LoaderV411111111111111111111111111111111111", "Loader v4"),
+    ("Ed25519SigVerify111111111111111111111111111", "Ed25519 Signature Verification Precompile"),
+    ("KeccakSecp256k11111111111111111111111111111", "Secp256k1 Signature Verification Precompile"),
+    ("NativeLoader1111111111111111111111111111111", "Native Loader"),
+    ("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s", "Metaplex Token Metadata Program"),
+    ("So11111111111111111111111111111111111111112", "Wrapped SOL Mint"),
+    ("SysvarC1ock11111111111111111111111111111111", "Clock Sysvar"),
+    ("SysvarRent111111111111111111111111111111111", "Rent Sysvar"),
+    ("SysvarEpochSchedu1e111111111111111111111111", "Epoch Schedule Sysvar"),
+    ("SysvarFees111111111111111111111111111111111", "Fees Sysvar"),
+    ("Sysvar1nstructions1111111111111111111111111", "Instructions Sysvar"),
+    ("SysvarRecentB1ockHashes11111111111111111111", "Recent Blockhashes Sysvar"),
+    ("SysvarRewards111111111111111111111111111111", "Rewards Sysvar"),
+    ("SysvarS1otHashes111111111111111111111111111", "Slot Hashes Sysvar"),
+    ("SysvarS1otHistory11111111111111111111111111", "Slot History Sysvar"),
+    ("SysvarStakeHistory1111111111111111111111111", "Stake History Sysvar"),
+    ("SysvarEpochRewards1111111111111111111111111", "Epoch Rewards Sysvar"),
+    ("SysvarLastRestartS1ot1111111111111111111111", "Last Restart Slot Sysvar"),
+];
+
+pub fn well_known_label(pubkey: &Pubkey) -> Option<&'static str> {
+    let address = pubkey.to_string();
+    WELL_KNOWN_ADDRESSES
+        .iter()
+        .find(|(known, _)| *known == address)
+        .map(|(_, label)| *label)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    #[test]
+    fn labels_well_known_addresses_only() {
+        assert_eq!(well_known_label(&Pubkey::default()), Some("System Program"));
+        assert_eq!(well_known_label(&solana_sdk::sysvar::clock::id()), Some("Clock Sysvar"));
+        assert_eq!(well_known_label(&Keypair::new().pubkey()), None);
+
+        for (address, label) in WELL_KNOWN_ADDRESSES {
+            let pubkey: Pubkey = address.parse().unwrap_or_else(|_| panic!("{} is not a pubkey", address));
+            assert_eq!(well_known_label(&pubkey), Some(*label));
+        }
+    }
+}
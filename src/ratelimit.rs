@@ -0,0 +1,461 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header::RETRY_AFTER, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::auth::{Caller, PUBLIC_PATHS};
+use crate::config::RateLimitConfig;
+use crate::cors::PathPattern;
+use crate::error::{ApiError, ErrorCode};
+use crate::tls::ClientIdentity;
+use crate::vault::{now, write_private};
+
+pub const QUOTA_FILE_VERSION: u32 = 1;
+const SECONDS_PER_DAY: u64 = 86_400;
+/// How often quota usage is saved and idle buckets are dropped.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Limits for one group of routes.
+struct Rule {
+    name: String,
+    burst: u32,
+    per_second: f64,
+    daily_quota: u64,
+}
+
+impl Rule {
+    fn new(name: &str, requests_per_minute: u32, burst: u32, daily_quota: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            burst,
+            per_second: f64::from(requests_per_minute) / 60.0,
+            daily_quota,
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, rule: &Rule, at: Instant) {
+        let elapsed = at.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rule.per_second).min(f64::from(rule.burst));
+        self.updated = at;
+    }
+
+    /// Seconds until the bucket is full again.
+    fn reset_secs(&self, rule: &Rule) -> u64 {
+        ((f64::from(rule.burst) - self.tokens) / rule.per_second).ceil() as u64
+    }
+}
+
+/// Quota usage for the current UTC day, keyed by `<rule>|<client>`.
+#[derive(Serialize, Deserialize, Default)]
+struct QuotaFile {
+    version: u32,
+    /// Days since the Unix epoch.
+    day: u64,
+    used: HashMap<String, u64>,
+}
+
+struct Quotas {
+    file: QuotaFile,
+    dirty: bool,
+}
+
+impl Quotas {
+    /// Starts a fresh count when the UTC day has changed.
+    fn roll_over(&mut self, day: u64) {
+        if self.file.day != day {
+            self.file.day = day;
+            self.file.used.clear();
+            self.dirty = true;
+        }
+    }
+}
+
+/// Where a client stands against a rule, reported in the `X-RateLimit-*` headers.
+struct Usage {
+    limit: u32,
+    remaining: u32,
+    reset_secs: u64,
+    /// Daily quota limit and remaining requests, when the rule has a quota.
+    quota: Option<(u64, u64)>,
+}
+
+impl Usage {
+    fn insert_headers(&self, headers: &mut HeaderMap) {
+        headers.insert("x-ratelimit-limit", HeaderValue::from(self.limit));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from(self.remaining));
+        headers.insert("x-ratelimit-reset", HeaderValue::from(self.reset_secs));
+        if let Some((limit, remaining)) = self.quota {
+            headers.insert("x-ratelimit-quota-limit", HeaderValue::from(limit));
+            headers.insert("x-ratelimit-quota-remaining", HeaderValue::from(remaining));
+            headers.insert("x-ratelimit-quota-reset", HeaderValue::from(secs_until_tomorrow()));
+        }
+    }
+}
+
+struct Rejection {
+    error: ApiError,
+    retry_after: u64,
+    usage: Usage,
+}
+
+pub struct RateLimiter {
+    default_rule: Rule,
+    routes: Vec<(Vec<PathPattern>, Rule)>,
+    /// Keyed by rule index and client.
+    buckets: Mutex<HashMap<(usize, String), Bucket>>,
+    quotas: Mutex<Quotas>,
+    quota_path: PathBuf,
+}
+
+impl RateLimiter {
+    /// Builds the limiter from a validated `[rate_limit]` config and loads
+    /// today's quota usage.
+    pub fn from_config(config: &RateLimitConfig) -> Result<Self, String> {
+        let routes = config
+            .routes
+            .iter()
+            .map(|route| {
+                let patterns = route.paths.iter().map(|path| PathPattern::parse(path)).collect();
+                let rule = Rule::new(
+                    &route.name,
+                    route.requests_per_minute,
+                    route.burst.unwrap_or(config.burst),
+                    route.daily_quota.unwrap_or(config.daily_quota),
+                );
+                (patterns, rule)
+            })
+            .collect();
+
+        let path = &config.quota_path;
+        let mut file = if path.exists() {
+            let contents = fs::read(path).map_err(|e| format!("reading {}: {}", path.display(), e))?;
+            let file: QuotaFile =
+                serde_json::from_slice(&contents).map_err(|e| format!("parsing {}: {}", path.display(), e))?;
+            if file.version != QUOTA_FILE_VERSION {
+                return Err(format!("unsupported quota file version {}", file.version));
+            }
+            file
+        } else {
+            QuotaFile { version: QUOTA_FILE_VERSION, ..Default::default() }
+        };
+        if file.day != today() {
+            file.day = today();
+            file.used.clear();
+        }
+
+        Ok(Self {
+            default_rule: Rule::new("default", config.requests_per_minute, config.burst, config.daily_quota),
+            routes,
+            buckets: Mutex::new(HashMap::new()),
+            quotas: Mutex::new(Quotas { file, dirty: false }),
+            quota_path: path.clone(),
+        })
+    }
+
+    fn rule_for(&self, path: &str) -> (usize, &Rule) {
+        self.routes
+            .iter()
+            .enumerate()
+            .find(|(_, (patterns, _))| patterns.iter().any(|pattern| pattern.matches(path)))
+            .map_or((self.routes.len(), &self.default_rule), |(index, (_, rule))| (index, rule))
+    }
+
+    /// Charges one request to `client` on `path`. Requests refused by the
+    /// bucket or the quota are not counted against the quota.
+    fn check(&self, client: &str, path: &str) -> Result<Usage, Rejection> {
+        let (index, rule) = self.rule_for(path);
+        let quota_key = format!("{}|{}", rule.name, client);
+        let mut quotas = self.quotas.lock().unwrap();
+        quotas.roll_over(today());
+        let used = quotas.file.used.get(&quota_key).copied().unwrap_or(0);
+
+        let at = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets
+            .entry((index, client.to_string()))
+            .or_insert_with(|| Bucket { tokens: f64::from(rule.burst), updated: at });
+        bucket.refill(rule, at);
+        let mut usage = Usage {
+            limit: rule.burst,
+            remaining: bucket.tokens as u32,
+            reset_secs: bucket.reset_secs(rule),
+            quota: (rule.daily_quota > 0).then(|| (rule.daily_quota, rule.daily_quota.saturating_sub(used))),
+        };
+
+        if rule.daily_quota > 0 && used >= rule.daily_quota {
+            return Err(Rejection {
+                error: ApiError::new(
+                    ErrorCode::QuotaExceeded,
+                    format!("Daily quota of {} requests for '{}' used up", rule.daily_quota, rule.name),
+                ),
+                retry_after: secs_until_tomorrow(),
+                usage,
+            });
+        }
+        if bucket.tokens < 1.0 {
+            let retry_after = (((1.0 - bucket.tokens) / rule.per_second).ceil() as u64).max(1);
+            return Err(Rejection {
+                error: ApiError::new(
+                    ErrorCode::RateLimited,
+                    format!("Rate limit for '{}' exceeded; retry in {} seconds", rule.name, retry_after),
+                ),
+                retry_after,
+                usage,
+            });
+        }
+
+        bucket.tokens -= 1.0;
+        usage.remaining = bucket.tokens as u32;
+        usage.reset_secs = bucket.reset_secs(rule);
+        if let Some((limit, _)) = usage.quota {
+            quotas.file.used.insert(quota_key, used + 1);
+            quotas.dirty = true;
+            usage.quota = Some((limit, limit - used - 1));
+        }
+        Ok(usage)
+    }
+
+    /// Writes the quota file to a temporary file and renames it into place.
+    fn save_quotas(&self) -> Result<(), String> {
+        let contents = {
+            let mut quotas = self.quotas.lock().unwrap();
+            if !quotas.dirty {
+                return Ok(());
+            }
+            quotas.dirty = false;
+            serde_json::to_vec(&quotas.file).map_err(|e| e.to_string())?
+        };
+        let tmp = self.quota_path.with_extension("json.tmp");
+        write_private(&tmp, &contents).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, &self.quota_path).map_err(|e| e.to_string())
+    }
+
+    /// Periodically saves quota usage and forgets buckets that have refilled,
+    /// so idle clients don't hold memory.
+    pub fn spawn_maintenance(self: Arc<Self>) {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(FLUSH_INTERVAL).await;
+                if let Err(e) = self.save_quotas() {
+                    self.quotas.lock().unwrap().dirty = true;
+                    tracing::error!("Failed to save quota usage to {}: {}", self.quota_path.display(), e);
+                }
+                let at = Instant::now();
+                let mut buckets = self.buckets.lock().unwrap();
+                buckets.retain(|(index, _), bucket| {
+                    let rule = self.routes.get(*index).map_or(&self.default_rule, |(_, rule)| rule);
+                    bucket.refill(rule, at);
+                    bucket.tokens < f64::from(rule.burst)
+                });
+            }
+        });
+    }
+}
+
+fn today() -> u64 {
+    now() / SECONDS_PER_DAY
+}
+
+fn secs_until_tomorrow() -> u64 {
+    SECONDS_PER_DAY - now() % SECONDS_PER_DAY
+}
+
+/// The API key a request authenticated with, else the identity of its client
+/// certificate, else its peer address.
+fn client_of(request: &Request) -> String {
+    if let Some(Caller(caller)) = request.extensions().get::<Caller>() {
+        return format!("key:{}", caller.id);
+    }
+    if let Some(identity) = request.extensions().get::<ClientIdentity>().and_then(|client| client.identity.as_ref()) {
+        return format!("cert:{}", identity);
+    }
+    match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(peer)) => format!("ip:{}", peer.ip()),
+        None => "unknown".to_string(),
+    }
+}
+
+/// Applies the rate limits and daily quotas, answering `429` with
+/// `Retry-After` once a client is over either. Runs inside `authenticate`
+/// so that callers are identified by API key.
+pub async fn limit(State(limiter): State<Arc<RateLimiter>>, request: Request, next: Next) -> Response {
+    if PUBLIC_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+    let client = client_of(&request);
+    let usage = match limiter.check(&client, request.uri().path()) {
+        Ok(usage) => usage,
+        Err(rejection) => {
+            tracing::warn!("Refused {} {} for {}: {}", request.method(), request.uri().path(), client, rejection.error);
+            let mut response = rejection.error.into_response();
+            let headers = response.headers_mut();
+            headers.insert(RETRY_AFTER, HeaderValue::from(rejection.retry_after));
+            rejection.usage.insert_headers(headers);
+            return response;
+        }
+    };
+
+    let mut response = next.run(request).await;
+    usage.insert_headers(response.headers_mut());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RateLimitRoute;
+    use crate::keystore::random_bytes;
+    use axum::{body::Body, http::StatusCode, middleware, routing::post, Router};
+    use tower::ServiceExt;
+
+    fn quota_file() -> PathBuf {
+        std::env::temp_dir().join(format!("quotas-{}.json", hex::encode(random_bytes::<8>())))
+    }
+
+    /// One request a minute, so buckets don't noticeably refill during a test.
+    fn config(burst: u32, daily_quota: u64) -> RateLimitConfig {
+        RateLimitConfig {
+            requests_per_minute: 1,
+            burst,
+            daily_quota,
+            quota_path: quota_file(),
+            routes: vec![RateLimitRoute {
+                name: "sign".to_string(),
+                paths: vec!["/message/sign".to_string(), "/keypair/*".to_string()],
+                requests_per_minute: 1,
+                burst: Some(1),
+                daily_quota: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn refused(result: Result<Usage, Rejection>) -> (ErrorCode, u64) {
+        match result {
+            Ok(_) => panic!("request was allowed"),
+            Err(rejection) => (rejection.error.code, rejection.retry_after),
+        }
+    }
+
+    #[test]
+    fn buckets_allow_a_burst_per_client_and_rule() {
+        let limiter = RateLimiter::from_config(&config(3, 0)).unwrap();
+        for remaining in [2, 1, 0] {
+            let usage = limiter.check("ip:1", "/transaction/build").unwrap_or_else(|_| panic!("refused"));
+            assert_eq!((usage.limit, usage.remaining, usage.quota), (3, remaining, None));
+        }
+        let (code, retry_after) = refused(limiter.check("ip:1", "/transaction/build"));
+        assert_eq!(code, ErrorCode::RateLimited);
+        assert!((1..=60).contains(&retry_after));
+
+        // Other clients, and routes under another rule, have buckets of their own.
+        assert!(limiter.check("ip:2", "/transaction/build").is_ok());
+        assert!(limiter.check("ip:1", "/message/sign").is_ok());
+        // Paths matching one rule share its bucket.
+        assert_eq!(refused(limiter.check("ip:1", "/keypair/new")).0, ErrorCode::RateLimited);
+    }
+
+    #[test]
+    fn buckets_refill_at_the_configured_rate() {
+        let rule = Rule::new("default", 60, 5, 0);
+        let start = Instant::now();
+        let mut bucket = Bucket { tokens: 0.0, updated: start };
+        assert_eq!(bucket.reset_secs(&rule), 5);
+        bucket.refill(&rule, start + Duration::from_secs(2));
+        assert_eq!(bucket.tokens, 2.0);
+        bucket.refill(&rule, start + Duration::from_secs(60));
+        assert_eq!(bucket.tokens, 5.0);
+        assert_eq!(bucket.reset_secs(&rule), 0);
+    }
+
+    #[test]
+    fn quotas_count_only_allowed_requests() {
+        let config = config(2, 3);
+        let limiter = RateLimiter::from_config(&config).unwrap();
+        assert_eq!(limiter.check("key:a", "/transaction/build").ok().and_then(|usage| usage.quota), Some((3, 2)));
+        assert_eq!(limiter.check("key:a", "/transaction/build").ok().and_then(|usage| usage.quota), Some((3, 1)));
+        // Refused by the bucket, so not charged to the quota.
+        assert_eq!(refused(limiter.check("key:a", "/transaction/build")).0, ErrorCode::RateLimited);
+        assert_eq!(limiter.quotas.lock().unwrap().file.used.get("default|key:a"), Some(&2));
+
+        limiter.buckets.lock().unwrap().clear();
+        assert!(limiter.check("key:a", "/transaction/build").is_ok());
+        let (code, retry_after) = refused(limiter.check("key:a", "/transaction/build"));
+        assert_eq!(code, ErrorCode::QuotaExceeded);
+        assert!((1..=SECONDS_PER_DAY).contains(&retry_after));
+        assert_eq!(limiter.quotas.lock().unwrap().file.used.get("default|key:a"), Some(&3));
+        assert!(limiter.check("key:b", "/transaction/build").is_ok());
+    }
+
+    #[test]
+    fn quota_usage_survives_a_restart_within_the_day() {
+        let config = config(10, 2);
+        let limiter = RateLimiter::from_config(&config).unwrap();
+        assert!(limiter.check("key:a", "/transaction/build").is_ok());
+        assert!(limiter.check("key:a", "/transaction/build").is_ok());
+        limiter.save_quotas().unwrap();
+
+        let restarted = RateLimiter::from_config(&config).unwrap();
+        assert_eq!(refused(restarted.check("key:a", "/transaction/build")).0, ErrorCode::QuotaExceeded);
+
+        // Usage from an earlier day is dropped.
+        let used = restarted.quotas.lock().unwrap().file.used.clone();
+        let stale = QuotaFile { version: QUOTA_FILE_VERSION, day: today() - 1, used };
+        fs::write(&config.quota_path, serde_json::to_vec(&stale).unwrap()).unwrap();
+        let next_day = RateLimiter::from_config(&config).unwrap();
+        assert!(next_day.check("key:a", "/transaction/build").is_ok());
+
+        fs::write(&config.quota_path, br#"{"version":99,"day":0,"used":{}}"#).unwrap();
+        let error = RateLimiter::from_config(&config).err();
+        assert_eq!(error.as_deref(), Some("unsupported quota file version 99"));
+        fs::remove_file(&config.quota_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn middleware_answers_429_with_retry_after() {
+        let limiter = Arc::new(RateLimiter::from_config(&config(1, 0)).unwrap());
+        let app = Router::new()
+            .route("/transaction/build", post(|| async { "ok" }))
+            .route("/health", post(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(limiter, limit));
+        let send = |path: &'static str| {
+            let app = app.clone();
+            async move {
+                let mut request = axum::http::Request::post(path).body(Body::empty()).unwrap();
+                request.extensions_mut().insert(ConnectInfo(SocketAddr::from(([192, 0, 2, 1], 4000))));
+                app.oneshot(request).await.unwrap()
+            }
+        };
+
+        let allowed = send("/transaction/build").await;
+        assert_eq!(allowed.status(), StatusCode::OK);
+        assert_eq!(allowed.headers()["x-ratelimit-limit"], "1");
+        assert_eq!(allowed.headers()["x-ratelimit-remaining"], "0");
+        assert!(allowed.headers().get(RETRY_AFTER).is_none());
+
+        let limited = send("/transaction/build").await;
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(limited.headers()[RETRY_AFTER].to_str().unwrap().parse::<u64>().unwrap() >= 1);
+        assert_eq!(limited.headers()["x-ratelimit-remaining"], "0");
+
+        // Public paths are never limited.
+        for _ in 0..3 {
+            assert_eq!(send("/health").await.status(), StatusCode::OK);
+        }
+    }
+}
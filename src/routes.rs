@@ -1,13 +1,152 @@
-use axum::{Router, routing::post};
-use crate::handlers::{keypair::*, token::*, message::*, send::*};
+use axum::{Router, middleware, routing::{delete, get, post}};
+use crate::handlers::{keypair::*, token::*, message::*, send::*, pda::*, pubkey::*, vanity::*, vault::*, transaction::*, instruction::*, siws::*, admin::*, metrics::*};
+use crate::audit;
+use crate::auth::{require_scope, Scope};
+use crate::config::FeatureToggles;
+use crate::error::not_found;
+use crate::openapi;
+use crate::state::AppState;
 
-pub fn app_router() -> Router {
-    Router::new()
-        .route("/keypair", post(generate_keypair))
+/// Routes are grouped by the API key scope they need; keep
+/// `openapi::document` in step when moving a route between groups. Calls to
+/// the sign, keygen and admin groups, including refused ones, are audited.
+pub fn app_router(state: AppState, features: &FeatureToggles) -> Router {
+    let mut build = Router::new()
         .route("/token/create", post(create_token))
         .route("/token/mint", post(mint_token))
-        .route("/message/sign", post(sign_message))
         .route("/message/verify", post(verify_message))
+        .route("/message/verify/batch", post(verify_message_batch))
+        .route("/message/offchain/verify", post(verify_offchain_message))
         .route("/send/sol", post(send_sol))
         .route("/send/token", post(send_token))
-} 
\ No newline at end of file
+        .route("/transaction/verify", post(verify_transaction))
+        .route("/transaction/decode", post(decode_transaction))
+        .route("/instruction/decode", post(decode_instruction))
+        .route("/pda/find", post(find_pda))
+        .route("/pda/create", post(create_pda))
+        .route("/pubkey/inspect", post(inspect_pubkey));
+    let sign = Router::new()
+        .route("/message/sign", post(sign_message))
+        .route("/message/offchain/sign", post(sign_offchain_message))
+        .route("/transaction/sign", post(sign_transaction));
+    let mut keygen = Router::new()
+        .route("/keypair", post(generate_keypair))
+        .route("/keypair/export", post(export_keypair))
+        .route("/keypair/import", post(import_keypair))
+        .route("/keypair/decrypt", post(decrypt_keystore));
+    let mut admin = Router::new()
+        .route("/admin/api-keys", get(list_api_keys).post(create_api_key))
+        .route("/admin/api-keys/:key_id", delete(delete_api_key));
+
+    if features.vanity {
+        keygen = keygen
+            .route("/keypair/vanity", post(start_vanity))
+            .route("/keypair/vanity/:job_id", get(vanity_status).delete(cancel_vanity));
+    }
+    if features.siws {
+        build = build
+            .route("/siws/nonce", post(siws_nonce))
+            .route("/siws/message", post(build_siws_message))
+            .route("/siws/verify", post(verify_siws));
+    }
+    if features.vault {
+        keygen = keygen
+            .route("/vault/keys", get(list_vault_keys).post(create_vault_key))
+            .route("/vault/keys/:key_id", get(get_vault_key))
+            .route("/vault/keys/:key_id/rotate", post(rotate_vault_key));
+        admin = admin
+            .route("/vault/keys/:key_id", delete(delete_vault_key))
+            .route("/vault/keys/:key_id/disable", post(disable_vault_key))
+            .route("/vault/keys/:key_id/enable", post(enable_vault_key));
+    }
+
+    let scoped = |router: Router<AppState>, scope: Scope| {
+        router.route_layer(middleware::from_fn_with_state(scope, require_scope))
+    };
+    let audit_log = state.audit.clone();
+    let audited = |router: Router<AppState>| {
+        router.route_layer(middleware::from_fn_with_state(audit_log.clone(), audit::record))
+    };
+    let mut router = Router::new()
+        .merge(scoped(build, Scope::Build))
+        .merge(audited(scoped(sign, Scope::Sign)))
+        .merge(audited(scoped(keygen, Scope::Keygen)))
+        .merge(audited(scoped(admin, Scope::Admin)));
+    // A route layer needs at least one route, so the group is only built when enabled.
+    if features.metrics {
+        router = router.merge(scoped(Router::new().route("/metrics", get(export_metrics)), Scope::Metrics));
+    }
+    router.merge(openapi::router(features)).fallback(not_found).with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Method, Request, StatusCode};
+    use std::collections::BTreeSet;
+    use tower::ServiceExt;
+
+    const ALL_OFF: FeatureToggles = FeatureToggles { vault: false, vanity: false, siws: false, metrics: false };
+
+    /// (method, path) pairs in the OpenAPI document, minus `/health`, which
+    /// `main` adds.
+    fn documented(features: &FeatureToggles) -> BTreeSet<(Method, String)> {
+        let document = openapi::document(features);
+        let paths = document["paths"].as_object().expect("paths");
+        paths
+            .iter()
+            .filter(|(path, _)| *path != "/health")
+            .flat_map(|(path, item)| {
+                let methods = item.as_object().expect("path item").keys();
+                methods.map(move |method| (method.to_uppercase().parse().expect("method"), path.clone()))
+            })
+            .collect()
+    }
+
+    /// Sends `method path` with `{param}`s filled in; returns the status and
+    /// whether the router's fallback answered.
+    async fn probe(router: &Router, method: &Method, path: &str) -> (StatusCode, bool) {
+        let segments: Vec<&str> = path.split('/').map(|s| if s.starts_with('{') { "x" } else { s }).collect();
+        let uri = segments.join("/");
+        let request = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).contains("No such endpoint"))
+    }
+
+    #[tokio::test]
+    async fn router_serves_exactly_the_documented_operations() {
+        for features in [FeatureToggles::default(), ALL_OFF] {
+            let router = app_router(AppState::default(), &features);
+            let operations = documented(&features);
+            for (method, path) in &operations {
+                let (status, fallback) = probe(&router, method, path).await;
+                assert!(!fallback && status != StatusCode::METHOD_NOT_ALLOWED, "{} {} is not routed", method, path);
+            }
+            let paths: BTreeSet<&String> = operations.iter().map(|(_, path)| path).collect();
+            for path in paths {
+                for method in [Method::GET, Method::POST, Method::DELETE] {
+                    if !operations.contains(&(method.clone(), path.clone())) {
+                        let (status, _) = probe(&router, &method, path).await;
+                        let undocumented = format!("{} {} is routed but undocumented", method, path);
+                        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED, "{}", undocumented);
+                    }
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn disabled_features_are_neither_routed_nor_documented() {
+        let router = app_router(AppState::default(), &ALL_OFF);
+        let all_on = documented(&FeatureToggles::default());
+        let disabled: Vec<_> = all_on.difference(&documented(&ALL_OFF)).cloned().collect();
+        assert!(disabled.iter().any(|(_, path)| path == "/vault/keys"));
+        for (method, path) in disabled {
+            let (status, fallback) = probe(&router, &method, &path).await;
+            assert!(fallback && status == StatusCode::NOT_FOUND, "{} {} is routed while disabled", method, path);
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::keystore::random_bytes;
+use crate::utils::validate_pubkey;
+
+const HEADER_SUFFIX: &str = " wants you to sign in with your Solana account:";
+pub const MESSAGE_VERSION: &str = "1";
+/// CAIP-122 requires nonces of at least 8 alphanumeric characters.
+const MIN_NONCE_LEN: usize = 8;
+
+/// How long an issued nonce can be used to sign in.
+pub const NONCE_TTL: Duration = Duration::from_secs(10 * 60);
+/// Cap on outstanding nonces, so unauthenticated callers can't grow the store
+/// without bound.
+const MAX_OUTSTANDING_NONCES: usize = 100_000;
+
+/// A Sign-In With Solana message (the Solana flavour of CAIP-122), in the text
+/// layout wallets display and sign.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SiwsMessage {
+    pub domain: String,
+    pub address: String,
+    pub statement: Option<String>,
+    pub uri: String,
+    pub version: String,
+    pub chain_id: Option<String>,
+    pub nonce: String,
+    pub issued_at: String,
+    pub expiration_time: Option<String>,
+    pub not_before: Option<String>,
+    pub request_id: Option<String>,
+    pub resources: Vec<String>,
+}
+
+pub fn now_rfc3339() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+pub fn parse_time(value: &str, field: &str) -> Result<DateTime<FixedOffset>, String> {
+    DateTime::parse_from_rfc3339(value).map_err(|e| format!("Invalid {}: {}", field, e))
+}
+
+fn single_line(value: &str, field: &str) -> Result<(), String> {
+    if value.contains('\n') || value.contains('\r') {
+        return Err(format!("{} must be a single line", field));
+    }
+    Ok(())
+}
+
+impl SiwsMessage {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.domain.is_empty() || self.domain.contains(char::is_whitespace) {
+            return Err("domain must be a non-empty host without whitespace".to_string());
+        }
+        validate_pubkey(&self.address).map_err(|e| format!("Invalid address: {}", e))?;
+        if let Some(statement) = &self.statement {
+            single_line(statement, "statement")?;
+        }
+        if self.uri.is_empty() {
+            return Err("uri is required".to_string());
+        }
+        single_line(&self.uri, "uri")?;
+        if self.version != MESSAGE_VERSION {
+            return Err(format!("Unsupported version '{}'", self.version));
+        }
+        if let Some(chain_id) = &self.chain_id {
+            single_line(chain_id, "chain_id")?;
+        }
+        if self.nonce.len() < MIN_NONCE_LEN || !self.nonce.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("nonce must be at least {} alphanumeric characters", MIN_NONCE_LEN));
+        }
+        parse_time(&self.issued_at, "issued_at")?;
+        if let Some(expiration_time) = &self.expiration_time {
+            parse_time(expiration_time, "expiration_time")?;
+        }
+        if let Some(not_before) = &self.not_before {
+            parse_time(not_before, "not_before")?;
+        }
+        if let Some(request_id) = &self.request_id {
+            single_line(request_id, "request_id")?;
+        }
+        for resource in &self.resources {
+            single_line(resource, "resources")?;
+        }
+        Ok(())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}{}\n{}", self.domain, HEADER_SUFFIX, self.address);
+        if let Some(statement) = &self.statement {
+            text.push_str("\n\n");
+            text.push_str(statement);
+        }
+
+        let mut fields = vec![
+            format!("URI: {}", self.uri),
+            format!("Version: {}", self.version),
+        ];
+        if let Some(chain_id) = &self.chain_id {
+            fields.push(format!("Chain ID: {}", chain_id));
+        }
+        fields.push(format!("Nonce: {}", self.nonce));
+        fields.push(format!("Issued At: {}", self.issued_at));
+        if let Some(expiration_time) = &self.expiration_time {
+            fields.push(format!("Expiration Time: {}", expiration_time));
+        }
+        if let Some(not_before) = &self.not_before {
+            fields.push(format!("Not Before: {}", not_before));
+        }
+        if let Some(request_id) = &self.request_id {
+            fields.push(format!("Request ID: {}", request_id));
+        }
+        if !self.resources.is_empty() {
+            fields.push("Resources:".to_string());
+            fields.extend(self.resources.iter().map(|r| format!("- {}", r)));
+        }
+
+        text.push_str("\n\n");
+        text.push_str(&fields.join("\n"));
+        text
+    }
+
+    /// Parses a message produced by `to_text` or a wallet using the same layout.
+    /// Fields must appear in the standard order.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.split('\n').peekable();
+        let domain = lines
+            .next()
+            .and_then(|line| line.strip_suffix(HEADER_SUFFIX))
+            .ok_or("Message does not start with a Sign-In With Solana header")?
+            .to_string();
+        let address = lines.next().ok_or("Message is missing the address")?.to_string();
+
+        if lines.next() != Some("") {
+            return Err("Expected a blank line after the address".to_string());
+        }
+        let statement = match lines.peek() {
+            Some(line) if !line.starts_with("URI: ") => {
+                let statement = line.to_string();
+                lines.next();
+                if lines.next() != Some("") {
+                    return Err("Expected a blank line after the statement".to_string());
+                }
+                Some(statement)
+            }
+            _ => None,
+        };
+
+        let mut field = |name: &str, required: bool| -> Result<Option<String>, String> {
+            let prefix = format!("{}: ", name);
+            match lines.peek().and_then(|line| line.strip_prefix(prefix.as_str())) {
+                Some(value) => {
+                    let value = value.to_string();
+                    lines.next();
+                    Ok(Some(value))
+                }
+                None if required => Err(format!("Message is missing '{}'", name)),
+                None => Ok(None),
+            }
+        };
+        let uri = field("URI", true)?.unwrap_or_default();
+        let version = field("Version", true)?.unwrap_or_default();
+        let chain_id = field("Chain ID", false)?;
+        let nonce = field("Nonce", true)?.unwrap_or_default();
+        let issued_at = field("Issued At", true)?.unwrap_or_default();
+        let expiration_time = field("Expiration Time", false)?;
+        let not_before = field("Not Before", false)?;
+        let request_id = field("Request ID", false)?;
+
+        let mut resources = Vec::new();
+        if lines.peek() == Some(&"Resources:") {
+            lines.next();
+            while let Some(resource) = lines.peek().and_then(|line| line.strip_prefix("- ")) {
+                resources.push(resource.to_string());
+                lines.next();
+            }
+        }
+        if let Some(line) = lines.next() {
+            return Err(format!("Unexpected line in message: '{}'", line));
+        }
+
+        let message = Self {
+            domain,
+            address,
+            statement,
+            uri,
+            version,
+            chain_id,
+            nonce,
+            issued_at,
+            expiration_time,
+            not_before,
+            request_id,
+            resources,
+        };
+        message.validate()?;
+        Ok(message)
+    }
+}
+
+/// Nonces issued for sign-in messages. Each one can be redeemed once, within
+/// `NONCE_TTL`, which is what stops a signed message from being replayed.
+#[derive(Default)]
+pub struct SiwsNonces {
+    nonces: Mutex<HashMap<String, Instant>>,
+}
+
+impl SiwsNonces {
+    /// Issues a fresh nonce and returns it with its time to live.
+    pub fn issue(&self) -> Result<(String, Duration), String> {
+        let mut nonces = self.nonces.lock().unwrap();
+        let now = Instant::now();
+        nonces.retain(|_, expires| *expires > now);
+        if nonces.len() >= MAX_OUTSTANDING_NONCES {
+            return Err("Too many outstanding nonces, try again later".to_string());
+        }
+        let nonce = hex::encode(random_bytes::<16>());
+        nonces.insert(nonce.clone(), now + NONCE_TTL);
+        Ok((nonce, NONCE_TTL))
+    }
+
+    /// Removes `nonce`, returning whether it was outstanding and unexpired.
+    pub fn consume(&self, nonce: &str) -> bool {
+        self.nonces
+            .lock()
+            .unwrap()
+            .remove(nonce)
+            .is_some_and(|expires| expires > Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT";
+
+    fn minimal() -> SiwsMessage {
+        SiwsMessage {
+            domain: "example.com".to_string(),
+            address: ADDRESS.to_string(),
+            statement: None,
+            uri: "https://example.com/login".to_string(),
+            version: MESSAGE_VERSION.to_string(),
+            chain_id: None,
+            nonce: "43a3195399c4c44f".to_string(),
+            issued_at: "2026-01-01T00:00:00.000Z".to_string(),
+            expiration_time: None,
+            not_before: None,
+            request_id: None,
+            resources: Vec::new(),
+        }
+    }
+
+    fn full() -> SiwsMessage {
+        SiwsMessage {
+            statement: Some("Sign in to Example".to_string()),
+            chain_id: Some("mainnet".to_string()),
+            expiration_time: Some("2026-01-01T01:00:00Z".to_string()),
+            not_before: Some("2026-01-01T00:00:00Z".to_string()),
+            request_id: Some("req-1".to_string()),
+            resources: vec!["https://example.com/tos".to_string(), "ipfs://bafy".to_string()],
+            ..minimal()
+        }
+    }
+
+    #[test]
+    fn formats_the_standard_layout() {
+        let expected = format!(
+            "example.com wants you to sign in with your Solana account:\n{}\n\nSign in to Example\n\n\
+             URI: https://example.com/login\nVersion: 1\nChain ID: mainnet\nNonce: 43a3195399c4c44f\n\
+             Issued At: 2026-01-01T00:00:00.000Z\nExpiration Time: 2026-01-01T01:00:00Z\n\
+             Not Before: 2026-01-01T00:00:00Z\nRequest ID: req-1\n\
+             Resources:\n- https://example.com/tos\n- ipfs://bafy",
+            ADDRESS
+        );
+        assert_eq!(full().to_text(), expected);
+    }
+
+    #[test]
+    fn parses_what_it_formats() {
+        for message in [minimal(), full()] {
+            assert_eq!(SiwsMessage::parse(&message.to_text()).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        let text = minimal().to_text();
+        assert!(SiwsMessage::parse(&text.replace("wants you", "would like you")).is_err());
+        assert!(SiwsMessage::parse(&text.replace("Version: 1", "Version: 2")).is_err());
+        assert!(SiwsMessage::parse(&text.replace("Nonce: 43a3195399c4c44f", "Nonce: short")).is_err());
+        assert!(SiwsMessage::parse(&text.replace(ADDRESS, "not-an-address")).is_err());
+        assert!(SiwsMessage::parse(&format!("{}\nExtra: line", text)).is_err());
+        // Fields out of the standard order.
+        let swapped = text.replace("URI: https://example.com/login\nVersion: 1", "Version: 1\nURI: https://example.com/login");
+        assert!(SiwsMessage::parse(&swapped).is_err());
+    }
+
+    #[test]
+    fn rejects_fields_that_would_inject_lines() {
+        let message = SiwsMessage { statement: Some("hi\nNonce: 00000000".to_string()), ..minimal() };
+        assert!(message.validate().is_err());
+        let message = SiwsMessage { resources: vec!["a\r\nb".to_string()], ..minimal() };
+        assert!(message.validate().is_err());
+    }
+
+    #[test]
+    fn nonces_are_single_use() {
+        let nonces = SiwsNonces::default();
+        let (nonce, ttl) = nonces.issue().unwrap();
+        assert_eq!(ttl, NONCE_TTL);
+        assert!(nonces.consume(&nonce));
+        assert!(!nonces.consume(&nonce));
+        assert!(!nonces.consume("never-issued"));
+    }
+}
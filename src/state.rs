@@ -0,0 +1,51 @@
+use axum::extract::FromRef;
+use std::sync::Arc;
+use crate::audit::AuditLog;
+use crate::auth::ApiKeyStore;
+use crate::handlers::vanity::VanityJobs;
+use crate::metrics::Metrics;
+use crate::siws::SiwsNonces;
+use crate::vault::KeyVault;
+
+/// Shared state for handlers that need more than their request body.
+/// Each subsystem is exposed to handlers through `FromRef`, so a handler only
+/// asks for the piece it uses, e.g. `State(jobs): State<Arc<VanityJobs>>`.
+#[derive(Clone, Default)]
+pub struct AppState {
+    pub vanity: Arc<VanityJobs>,
+    pub vault: Arc<KeyVault>,
+    pub siws: Arc<SiwsNonces>,
+    pub api_keys: Arc<ApiKeyStore>,
+    pub metrics: Arc<Metrics>,
+    pub audit: Arc<AuditLog>,
+}
+
+impl FromRef<AppState> for Arc<VanityJobs> {
+    fn from_ref(state: &AppState) -> Self {
+        state.vanity.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<KeyVault> {
+    fn from_ref(state: &AppState) -> Self {
+        state.vault.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<SiwsNonces> {
+    fn from_ref(state: &AppState) -> Self {
+        state.siws.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ApiKeyStore> {
+    fn from_ref(state: &AppState) -> Self {
+        state.api_keys.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Metrics> {
+    fn from_ref(state: &AppState) -> Self {
+        state.metrics.clone()
+    }
+}
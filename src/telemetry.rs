@@ -0,0 +1,259 @@
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request},
+    http::{HeaderMap, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, TracerProvider};
+use opentelemetry_sdk::Resource;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use std::net::SocketAddr;
+use std::time::Instant;
+use crate::auth::Caller;
+use crate::config::{LogConfig, LogFormat, TelemetryConfig};
+use crate::error::ErrorCode;
+use crate::keystore::random_bytes;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Longest client-supplied request ID that is kept rather than replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// The ID of the request being served, for error responses.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Installs the log subscriber and, when `otlp_endpoint` is set, a layer
+/// exporting spans over OTLP/gRPC. The returned provider must be kept alive
+/// for spans to be exported.
+pub fn init(log: &LogConfig, telemetry: &TelemetryConfig) -> Result<Option<TracerProvider>, String> {
+    let provider = match &telemetry.otlp_endpoint {
+        Some(endpoint) => {
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_tonic()
+                .with_endpoint(endpoint.clone())
+                .build()
+                .map_err(|e| format!("cannot create OTLP exporter for {}: {}", endpoint, e))?;
+            let provider = TracerProvider::builder()
+                .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+                .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(telemetry.sample_ratio))))
+                .with_resource(Resource::new([
+                    KeyValue::new("service.name", telemetry.service_name.clone()),
+                    KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+                ]))
+                .build();
+            Some(provider)
+        }
+        None => None,
+    };
+    let otel_layer = provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer(env!("CARGO_PKG_NAME"))));
+
+    let (text_layer, json_layer) = match log.format {
+        LogFormat::Text => (Some(tracing_subscriber::fmt::layer()), None),
+        LogFormat::Json => (None, Some(tracing_subscriber::fmt::layer().json().flatten_event(true).with_span_list(false))),
+    };
+
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::EnvFilter::new(&log.level))
+        .with(text_layer)
+        .with(json_layer)
+        .with(otel_layer)
+        .try_init()
+        .map_err(|e| e.to_string())?;
+    Ok(provider)
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// Keeps a client's `X-Request-Id` if it is short, printable ASCII; otherwise
+/// a new one is generated.
+fn request_id(headers: &HeaderMap) -> String {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic()))
+        .map_or_else(|| hex::encode(random_bytes::<16>()), String::from)
+}
+
+/// Wraps each request in a span carrying its request ID, continuing the
+/// caller's trace when a W3C `traceparent` header is present. The ID is
+/// echoed in `X-Request-Id` and included in error bodies.
+pub async fn request_context(request: Request, next: Next) -> Response {
+    let request_id = request_id(request.headers());
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+
+    let span = tracing::info_span!(
+        "request",
+        otel.name = format!("{} {}", method, route),
+        otel.kind = "server",
+        otel.status_code = tracing::field::Empty,
+        http.request.method = %method,
+        http.route = %route,
+        http.response.status_code = tracing::field::Empty,
+        request_id = %request_id,
+        trace_id = tracing::field::Empty,
+    );
+    span.set_parent(TraceContextPropagator::new().extract(&HeaderExtractor(request.headers())));
+    let span_context = span.context().span().span_context().clone();
+    if span_context.is_valid() {
+        span.record("trace_id", tracing::field::display(span_context.trace_id()));
+    }
+
+    let mut response = REQUEST_ID
+        .scope(request_id.clone(), next.run(request).instrument(span.clone()))
+        .await;
+    span.record("http.response.status_code", response.status().as_u16());
+    if response.status().is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Logs one `access` event per request. Bodies and query strings are left
+/// out, so secrets sent to the API never reach the log; the caller comes
+/// from the `Caller` that `auth::authenticate` puts on the response.
+pub async fn access_log(request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let client_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(peer)| peer.ip().to_string());
+
+    let response = next.run(request).await;
+    let api_key_id = response.extensions().get::<Caller>().map(|Caller(caller)| caller.id.clone());
+    let error_code = response.extensions().get::<ErrorCode>().map(|code| code.name());
+    tracing::info!(
+        target: "access",
+        method = %method,
+        path = %path,
+        status = response.status().as_u16(),
+        latency_ms = started.elapsed().as_secs_f64() * 1000.0,
+        client_ip = client_ip.as_deref(),
+        api_key_id = api_key_id.as_deref(),
+        error_code = error_code.as_deref(),
+        "{} {} {}",
+        method,
+        path,
+        response.status().as_u16()
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ApiError;
+    use axum::{body::{to_bytes, Body}, middleware, routing::get, Router};
+    use opentelemetry::trace::TraceId;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    fn app() -> Router {
+        Router::new()
+            .route("/ok", get(|| async { "ok" }))
+            .route("/fail", get(|| async { ApiError::invalid_request("nope") }))
+            .route(
+                "/trace",
+                get(|| async { tracing::Span::current().context().span().span_context().trace_id().to_string() }),
+            )
+            .layer(middleware::from_fn(request_context))
+    }
+
+    async fn call(path: &str, headers: &[(&str, &str)]) -> Response {
+        let mut request = Request::get(path);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        app().oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    fn echoed_id(response: &Response) -> String {
+        response.headers()[REQUEST_ID_HEADER].to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn valid_request_id_is_kept_and_echoed() {
+        let response = call("/ok", &[(REQUEST_ID_HEADER, "client-id-42")]).await;
+        assert_eq!(echoed_id(&response), "client-id-42");
+    }
+
+    #[tokio::test]
+    async fn unusable_request_ids_are_replaced() {
+        let oversized = "a".repeat(MAX_REQUEST_ID_LEN + 1);
+        for id in [oversized.as_str(), "has space", "tab\there", ""] {
+            let response = call("/ok", &[(REQUEST_ID_HEADER, id)]).await;
+            let echoed = echoed_id(&response);
+            assert_ne!(echoed, id);
+            assert_eq!(echoed.len(), 32);
+            assert!(echoed.bytes().all(|b| b.is_ascii_hexdigit()));
+        }
+
+        let generated = echoed_id(&call("/ok", &[]).await);
+        assert_ne!(generated, echoed_id(&call("/ok", &[]).await));
+    }
+
+    #[tokio::test]
+    async fn error_bodies_carry_the_request_id() {
+        let response = call("/fail", &[(REQUEST_ID_HEADER, "trace-me")]).await;
+        assert_eq!(echoed_id(&response), "trace-me");
+        let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["code"], "INVALID_REQUEST");
+        assert_eq!(body["request_id"], "trace-me");
+
+        assert_eq!(current_request_id(), None);
+    }
+
+    #[tokio::test]
+    async fn traceparent_continues_the_callers_trace() {
+        let provider = TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let traceparent = format!("00-{}-00f067aa0ba902b7-01", TRACE_ID);
+        let response = call("/trace", &[("traceparent", &traceparent)]).await;
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(std::str::from_utf8(&body).unwrap(), TRACE_ID);
+
+        let response = call("/trace", &[]).await;
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let trace_id = std::str::from_utf8(&body).unwrap();
+        assert_ne!(trace_id, TRACE_ID);
+        assert_ne!(trace_id, TraceId::INVALID.to_string());
+    }
+}
@@ -0,0 +1,489 @@
+use axum::{extract::{ConnectInfo, Request}, Router};
+use hyper::body::Incoming;
+use hyper_util::rt::TokioIo;
+use hyper_util::server::graceful::GracefulShutdown;
+use std::future::Future;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::server::{AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient};
+use tokio_rustls::rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tower::ServiceExt;
+use x509_parser::prelude::{FromDer, X509Certificate, X509Name};
+use crate::auth::Scope;
+use crate::config::{ClientAuth, ClientIdentityConfig, TlsConfig};
+
+/// Connections that have not finished the TLS handshake by then are dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The verified client certificate of a mutual TLS connection, attached to
+/// each request on that connection as an extension.
+#[derive(Clone, Debug)]
+pub struct ClientIdentity {
+    /// Subject attributes in certificate order with RFC 4514 escaping, e.g.
+    /// `O=Acme,CN=ops-client`.
+    pub subject: String,
+    /// The name `subject` maps to in `tls.client_identities`, if any.
+    pub identity: Option<String>,
+    /// Scopes of the mapped identity, used for requests without an API key.
+    pub scopes: Vec<Scope>,
+}
+
+impl ClientIdentity {
+    pub fn allows(&self, scope: Scope) -> bool {
+        scope.granted_by(&self.scopes)
+    }
+}
+
+/// The active rustls config, swapped out whenever the certificate, key or
+/// client CA files change on disk.
+pub struct TlsState {
+    settings: TlsConfig,
+    server_config: RwLock<Arc<ServerConfig>>,
+}
+
+impl TlsState {
+    pub fn load(settings: TlsConfig) -> Result<Self, String> {
+        let server_config = build_server_config(&settings)?;
+        Ok(Self {
+            settings,
+            server_config: RwLock::new(Arc::new(server_config)),
+        })
+    }
+
+    fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.server_config.read().unwrap().clone())
+    }
+
+    fn watched_paths(&self) -> Vec<&PathBuf> {
+        [&self.settings.cert_path, &self.settings.key_path, &self.settings.client_ca_path]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Polls the certificate files and reloads them after a change. A broken
+    /// replacement is logged and the previous certificate stays in use.
+    pub fn spawn_reloader(self: Arc<Self>) {
+        let interval = Duration::from_secs(self.settings.reload_interval_secs);
+        tokio::spawn(async move {
+            let mut last_modified = modified_times(&self.watched_paths());
+            loop {
+                tokio::time::sleep(interval).await;
+                let modified = modified_times(&self.watched_paths());
+                if modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                match build_server_config(&self.settings) {
+                    Ok(server_config) => {
+                        *self.server_config.write().unwrap() = Arc::new(server_config);
+                        tracing::info!("Reloaded TLS certificates");
+                    }
+                    Err(e) => tracing::error!("Failed to reload TLS certificates, keeping the previous ones: {}", e),
+                }
+            }
+        });
+    }
+}
+
+fn modified_times(paths: &[&PathBuf]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok())
+        .collect()
+}
+
+fn read_pem(path: &Path) -> Result<Vec<rustls_pemfile::Item>, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("cannot open {}: {}", path.display(), e))?;
+    rustls_pemfile::read_all(&mut BufReader::new(file))
+        .map_err(|e| format!("cannot parse {}: {}", path.display(), e))
+}
+
+fn read_certs(path: &Path) -> Result<Vec<Certificate>, String> {
+    let certs: Vec<Certificate> = read_pem(path)?
+        .into_iter()
+        .filter_map(|item| match item {
+            rustls_pemfile::Item::X509Certificate(der) => Some(Certificate(der)),
+            _ => None,
+        })
+        .collect();
+    if certs.is_empty() {
+        return Err(format!("no certificates found in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+fn read_key(path: &Path) -> Result<PrivateKey, String> {
+    read_pem(path)?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(der)
+            | rustls_pemfile::Item::RSAKey(der)
+            | rustls_pemfile::Item::ECKey(der) => Some(PrivateKey(der)),
+            _ => None,
+        })
+        .ok_or_else(|| format!("no private key found in {}", path.display()))
+}
+
+fn build_server_config(settings: &TlsConfig) -> Result<ServerConfig, String> {
+    let (Some(cert_path), Some(key_path)) = (&settings.cert_path, &settings.key_path) else {
+        return Err("tls.cert_path and tls.key_path are required".to_string());
+    };
+    let certs = read_certs(cert_path)?;
+    let key = read_key(key_path)?;
+
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = match &settings.client_ca_path {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(ca_path)? {
+                roots
+                    .add(&cert)
+                    .map_err(|e| format!("invalid client CA in {}: {}", ca_path.display(), e))?;
+            }
+            let verifier = match settings.client_auth {
+                ClientAuth::Required => AllowAnyAuthenticatedClient::new(roots).boxed(),
+                ClientAuth::Optional => AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed(),
+            };
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut server_config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("invalid certificate or key: {}", e))?;
+    // hyper is built with HTTP/1 only.
+    server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(server_config)
+}
+
+/// Accepts TLS connections and serves `app` on each until `shutdown`
+/// completes, then stops accepting and waits for open connections to finish
+/// their requests. Client certificates are already verified by rustls; here
+/// they are only mapped to identities.
+pub async fn serve(listener: TcpListener, app: Router, tls: Arc<TlsState>, shutdown: impl Future<Output = ()>) {
+    // `Config::validate` has already checked that every subject parses.
+    let identities: Arc<Vec<(Subject, ClientIdentityConfig)>> = Arc::new(
+        tls.settings
+            .client_identities
+            .iter()
+            .filter_map(|(subject, mapped)| Some((parse_subject(subject).ok()?, mapped.clone())))
+            .collect(),
+    );
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(shutdown);
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = &mut shutdown => break,
+        };
+        let (stream, peer) = match accepted {
+            Ok(connection) => connection,
+            Err(e) => {
+                tracing::warn!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        let acceptor = tls.acceptor();
+        let identities = identities.clone();
+        let app = app.clone();
+        // Taken before the handshake, so a shutdown that starts meanwhile still
+        // reaches the connection.
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            let stream = match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    tracing::debug!("TLS handshake with {} failed: {}", peer, e);
+                    return;
+                }
+                Err(_) => {
+                    tracing::debug!("TLS handshake with {} timed out", peer);
+                    return;
+                }
+            };
+            let client = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(|cert| certificate_subject(&cert.0).and_then(|subject| client_identity(subject, &identities)));
+            let client = match client {
+                Some(Ok(client)) => {
+                    tracing::debug!(
+                        "Client certificate from {}: subject '{}', identity {:?}",
+                        peer, client.subject, client.identity
+                    );
+                    Some(client)
+                }
+                Some(Err(e)) => {
+                    tracing::warn!("Refusing client certificate from {}: {}", peer, e);
+                    return;
+                }
+                None => None,
+            };
+
+            let service = hyper::service::service_fn(move |mut request: Request<Incoming>| {
+                request.extensions_mut().insert(ConnectInfo(peer));
+                if let Some(client) = &client {
+                    request.extensions_mut().insert(client.clone());
+                }
+                app.clone().oneshot(request)
+            });
+            let connection = hyper::server::conn::http1::Builder::new().serve_connection(TokioIo::new(stream), service);
+            if let Err(e) = watcher.watch(connection).await {
+                tracing::debug!("Connection from {} closed with error: {}", peer, e);
+            }
+        });
+    }
+
+    drop(listener);
+    tracing::info!("Shutting down; waiting for {} open connections", graceful.count());
+    graceful.shutdown().await;
+}
+
+fn client_identity(subject: Subject, identities: &[(Subject, ClientIdentityConfig)]) -> Result<ClientIdentity, String> {
+    let text = format_subject(&subject);
+    let Some((_, mapped)) = identities.iter().find(|(listed, _)| *listed == subject) else {
+        if identities.is_empty() {
+            return Ok(ClientIdentity { subject: text, identity: None, scopes: Vec::new() });
+        }
+        return Err(format!("subject '{}' is not in tls.client_identities", text));
+    };
+    Ok(ClientIdentity {
+        subject: text,
+        identity: Some(mapped.identity.clone()),
+        scopes: mapped.scopes.clone(),
+    })
+}
+
+/// A distinguished name: its relative distinguished names in certificate
+/// order, each holding one or more (attribute type, value) pairs.
+pub type Subject = Vec<Vec<(String, String)>>;
+
+/// Short names for the usual subject attributes; others go by dotted OID.
+const ATTRIBUTE_NAMES: &[(&str, &str)] = &[
+    ("2.5.4.3", "CN"),
+    ("2.5.4.6", "C"),
+    ("2.5.4.7", "L"),
+    ("2.5.4.8", "ST"),
+    ("2.5.4.10", "O"),
+    ("2.5.4.11", "OU"),
+    ("1.2.840.113549.1.9.1", "emailAddress"),
+];
+
+fn certificate_subject(der: &[u8]) -> Result<Subject, String> {
+    let (_, certificate) =
+        X509Certificate::from_der(der).map_err(|e| format!("unreadable client certificate: {}", e))?;
+    name_attributes(certificate.subject())
+}
+
+fn name_attributes(name: &X509Name) -> Result<Subject, String> {
+    name.iter()
+        .map(|rdn| {
+            rdn.iter()
+                .map(|attribute| {
+                    let oid = attribute.attr_type().to_id_string();
+                    let kind = ATTRIBUTE_NAMES
+                        .iter()
+                        .find(|(id, _)| *id == oid)
+                        .map_or(oid.clone(), |(_, short)| short.to_string());
+                    let value = attribute
+                        .as_str()
+                        .map_err(|_| format!("subject attribute {} is not a readable string", kind))?;
+                    Ok((kind, value.to_string()))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Writes a subject with RFC 4514 escaping, keeping certificate order, e.g.
+/// `O=Acme,CN=ops-client`.
+pub fn format_subject(subject: &Subject) -> String {
+    subject
+        .iter()
+        .map(|rdn| {
+            rdn.iter()
+                .map(|(kind, value)| format!("{}={}", kind, escape_value(value)))
+                .collect::<Vec<_>>()
+                .join("+")
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn escape_value(value: &str) -> String {
+    let last = value.chars().count().saturating_sub(1);
+    let mut escaped = String::with_capacity(value.len());
+    for (i, c) in value.chars().enumerate() {
+        match c {
+            '"' | '+' | ',' | ';' | '<' | '>' | '\\' => escaped.push('\\'),
+            '#' if i == 0 => escaped.push('\\'),
+            ' ' if i == 0 || i == last => escaped.push('\\'),
+            '\0' => {
+                escaped.push_str("\\00");
+                continue;
+            }
+            _ => {}
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Parses a subject written as in `format_subject`: attributes separated by
+/// unescaped `,` (or `+` within one RDN), values escaped per RFC 4514.
+pub fn parse_subject(text: &str) -> Result<Subject, String> {
+    let mut subject: Subject = vec![Vec::new()];
+    let mut kind: Option<String> = None;
+    let mut token = Vec::new();
+    let mut bytes = text.bytes();
+    let finish = |kind: &mut Option<String>, token: &mut Vec<u8>, subject: &mut Subject| {
+        let Some(kind) = kind.take() else {
+            return Err(format!("'{}' has no '='", String::from_utf8_lossy(token).trim()));
+        };
+        let value = String::from_utf8(std::mem::take(token)).map_err(|_| "value is not UTF-8".to_string())?;
+        subject.last_mut().unwrap().push((attribute_type(&kind)?, value));
+        Ok::<(), String>(())
+    };
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'\\' if kind.is_some() => {
+                let next = bytes.next().ok_or("dangling '\\' at the end")?;
+                if next.is_ascii_hexdigit() {
+                    let low = bytes.next().filter(u8::is_ascii_hexdigit).ok_or("'\\' needs two hex digits")?;
+                    let hex = [next, low];
+                    let hex = std::str::from_utf8(&hex).unwrap();
+                    token.push(u8::from_str_radix(hex, 16).unwrap());
+                } else if b"\"+,;<>\\ #=".contains(&next) {
+                    token.push(next);
+                } else {
+                    return Err(format!("'\\{}' is not a valid escape", next as char));
+                }
+            }
+            b'=' if kind.is_none() => kind = Some(String::from_utf8_lossy(&std::mem::take(&mut token)).into_owned()),
+            b',' | b'+' => {
+                finish(&mut kind, &mut token, &mut subject)?;
+                if byte == b',' {
+                    subject.push(Vec::new());
+                }
+            }
+            _ => token.push(byte),
+        }
+    }
+    finish(&mut kind, &mut token, &mut subject)?;
+    Ok(subject)
+}
+
+fn attribute_type(kind: &str) -> Result<String, String> {
+    let kind = kind.trim();
+    if let Some((_, short)) = ATTRIBUTE_NAMES.iter().find(|(_, short)| short.eq_ignore_ascii_case(kind)) {
+        return Ok(short.to_string());
+    }
+    let mut arcs = kind.split('.');
+    let dotted = kind.contains('.') && arcs.all(|arc| !arc.is_empty() && arc.bytes().all(|b| b.is_ascii_digit()));
+    if dotted {
+        Ok(kind.to_string())
+    } else {
+        Err(format!("unknown attribute type '{}'", kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// DER for a Name with one attribute per RDN: (OID contents, UTF8String).
+    fn name_der(attributes: &[(&[u8], &str)]) -> Vec<u8> {
+        fn element(tag: u8, contents: &[u8]) -> Vec<u8> {
+            assert!(contents.len() < 0x80);
+            [&[tag, contents.len() as u8][..], contents].concat()
+        }
+        let rdns: Vec<u8> = attributes
+            .iter()
+            .flat_map(|(oid, value)| {
+                let attribute = [element(0x06, oid), element(0x0c, value.as_bytes())].concat();
+                element(0x31, &element(0x30, &attribute))
+            })
+            .collect();
+        element(0x30, &rdns)
+    }
+
+    const ORGANIZATION: &[u8] = &[0x55, 0x04, 0x0a];
+    const COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+    fn subject_of(attributes: &[(&[u8], &str)]) -> Subject {
+        let der = name_der(attributes);
+        let (_, name) = X509Name::from_der(&der).unwrap();
+        name_attributes(&name).unwrap()
+    }
+
+    fn subject(text: &str) -> Subject {
+        parse_subject(text).unwrap_or_else(|e| panic!("{}: {}", text, e))
+    }
+
+    fn ops_identities() -> Vec<(Subject, ClientIdentityConfig)> {
+        let mapped = ClientIdentityConfig { identity: "ops".to_string(), scopes: vec![Scope::Sign] };
+        vec![(subject("O=Acme,CN=ops-client"), mapped)]
+    }
+
+    #[test]
+    fn reads_subjects_in_certificate_order() {
+        let subject = subject_of(&[(ORGANIZATION, "Acme"), (COMMON_NAME, "ops-client")]);
+        assert_eq!(format_subject(&subject), "O=Acme,CN=ops-client");
+        let client = client_identity(subject, &ops_identities()).unwrap();
+        assert_eq!(client.identity.as_deref(), Some("ops"));
+        assert!(client.allows(Scope::Sign) && !client.allows(Scope::Keygen));
+    }
+
+    #[test]
+    fn separators_inside_values_cannot_forge_a_subject() {
+        let forged = subject_of(&[(ORGANIZATION, "Acme,CN=ops-client")]);
+        assert_eq!(format_subject(&forged), "O=Acme\\,CN=ops-client");
+        assert_ne!(forged, subject("O=Acme,CN=ops-client"));
+        let refused = client_identity(forged.clone(), &ops_identities()).expect_err("forged subject was mapped");
+        assert!(refused.contains("O=Acme\\,CN=ops-client"), "{}", refused);
+        assert_eq!(subject("O=Acme\\,CN=ops-client"), forged);
+    }
+
+    #[test]
+    fn unlisted_subjects_pass_only_without_a_mapping() {
+        let other = subject_of(&[(COMMON_NAME, "someone")]);
+        assert!(client_identity(other.clone(), &ops_identities()).is_err());
+        let client = client_identity(other, &[]).unwrap();
+        assert_eq!((client.subject.as_str(), client.identity), ("CN=someone", None));
+        assert!(client.scopes.is_empty());
+    }
+
+    #[test]
+    fn escapes_and_parses_per_rfc_4514() {
+        let leading_hash = vec![vec![("CN".to_string(), "#1".to_string())]];
+        assert_eq!(format_subject(&leading_hash), "CN=\\#1");
+        let tricky = vec![vec![("CN".to_string(), " #a+b;\"<c>\\ ".to_string())]];
+        let text = format_subject(&tricky);
+        assert_eq!(text, "CN=\\ #a\\+b\\;\\\"\\<c\\>\\\\\\ ");
+        assert_eq!(subject(&text), tricky);
+
+        let multi = subject("cn=caf\\C3\\A9+OU=x,2.5.4.10=Acme");
+        assert_eq!(
+            multi,
+            vec![
+                vec![("CN".to_string(), "café".to_string()), ("OU".to_string(), "x".to_string())],
+                vec![("2.5.4.10".to_string(), "Acme".to_string())],
+            ]
+        );
+        assert_eq!(format_subject(&multi), "CN=café+OU=x,2.5.4.10=Acme");
+    }
+
+    #[test]
+    fn rejects_malformed_subjects() {
+        for text in ["CN", "CN=a,", "XX=a", "CN=a\\q", "CN=a\\", "CN=\\4", "CN=\\FF"] {
+            assert!(parse_subject(text).is_err(), "{} was accepted", text);
+        }
+    }
+}
@@ -1,7 +1,11 @@
-use bs58;
-use base64;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use bip39::{Language, Mnemonic, Seed};
+use solana_sdk::derivation_path::DerivationPath;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signer::keypair::{keypair_from_seed, keypair_from_seed_and_derivation_path};
 use std::str::FromStr;
+use crate::error::{ApiError, ErrorCode};
+use crate::openapi::ApiSchema;
 
 pub fn decode_base58(s: &str) -> Result<Vec<u8>, String> {
     bs58::decode(s).into_vec().map_err(|e| e.to_string())
@@ -12,23 +16,338 @@ pub fn encode_base58(bytes: &[u8]) -> String {
 }
 
 pub fn encode_base64(bytes: &[u8]) -> String {
-    base64::encode(bytes)
+    STANDARD.encode(bytes)
 }
 
 pub fn decode_base64(s: &str) -> Result<Vec<u8>, String> {
-    base64::decode(s).map_err(|e| e.to_string())
+    STANDARD.decode(s).map_err(|e| e.to_string())
+}
+
+pub fn decode_hex(s: &str) -> Result<Vec<u8>, String> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    hex::decode(s).map_err(|e| e.to_string())
 }
 
 pub fn validate_pubkey(pubkey: &str) -> Result<Pubkey, String> {
     Pubkey::from_str(pubkey).map_err(|e| format!("Invalid pubkey: {}", e))
 }
 
-pub fn validate_private_key(secret: &str) -> Result<[u8; 64], String> {
-    let decoded = decode_base58(secret)?;
-    if decoded.len() != 64 {
-        return Err("Private key must be 64 bytes".to_string());
+/// How a message is carried in a JSON string field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, ApiSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageEncoding {
+    #[default]
+    Utf8,
+    Base64,
+    Hex,
+    Base58,
+}
+
+impl MessageEncoding {
+    pub fn decode(self, s: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Utf8 => Ok(s.as_bytes().to_vec()),
+            Self::Base64 => decode_base64(s),
+            Self::Hex => decode_hex(s),
+            Self::Base58 => decode_base58(s),
+        }
+    }
+}
+
+/// How a signature is carried in a JSON string field. Base64 is the default for
+/// compatibility; base58 is what the rest of Solana uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, ApiSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureEncoding {
+    #[default]
+    Base64,
+    Base58,
+    Hex,
+}
+
+impl SignatureEncoding {
+    pub fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            Self::Base64 => encode_base64(bytes),
+            Self::Base58 => encode_base58(bytes),
+            Self::Hex => hex::encode(bytes),
+        }
+    }
+
+    pub fn decode(self, s: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Base64 => decode_base64(s),
+            Self::Base58 => decode_base58(s),
+            Self::Hex => decode_hex(s),
+        }
+    }
+}
+
+/// Default BIP44 path for mnemonics, the first account as derived by Phantom and
+/// `solana-keygen recover 'prompt://?key=0/0'`.
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/501'/0'/0'";
+
+/// Special `derivation_path` value selecting `solana-keygen new`'s derivation,
+/// which uses the first 32 bytes of the BIP39 seed directly.
+pub const LEGACY_DERIVATION_PATH: &str = "legacy";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, ApiSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretFormat {
+    Base58Keypair,
+    Base58Seed,
+    HexKeypair,
+    HexSeed,
+    Base64Keypair,
+    Base64Seed,
+    JsonByteArray,
+    Bip39Mnemonic,
+}
+
+pub struct DecodedSecret {
+    pub bytes: [u8; 64],
+    pub format: SecretFormat,
+}
+
+/// Decodes a secret key in any format we accept and returns the 64-byte
+/// keypair (seed followed by pubkey) plus the format that was detected:
+///
+/// - a 64-byte keypair or 32-byte seed in base58, hex or base64
+/// - a solana-keygen `id.json` byte array
+/// - a BIP39 mnemonic, with an optional passphrase and derivation path
+pub fn validate_private_key(
+    secret: &str,
+    passphrase: Option<&str>,
+    derivation_path: Option<&str>,
+) -> Result<DecodedSecret, ApiError> {
+    let invalid = |message: String| ApiError::new(ErrorCode::InvalidSecretKey, format!("Invalid private key: {}", message));
+    let secret = secret.trim();
+
+    // A JSON byte array may be spaced out, so it is recognised before mnemonics.
+    if !secret.starts_with('[') && secret.split_whitespace().count() > 1 {
+        let bytes = keypair_from_mnemonic(
+            secret,
+            passphrase.unwrap_or(""),
+            derivation_path.unwrap_or(DEFAULT_DERIVATION_PATH),
+        )
+        .map_err(|e| ApiError::new(ErrorCode::InvalidMnemonic, format!("Invalid private key: {}", e)))?;
+        return Ok(DecodedSecret { bytes, format: SecretFormat::Bip39Mnemonic });
+    }
+
+    if passphrase.is_some() || derivation_path.is_some() {
+        return Err(ApiError::invalid_request(
+            "Invalid private key: passphrase and derivation_path only apply to mnemonic secrets",
+        ));
+    }
+
+    let (decoded, keypair_format, seed_format) = if secret.starts_with('[') {
+        let bytes: Vec<u8> = serde_json::from_str(secret)
+            .map_err(|e| invalid(format!("Invalid JSON byte array: {}", e)))?;
+        (bytes, SecretFormat::JsonByteArray, SecretFormat::JsonByteArray)
+    } else if is_hex_key(secret) {
+        (decode_hex(secret).map_err(invalid)?, SecretFormat::HexKeypair, SecretFormat::HexSeed)
+    } else if let Some(bytes) = decode_base58(secret).ok().filter(|b| b.len() == 32 || b.len() == 64) {
+        (bytes, SecretFormat::Base58Keypair, SecretFormat::Base58Seed)
+    } else if let Some(bytes) = decode_base64(secret).ok().filter(|b| b.len() == 32 || b.len() == 64) {
+        (bytes, SecretFormat::Base64Keypair, SecretFormat::Base64Seed)
+    } else {
+        return Err(invalid("Private key must be 64 bytes (or a 32-byte seed) in base58, hex, base64 or a JSON byte array, or a BIP39 mnemonic".to_string()));
+    };
+
+    match decoded.len() {
+        64 => {
+            let bytes = keypair_from_seed_bytes(&decoded[..32]).map_err(invalid)?;
+            if bytes[32..] != decoded[32..] {
+                return Err(invalid("Public key half of the secret does not match its seed".to_string()));
+            }
+            Ok(DecodedSecret { bytes, format: keypair_format })
+        }
+        32 => Ok(DecodedSecret { bytes: keypair_from_seed_bytes(&decoded).map_err(invalid)?, format: seed_format }),
+        len => Err(ApiError::new(
+            ErrorCode::InvalidSecretLength,
+            format!("Invalid private key: Private key must be 64 bytes or a 32-byte seed, got {} bytes", len),
+        )),
+    }
+}
+
+fn is_hex_key(s: &str) -> bool {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    (s.len() == 64 || s.len() == 128) && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn keypair_from_seed_bytes(seed: &[u8]) -> Result<[u8; 64], String> {
+    keypair_from_seed(seed)
+        .map(|keypair| keypair.to_bytes())
+        .map_err(|e| format!("Invalid seed: {}", e))
+}
+
+/// Derives a keypair from a BIP39 mnemonic. `derivation_path` is an absolute
+/// SLIP-0010 path such as `m/44'/501'/0'/0'`, or [`LEGACY_DERIVATION_PATH`].
+pub fn keypair_from_mnemonic(
+    phrase: &str,
+    passphrase: &str,
+    derivation_path: &str,
+) -> Result<[u8; 64], String> {
+    let normalized = phrase.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    let mnemonic = Mnemonic::from_phrase(&normalized, Language::English)
+        .map_err(|e| format!("Invalid mnemonic: {}", e))?;
+    let seed = Seed::new(&mnemonic, passphrase);
+
+    let keypair = if derivation_path == LEGACY_DERIVATION_PATH {
+        keypair_from_seed(seed.as_bytes())
+    } else {
+        let path = DerivationPath::from_absolute_path_str(derivation_path)
+            .map_err(|e| format!("Invalid derivation path: {}", e))?;
+        keypair_from_seed_and_derivation_path(seed.as_bytes(), Some(path))
+    }
+    .map_err(|e| format!("Key derivation failed: {}", e))?;
+
+    Ok(keypair.to_bytes())
+}
+
+/// Parses a pubkey given as base58, hex (64 chars, optional `0x`) or base64,
+/// returning the key together with the name of the encoding that matched.
+pub fn parse_pubkey_any(s: &str) -> Result<(Pubkey, &'static str), String> {
+    let s = s.trim();
+    let unprefixed = s.strip_prefix("0x").unwrap_or(s);
+    if unprefixed.len() == 64 && unprefixed.chars().all(|c| c.is_ascii_hexdigit()) {
+        let bytes = decode_hex(unprefixed)?;
+        return pubkey_from_slice(&bytes).map(|pk| (pk, "hex"));
+    }
+    if let Ok(bytes) = decode_base58(s) {
+        if bytes.len() == 32 {
+            return pubkey_from_slice(&bytes).map(|pk| (pk, "base58"));
+        }
+    }
+    if let Ok(bytes) = decode_base64(s) {
+        if bytes.len() == 32 {
+            return pubkey_from_slice(&bytes).map(|pk| (pk, "base64"));
+        }
+    }
+    Err("Pubkey must be 32 bytes encoded as base58, hex or base64".to_string())
+}
+
+pub fn pubkey_from_slice(bytes: &[u8]) -> Result<Pubkey, String> {
+    let array: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| format!("Pubkey must be 32 bytes, got {}", bytes.len()))?;
+    Ok(Pubkey::new_from_array(array))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn known_keypair() -> [u8; 64] {
+        keypair_from_seed(&[7u8; 32]).unwrap().to_bytes()
+    }
+
+    fn detect(secret: &str) -> Result<DecodedSecret, ApiError> {
+        validate_private_key(secret, None, None)
+    }
+
+    fn rejection(secret: &str) -> ErrorCode {
+        detect(secret).err().expect("secret should be rejected").code
+    }
+
+    #[test]
+    fn detects_every_keypair_and_seed_encoding() {
+        let keypair = known_keypair();
+        let (seed, json) = (&keypair[..32], serde_json::to_string(&keypair.to_vec()).unwrap());
+        let cases = [
+            (encode_base58(&keypair), SecretFormat::Base58Keypair),
+            (encode_base58(seed), SecretFormat::Base58Seed),
+            (hex::encode(keypair), SecretFormat::HexKeypair),
+            (format!("0x{}", hex::encode(seed)), SecretFormat::HexSeed),
+            (encode_base64(&keypair), SecretFormat::Base64Keypair),
+            (encode_base64(seed), SecretFormat::Base64Seed),
+            (json.replace(',', ", "), SecretFormat::JsonByteArray),
+            (json, SecretFormat::JsonByteArray),
+        ];
+        for (secret, format) in cases {
+            let decoded = detect(&format!("  {}\n", secret)).unwrap_or_else(|e| panic!("{}: {}", secret, e));
+            assert_eq!(decoded.format, format, "{}", secret);
+            assert_eq!(decoded.bytes, keypair, "{}", secret);
+        }
+    }
+
+    #[test]
+    fn rejects_keypair_whose_pubkey_does_not_match_its_seed() {
+        let mut keypair = known_keypair();
+        keypair[63] ^= 1;
+        assert_eq!(rejection(&encode_base58(&keypair)), ErrorCode::InvalidSecretKey);
+    }
+
+    #[test]
+    fn rejects_wrong_lengths_and_garbage() {
+        assert_eq!(rejection("[1, 2, 3]"), ErrorCode::InvalidSecretLength);
+        assert_eq!(rejection(&encode_base58(&[1u8; 40])), ErrorCode::InvalidSecretKey);
+        assert_eq!(rejection("not a key"), ErrorCode::InvalidMnemonic);
+        assert_eq!(rejection(""), ErrorCode::InvalidSecretKey);
     }
-    let mut key = [0u8; 64];
-    key.copy_from_slice(&decoded);
-    Ok(key)
-} 
\ No newline at end of file
+
+    #[test]
+    fn passphrase_only_applies_to_mnemonics() {
+        let secret = encode_base58(&known_keypair());
+        let error = validate_private_key(&secret, Some("pass"), None).err().expect("passphrase should be refused");
+        assert_eq!(error.code, ErrorCode::InvalidRequest);
+    }
+
+    #[test]
+    fn parses_pubkeys_in_any_encoding() {
+        let pubkey = Pubkey::new_from_array([9u8; 32]);
+        assert_eq!(parse_pubkey_any(&pubkey.to_string()).unwrap(), (pubkey, "base58"));
+        assert_eq!(parse_pubkey_any(&hex::encode(pubkey)).unwrap(), (pubkey, "hex"));
+        assert_eq!(parse_pubkey_any(&encode_base64(pubkey.as_ref())).unwrap(), (pubkey, "base64"));
+        assert!(parse_pubkey_any("abc").is_err());
+    }
+
+    const ABANDON_ABOUT: &str =
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn pubkey_of(keypair: [u8; 64]) -> String {
+        encode_base58(&keypair[32..])
+    }
+
+    #[test]
+    fn derives_bip44_accounts_from_mnemonics() {
+        let derived = keypair_from_mnemonic(ABANDON_ABOUT, "", DEFAULT_DERIVATION_PATH).unwrap();
+        assert_eq!(pubkey_of(derived), "HAgk14JpMQLgt6rVgv7cBQFJWFto5Dqxi472uT3DKpqk");
+        // The BIP39 seed for this phrase and passphrase is the reference
+        // vector c55257c3...; the path is then derived with SLIP-0010.
+        let derived = keypair_from_mnemonic(ABANDON_ABOUT, "TREZOR", DEFAULT_DERIVATION_PATH).unwrap();
+        assert_eq!(pubkey_of(derived), "7zSmbu6gKkb6HB7UDPtHYjwCWuBHU1D4TpNZFm4sndQe");
+    }
+
+    #[test]
+    fn legacy_derivation_uses_the_seed_directly() {
+        let derived = keypair_from_mnemonic(ABANDON_ABOUT, "", LEGACY_DERIVATION_PATH).unwrap();
+        assert_eq!(pubkey_of(derived), "EHqmfkN89RJ7Y33CXM6uCzhVeuywHoJXZZLszBHHZy7o");
+        let derived = keypair_from_mnemonic(ABANDON_ABOUT, "TREZOR", LEGACY_DERIVATION_PATH).unwrap();
+        assert_eq!(pubkey_of(derived), "6UChi37U4BGomEQR665JZNjTM2PiHcUmBCD3e7TD366v");
+    }
+
+    #[test]
+    fn mnemonics_are_normalized_before_derivation() {
+        let messy = format!("  {}  ", ABANDON_ABOUT.to_uppercase().replace(' ', "\n "));
+        assert_eq!(
+            keypair_from_mnemonic(&messy, "", DEFAULT_DERIVATION_PATH).unwrap(),
+            keypair_from_mnemonic(ABANDON_ABOUT, "", DEFAULT_DERIVATION_PATH).unwrap()
+        );
+    }
+
+    #[test]
+    fn mnemonic_secrets_honour_passphrase_and_path() {
+        let decoded = validate_private_key(ABANDON_ABOUT, Some("TREZOR"), Some(LEGACY_DERIVATION_PATH))
+            .unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(decoded.format, SecretFormat::Bip39Mnemonic);
+        assert_eq!(pubkey_of(decoded.bytes), "6UChi37U4BGomEQR665JZNjTM2PiHcUmBCD3e7TD366v");
+    }
+
+    #[test]
+    fn rejects_bad_mnemonics_and_paths() {
+        let bad_checksum = ABANDON_ABOUT.replace("about", "abandon");
+        assert!(keypair_from_mnemonic(&bad_checksum, "", DEFAULT_DERIVATION_PATH).is_err());
+        assert!(keypair_from_mnemonic(ABANDON_ABOUT, "", "m/44'/nope").is_err());
+    }
+}
@@ -0,0 +1,492 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::{Keypair, Signer};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::config::VaultConfig;
+use crate::keystore::{open, random_bytes, seal};
+use crate::utils::{decode_base64, decode_hex, encode_base64};
+use crate::openapi::ApiSchema;
+
+pub const VAULT_FILE_VERSION: u32 = 1;
+
+/// On-disk vault: key metadata in clear, every secret sealed under the
+/// master key with its key ID, version and pubkey as associated data.
+#[derive(Serialize, Deserialize, Default)]
+struct VaultFile {
+    version: u32,
+    keys: Vec<StoredKey>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct StoredKey {
+    id: String,
+    label: Option<String>,
+    created_at: u64,
+    disabled: bool,
+    /// All versions of the key, oldest first; only the last one signs.
+    versions: Vec<StoredKeyVersion>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct StoredKeyVersion {
+    version: u32,
+    pubkey: String,
+    created_at: u64,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Serialize, ApiSchema)]
+pub struct KeyInfo {
+    pub id: String,
+    pub label: Option<String>,
+    #[schema(format = "base58", example = "FuCUNPtGfJ4tK9r6toq9ySop6nQ1pZ2XkuRDku1H9ZTT")]
+    pub pubkey: String,
+    pub version: u32,
+    pub disabled: bool,
+    pub created_at: u64,
+    pub rotated_at: Option<u64>,
+    /// Pubkeys of retired versions, oldest first.
+    #[schema(format = "base58")]
+    pub previous_pubkeys: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum VaultError {
+    NotConfigured,
+    NotFound(String),
+    Disabled(String),
+    Storage(String),
+}
+
+impl std::fmt::Display for VaultError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VaultError::NotConfigured => write!(f, "Key vault is not configured (set VAULT_MASTER_KEY)"),
+            VaultError::NotFound(id) => write!(f, "Key '{}' not found", id),
+            VaultError::Disabled(id) => write!(f, "Key '{}' is disabled", id),
+            VaultError::Storage(e) => write!(f, "Key vault storage error: {}", e),
+        }
+    }
+}
+
+struct VaultInner {
+    path: PathBuf,
+    master_key: [u8; 32],
+    file: Mutex<VaultFile>,
+}
+
+/// Local key vault. Secrets are generated or imported once and afterwards only
+/// referenced by key ID, so they never travel in request bodies again.
+#[derive(Default)]
+pub struct KeyVault {
+    inner: Option<VaultInner>,
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn associated_data(id: &str, version: u32, pubkey: &str) -> Vec<u8> {
+    format!("solana-vault:{}:{}:{}", id, version, pubkey).into_bytes()
+}
+
+/// Parses a 32-byte master key given as base64 or hex.
+pub fn parse_master_key(encoded: &str) -> Result<[u8; 32], String> {
+    let encoded = encoded.trim();
+    let bytes = decode_hex(encoded)
+        .ok()
+        .filter(|b| b.len() == 32)
+        .or_else(|| decode_base64(encoded).ok())
+        .ok_or_else(|| "master key must be 32 bytes in base64 or hex".to_string())?;
+    bytes
+        .as_slice()
+        .try_into()
+        .map_err(|_| format!("master key must be 32 bytes, got {}", bytes.len()))
+}
+
+impl KeyInfo {
+    fn from_stored(key: &StoredKey) -> Self {
+        let current = key.versions.last().expect("stored keys always have a version");
+        KeyInfo {
+            id: key.id.clone(),
+            label: key.label.clone(),
+            pubkey: current.pubkey.clone(),
+            version: current.version,
+            disabled: key.disabled,
+            created_at: key.created_at,
+            rotated_at: (key.versions.len() > 1).then_some(current.created_at),
+            previous_pubkeys: key.versions[..key.versions.len() - 1]
+                .iter()
+                .map(|v| v.pubkey.clone())
+                .collect(),
+        }
+    }
+}
+
+impl KeyVault {
+    /// Opens the vault at `path`, creating an empty one on first write.
+    pub fn open(path: impl AsRef<Path>, master_key: [u8; 32]) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        let file = if path.exists() {
+            let contents = fs::read(&path).map_err(|e| format!("reading {}: {}", path.display(), e))?;
+            let file: VaultFile = serde_json::from_slice(&contents)
+                .map_err(|e| format!("parsing {}: {}", path.display(), e))?;
+            if file.version != VAULT_FILE_VERSION {
+                return Err(format!("unsupported vault file version {}", file.version));
+            }
+            file
+        } else {
+            VaultFile { version: VAULT_FILE_VERSION, keys: Vec::new() }
+        };
+
+        let vault = KeyVault {
+            inner: Some(VaultInner { path, master_key, file: Mutex::new(file) }),
+        };
+        vault.check_master_key()?;
+        Ok(vault)
+    }
+
+    /// Opens the vault at the configured path with the master key from
+    /// `VAULT_MASTER_KEY`. Without a master key the vault is disabled and its
+    /// endpoints report so.
+    pub fn from_config(config: &VaultConfig) -> Result<Self, String> {
+        let Ok(master_key) = std::env::var("VAULT_MASTER_KEY") else {
+            return Ok(KeyVault::default());
+        };
+        let master_key = parse_master_key(&master_key).map_err(|e| format!("VAULT_MASTER_KEY: {}", e))?;
+        KeyVault::open(&config.path, master_key)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    fn inner(&self) -> Result<&VaultInner, VaultError> {
+        self.inner.as_ref().ok_or(VaultError::NotConfigured)
+    }
+
+    /// Fails fast at startup if the vault was written under another master key.
+    fn check_master_key(&self) -> Result<(), String> {
+        let inner = self.inner().map_err(|e| e.to_string())?;
+        let file = inner.file.lock().unwrap();
+        if let Some(key) = file.keys.first() {
+            let version = key.versions.last().expect("stored keys always have a version");
+            inner
+                .decrypt(&key.id, version)
+                .map_err(|_| "master key does not match the existing vault".to_string())?;
+        }
+        Ok(())
+    }
+
+    pub fn create(&self, label: Option<String>, keypair: Option<[u8; 64]>) -> Result<KeyInfo, VaultError> {
+        let inner = self.inner()?;
+        let keypair = keypair.unwrap_or_else(|| Keypair::new().to_bytes());
+        let id = format!("key_{}", hex::encode(random_bytes::<8>()));
+        let created_at = now();
+
+        let mut file = inner.file.lock().unwrap();
+        let stored = StoredKey {
+            id: id.clone(),
+            label,
+            created_at,
+            disabled: false,
+            versions: vec![inner.seal_version(&id, 1, &keypair, created_at)?],
+        };
+        let info = KeyInfo::from_stored(&stored);
+        file.keys.push(stored);
+        if let Err(e) = inner.persist(&file) {
+            file.keys.pop();
+            return Err(e);
+        }
+        Ok(info)
+    }
+
+    pub fn list(&self) -> Result<Vec<KeyInfo>, VaultError> {
+        let inner = self.inner()?;
+        let file = inner.file.lock().unwrap();
+        Ok(file.keys.iter().map(KeyInfo::from_stored).collect())
+    }
+
+    pub fn get(&self, id: &str) -> Result<KeyInfo, VaultError> {
+        let inner = self.inner()?;
+        let file = inner.file.lock().unwrap();
+        find(&file, id).map(KeyInfo::from_stored)
+    }
+
+    /// Replaces the key's signing keypair with a freshly generated one. The
+    /// old version is retained, but no longer used for signing.
+    pub fn rotate(&self, id: &str) -> Result<KeyInfo, VaultError> {
+        let inner = self.inner()?;
+        let mut file = inner.file.lock().unwrap();
+        let index = position(&file, id)?;
+        let next_version = file.keys[index].versions.last().map_or(1, |v| v.version + 1);
+        let keypair = Keypair::new().to_bytes();
+        let version = inner.seal_version(id, next_version, &keypair, now())?;
+        file.keys[index].versions.push(version);
+        if let Err(e) = inner.persist(&file) {
+            file.keys[index].versions.pop();
+            return Err(e);
+        }
+        Ok(KeyInfo::from_stored(&file.keys[index]))
+    }
+
+    pub fn set_disabled(&self, id: &str, disabled: bool) -> Result<KeyInfo, VaultError> {
+        let inner = self.inner()?;
+        let mut file = inner.file.lock().unwrap();
+        let index = position(&file, id)?;
+        let was_disabled = std::mem::replace(&mut file.keys[index].disabled, disabled);
+        if let Err(e) = inner.persist(&file) {
+            file.keys[index].disabled = was_disabled;
+            return Err(e);
+        }
+        Ok(KeyInfo::from_stored(&file.keys[index]))
+    }
+
+    pub fn delete(&self, id: &str) -> Result<KeyInfo, VaultError> {
+        let inner = self.inner()?;
+        let mut file = inner.file.lock().unwrap();
+        let index = position(&file, id)?;
+        let removed = file.keys.remove(index);
+        if let Err(e) = inner.persist(&file) {
+            file.keys.insert(index, removed);
+            return Err(e);
+        }
+        Ok(KeyInfo::from_stored(&removed))
+    }
+
+    /// Decrypts the current version of an enabled key for signing.
+    pub fn signing_keypair(&self, id: &str) -> Result<[u8; 64], VaultError> {
+        let inner = self.inner()?;
+        let file = inner.file.lock().unwrap();
+        let key = find(&file, id)?;
+        if key.disabled {
+            return Err(VaultError::Disabled(id.to_string()));
+        }
+        let version = key.versions.last().expect("stored keys always have a version");
+        inner.decrypt(id, version)
+    }
+}
+
+fn position(file: &VaultFile, id: &str) -> Result<usize, VaultError> {
+    file.keys
+        .iter()
+        .position(|k| k.id == id)
+        .ok_or_else(|| VaultError::NotFound(id.to_string()))
+}
+
+fn find<'a>(file: &'a VaultFile, id: &str) -> Result<&'a StoredKey, VaultError> {
+    position(file, id).map(|index| &file.keys[index])
+}
+
+impl VaultInner {
+    fn seal_version(
+        &self,
+        id: &str,
+        version: u32,
+        keypair: &[u8; 64],
+        created_at: u64,
+    ) -> Result<StoredKeyVersion, VaultError> {
+        let pubkey = bs58::encode(&keypair[32..]).into_string();
+        let (nonce, ciphertext) = seal(&self.master_key, keypair, &associated_data(id, version, &pubkey))
+            .map_err(VaultError::Storage)?;
+        Ok(StoredKeyVersion {
+            version,
+            pubkey,
+            created_at,
+            nonce: encode_base64(&nonce),
+            ciphertext: encode_base64(&ciphertext),
+        })
+    }
+
+    fn decrypt(&self, id: &str, version: &StoredKeyVersion) -> Result<[u8; 64], VaultError> {
+        let nonce = decode_base64(&version.nonce).map_err(VaultError::Storage)?;
+        let ciphertext = decode_base64(&version.ciphertext).map_err(VaultError::Storage)?;
+        let plaintext = open(
+            &self.master_key,
+            &nonce,
+            &ciphertext,
+            &associated_data(id, version.version, &version.pubkey),
+        )
+        .map_err(VaultError::Storage)?;
+        let keypair: [u8; 64] = plaintext
+            .as_slice()
+            .try_into()
+            .map_err(|_| VaultError::Storage("stored key has the wrong length".to_string()))?;
+        let pubkey = Keypair::from_bytes(&keypair)
+            .map_err(|e| VaultError::Storage(e.to_string()))?
+            .pubkey();
+        if pubkey.to_string() != version.pubkey {
+            return Err(VaultError::Storage("stored key does not match its pubkey".to_string()));
+        }
+        Ok(keypair)
+    }
+
+    /// Writes the whole vault to a temporary file and renames it into place,
+    /// so a crash mid-write never leaves a truncated vault behind.
+    fn persist(&self, file: &VaultFile) -> Result<(), VaultError> {
+        let contents = serde_json::to_vec_pretty(file).map_err(|e| VaultError::Storage(e.to_string()))?;
+        let tmp = self.path.with_extension("json.tmp");
+        write_private(&tmp, &contents).map_err(|e| VaultError::Storage(e.to_string()))?;
+        fs::rename(&tmp, &self.path).map_err(|e| VaultError::Storage(e.to_string()))
+    }
+}
+
+#[cfg(unix)]
+pub fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+#[cfg(not(unix))]
+pub fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ApiError, ErrorCode};
+
+    /// A vault whose file lives in a directory that doesn't exist, so every
+    /// write fails.
+    fn unwritable_vault() -> KeyVault {
+        let path = std::env::temp_dir().join(format!("missing-{}", hex::encode(random_bytes::<8>()))).join("vault.json");
+        KeyVault::open(path, [5u8; 32]).unwrap()
+    }
+
+    fn seed(vault: &KeyVault) -> KeyInfo {
+        let inner = vault.inner.as_ref().unwrap();
+        let mut file = inner.file.lock().unwrap();
+        let stored = StoredKey {
+            id: "key_seeded".to_string(),
+            label: None,
+            created_at: 0,
+            disabled: false,
+            versions: vec![inner.seal_version("key_seeded", 1, &Keypair::new().to_bytes(), 0).unwrap()],
+        };
+        let info = KeyInfo::from_stored(&stored);
+        file.keys.push(stored);
+        info
+    }
+
+    #[test]
+    fn failed_writes_leave_the_vault_unchanged() {
+        let vault = unwritable_vault();
+        let key = seed(&vault);
+
+        assert!(vault.create(None, None).is_err());
+        assert!(vault.rotate(&key.id).is_err());
+        assert!(vault.set_disabled(&key.id, true).is_err());
+        assert!(vault.delete(&key.id).is_err());
+
+        let keys = vault.list().unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].pubkey, key.pubkey);
+        assert_eq!(keys[0].version, 1);
+        assert!(!keys[0].disabled);
+        assert!(vault.signing_keypair(&key.id).is_ok());
+    }
+
+    fn vault_path() -> PathBuf {
+        std::env::temp_dir().join(format!("vault-{}.json", hex::encode(random_bytes::<8>())))
+    }
+
+    fn sign(vault: &KeyVault, id: &str, message: &[u8]) -> (String, solana_sdk::signature::Signature) {
+        let keypair = Keypair::from_bytes(&vault.signing_keypair(id).unwrap()).unwrap();
+        (keypair.pubkey().to_string(), keypair.sign_message(message))
+    }
+
+    #[test]
+    fn keys_survive_reopening_from_disk() {
+        let path = vault_path();
+        let vault = KeyVault::open(&path, [7u8; 32]).unwrap();
+        let key = vault.create(Some("treasury".to_string()), None).unwrap();
+        assert_eq!((key.version, key.rotated_at), (1, None));
+        let (pubkey, signature) = sign(&vault, &key.id, b"hello");
+        assert_eq!(pubkey, key.pubkey);
+        drop(vault);
+
+        let vault = KeyVault::open(&path, [7u8; 32]).unwrap();
+        let reopened = vault.get(&key.id).unwrap();
+        assert_eq!((reopened.label.as_deref(), reopened.pubkey.as_str()), (Some("treasury"), key.pubkey.as_str()));
+        let (_, again) = sign(&vault, &key.id, b"hello");
+        assert_eq!(again, signature);
+        assert!(signature.verify(&bs58::decode(&key.pubkey).into_vec().unwrap(), b"hello"));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rotation_keeps_previous_pubkeys() {
+        let path = vault_path();
+        let vault = KeyVault::open(&path, [7u8; 32]).unwrap();
+        let key = vault.create(None, Some(Keypair::new().to_bytes())).unwrap();
+        let first = vault.rotate(&key.id).unwrap();
+        let second = vault.rotate(&key.id).unwrap();
+
+        assert_eq!(second.version, 3);
+        assert!(second.rotated_at.is_some());
+        assert_eq!(second.previous_pubkeys, [key.pubkey.clone(), first.pubkey.clone()]);
+        assert_eq!(sign(&vault, &key.id, b"m").0, second.pubkey);
+
+        let reopened = KeyVault::open(&path, [7u8; 32]).unwrap().get(&key.id).unwrap();
+        assert_eq!(reopened.previous_pubkeys, second.previous_pubkeys);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn disabled_keys_cannot_sign() {
+        let path = vault_path();
+        let vault = KeyVault::open(&path, [7u8; 32]).unwrap();
+        let key = vault.create(None, None).unwrap();
+        assert!(vault.set_disabled(&key.id, true).unwrap().disabled);
+
+        let err = vault.signing_keypair(&key.id).unwrap_err();
+        assert!(matches!(&err, VaultError::Disabled(id) if *id == key.id));
+        assert_eq!(ApiError::from(err).code, ErrorCode::KeyDisabled);
+        assert!(vault.get(&key.id).unwrap().disabled);
+
+        assert!(!vault.set_disabled(&key.id, false).unwrap().disabled);
+        assert!(vault.signing_keypair(&key.id).is_ok());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn deleted_keys_are_gone() {
+        let path = vault_path();
+        let vault = KeyVault::open(&path, [7u8; 32]).unwrap();
+        let kept = vault.create(None, None).unwrap();
+        let deleted = vault.create(None, None).unwrap();
+        assert_eq!(vault.delete(&deleted.id).unwrap().pubkey, deleted.pubkey);
+
+        assert!(matches!(vault.get(&deleted.id), Err(VaultError::NotFound(_))));
+        assert!(matches!(vault.signing_keypair(&deleted.id), Err(VaultError::NotFound(_))));
+        assert!(matches!(vault.delete(&deleted.id), Err(VaultError::NotFound(_))));
+        let ids: Vec<_> = KeyVault::open(&path, [7u8; 32]).unwrap().list().unwrap().into_iter().map(|k| k.id).collect();
+        assert_eq!(ids, [kept.id]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn wrong_master_key_fails_to_open() {
+        let path = vault_path();
+        KeyVault::open(&path, [7u8; 32]).unwrap().create(None, None).unwrap();
+
+        let err = KeyVault::open(&path, [8u8; 32]).err().unwrap();
+        assert_eq!(err, "master key does not match the existing vault");
+        assert!(KeyVault::open(&path, [7u8; 32]).is_ok());
+        fs::remove_file(path).unwrap();
+    }
+}
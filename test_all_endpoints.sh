@@ -7,8 +7,19 @@ YELLOW='\033[1;33m'
 BLUE='\033[0;34m'
 NC='\033[0m' # No Color
 
-# Base URL
-BASE_URL="http://127.0.0.1:3000"
+# Base URL, overridable from the environment
+BASE_URL="${BASE_URL:-http://127.0.0.1:3000}"
+
+# The server requires an API key with the build, sign and keygen scopes. Keys
+# are loaded at startup, so create one before starting the server:
+#   export API_KEY=$(cargo run -q -- --create-api-key smoke-test --scopes build,sign,keygen)
+# or start the server with --auth-enabled false and leave API_KEY unset.
+AUTH_HEADER=()
+if [ -n "$API_KEY" ]; then
+    AUTH_HEADER=(-H "X-API-Key: $API_KEY")
+else
+    echo -e "${YELLOW}⚠️  API_KEY is not set; requests will get 401 unless the server runs with --auth-enabled false${NC}"
+fi
 
 echo -e "${BLUE}🚀 Solana Fellowship API - Complete Endpoint Test${NC}"
 echo -e "${BLUE}================================================${NC}"
@@ -26,14 +37,14 @@ test_endpoint() {
     
     if [ -n "$data" ]; then
         echo -e "${YELLOW}Request Data: $data${NC}"
-        response=$(curl -s -X $method "$BASE_URL$endpoint" \
+        response=$(curl -s "${AUTH_HEADER[@]}" -X $method "$BASE_URL$endpoint" \
             -H "Content-Type: application/json" \
             -d "$data")
     else
-        response=$(curl -s -X $method "$BASE_URL$endpoint")
+        response=$(curl -s "${AUTH_HEADER[@]}" -X $method "$BASE_URL$endpoint")
     fi
     
-    if [ $? -eq 0 ]; then
+    if [[ "$response" == *'"success":true'* ]]; then
         echo -e "${GREEN}✅ Success${NC}"
         echo -e "${GREEN}Response: $response${NC}"
     else
@@ -46,8 +57,8 @@ test_endpoint() {
 # Test 1: Generate Keypair
 echo -e "${BLUE}1️⃣  Testing Generate Keypair${NC}"
 echo "----------------------------------------"
-keypair_response=$(curl -s -X POST "$BASE_URL/keypair")
-if [ $? -eq 0 ]; then
+keypair_response=$(curl -s "${AUTH_HEADER[@]}" -X POST "$BASE_URL/keypair")
+if [[ "$keypair_response" == *'"success":true'* ]]; then
     echo -e "${GREEN}✅ Keypair Generated Successfully${NC}"
     echo -e "${GREEN}Response: $keypair_response${NC}"
     
@@ -66,7 +77,7 @@ echo ""
 # Test 2: Create Token
 echo -e "${BLUE}2️⃣  Testing Create Token${NC}"
 echo "----------------------------------------"
-create_token_data="{\"mint_authority\":\"$pubkey\",\"mint\":\"$pubkey\",\"decimals\":6}"
+create_token_data="{\"mintAuthority\":\"$pubkey\",\"mint\":\"$pubkey\",\"decimals\":6}"
 test_endpoint "/token/create" "POST" "$create_token_data" "Create SPL Token Mint Instruction"
 echo ""
 
@@ -81,11 +92,11 @@ echo ""
 echo -e "${BLUE}4️⃣  Testing Sign Message${NC}"
 echo "----------------------------------------"
 sign_message_data="{\"message\":\"Hello, Solana!\",\"secret\":\"$secret\"}"
-sign_response=$(curl -s -X POST "$BASE_URL/message/sign" \
+sign_response=$(curl -s "${AUTH_HEADER[@]}" -X POST "$BASE_URL/message/sign" \
     -H "Content-Type: application/json" \
     -d "$sign_message_data")
 
-if [ $? -eq 0 ]; then
+if [[ "$sign_response" == *'"success":true'* ]]; then
     echo -e "${GREEN}✅ Message Signed Successfully${NC}"
     echo -e "${GREEN}Response: $sign_response${NC}"
     
@@ -110,7 +121,9 @@ echo ""
 # Test 6: Send SOL
 echo -e "${BLUE}6️⃣  Testing Send SOL${NC}"
 echo "----------------------------------------"
-send_sol_data="{\"from\":\"$pubkey\",\"to\":\"$pubkey\",\"lamports\":100000}"
+# The recipient must differ from the sender
+second_pubkey=$(curl -s "${AUTH_HEADER[@]}" -X POST "$BASE_URL/keypair" | grep -o '"pubkey":"[^"]*"' | cut -d'"' -f4)
+send_sol_data="{\"from\":\"$pubkey\",\"to\":\"$second_pubkey\",\"lamports\":100000}"
 test_endpoint "/send/sol" "POST" "$send_sol_data" "Create SOL Transfer Instruction"
 echo ""
 
@@ -135,6 +148,6 @@ echo -e "${BLUE}- 1 health check endpoint tested${NC}"
 echo -e "${BLUE}- All responses should show 'success: true'${NC}"
 echo ""
 echo -e "${YELLOW}💡 Tips:${NC}"
-echo -e "${YELLOW}- Make sure your server is running with: cargo run${NC}"
+echo -e "${YELLOW}- Make sure your server is running with: cargo run (and API_KEY is set, see the top of this script)${NC}"
 echo -e "${YELLOW}- Check the responses above for any errors${NC}"
 echo -e "${YELLOW}- All endpoints should return JSON with 'success' field${NC}" 
\ No newline at end of file